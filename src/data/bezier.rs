@@ -1,6 +1,6 @@
 #[macro_use]
 use serde_derive::*;
-use nalgebra_glm::{Mat4, Vec3};
+use nalgebra_glm::{Mat4, Vec3, Vec4};
 extern crate nalgebra;
 
 /// Data of a single bezier curve in a bezier patch
@@ -43,7 +43,11 @@ pub struct BezierPatchParameters {
     /// The four bezier curves that make up the patch
     pub curves: [BezierCurveParameters; 4],
     /// Color of this patch
-    pub color: Vec3
+    pub color: Vec3,
+    /// Whether the generated normals should be inverted. This is needed for patches
+    /// whose normals face away from the light, for example after mirroring.
+    #[serde(default)]
+    pub flip_normals: bool
 }
 
 impl BezierPatchParameters {
@@ -55,13 +59,15 @@ impl BezierPatchParameters {
                 BezierCurveParameters::empty(),
                 BezierCurveParameters::empty()
             ],
-            color: Vec3::new(0.7, 0.7, 0.7)
+            color: Vec3::new(0.7, 0.7, 0.7),
+            flip_normals: false
         }
     }
 
     pub fn default() -> BezierPatchParameters {
         BezierPatchParameters {
             color: Vec3::new(0.7, 0.7, 0.7),
+            flip_normals: false,
             curves: [
                 BezierCurveParameters {
                     control_points: [ Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.25, 0.0, 0.0), Vec3::new(0.75, 0.0, 0.0),  Vec3::new(1.0, 0.0, 0.0) ]
@@ -79,21 +85,28 @@ impl BezierPatchParameters {
         }
     }
 
-    /// Evalute the patch at given UV position in [0,1]x[0,1].
+    /// Evalute the patch at given UV position in [0,1]x[0,1].
+    /// When evaluating a lot of positions on the same patch, consider using a `PreparedPatch` instead.
     pub fn evaluate(&self, u: f32, v: f32) -> Vec3 {
-        let curve0 = &self.curves[0];
-        let curve1 = &self.curves[1];
-        let curve2 = &self.curves[2];
-        let curve3 = &self.curves[3];
+        PreparedPatch::new(self).evaluate(u, v)
+    }
 
-        let pt0 = curve0.evaluate(u);
-        let pt1 = curve1.evaluate(u);
-        let pt2 = curve2.evaluate(u);
-        let pt3 = curve3.evaluate(u);
+    /// Calculate the centroid of the control points of this patch.
+    pub fn centroid(&self) -> Vec3 {
+        let sum = self.curves.iter()
+            .flat_map(|c| c.control_points.iter())
+            .fold(Vec3::zeros(), |acc, p| acc + p);
 
-        let temp_curve = BezierCurveParameters::from_points([pt0, pt1, pt2, pt3]);
-        
-        temp_curve.evaluate(v)
+        sum / 16.0
+    }
+
+    /// Translate all control points of this patch by given offset.
+    pub fn translate(&mut self, offset: &Vec3) {
+        for curve in &mut self.curves {
+            for point in &mut curve.control_points {
+                *point += offset;
+            }
+        }
     }
 
     /// Clone this bezier patch, and apply mirroring on given mirror plane to the clone.
@@ -107,11 +120,118 @@ impl BezierPatchParameters {
                 point.component_mul_assign(&factors);
             }
         }
+
+        // Mirroring reverses the orientation of the surface, so the normals have to be flipped
+        // in order to still face the same way as the ones of the original patch.
+        match mirror_plane {
+            MirrorPlane::None => {},
+            _ => cloned.flip_normals = !cloned.flip_normals
+        }
     
         cloned
     }
 }
 
+/// A bezier patch in matrix form, ready for repeated evaluation. The constant bezier basis matrix
+/// is combined with the geometry matrices of the patch once on construction, which means that
+/// each evaluation only has to multiply the small parameter vectors.
+pub struct PreparedPatch {
+    /// The coefficient matrices for the x, y and z coordinates
+    coefficients: [Mat4; 3]
+}
+
+impl PreparedPatch {
+    /// Prepare given bezier patch for evaluation.
+    pub fn new(patch: &BezierPatchParameters) -> PreparedPatch {
+        let basis = Self::basis_matrix();
+
+        // Build the geometry matrix for given coordinate. Rows correspond to the curves (v),
+        // columns to the control points on each curve (u).
+        let geometry = |coord: usize| -> Mat4 {
+            let mut matrix = Mat4::zeros();
+
+            for (i, curve) in patch.curves.iter().enumerate() {
+                for (j, point) in curve.control_points.iter().enumerate() {
+                    matrix[(i, j)] = point[coord];
+                }
+            }
+
+            matrix
+        };
+
+        // The bezier basis matrix is symmetric, so there is no need to transpose it.
+        PreparedPatch {
+            coefficients: [
+                basis * geometry(0) * basis,
+                basis * geometry(1) * basis,
+                basis * geometry(2) * basis
+            ]
+        }
+    }
+
+    /// Evalute the patch at given UV position in [0,1]x[0,1].
+    pub fn evaluate(&self, u: f32, v: f32) -> Vec3 {
+        let u_vec = Self::parameter_vector(u);
+        let v_vec = Self::parameter_vector(v);
+
+        Vec3::new(
+            v_vec.dot(&(self.coefficients[0] * u_vec)),
+            v_vec.dot(&(self.coefficients[1] * u_vec)),
+            v_vec.dot(&(self.coefficients[2] * u_vec))
+        )
+    }
+
+    /// The power basis vector (t³, t², t, 1) for given parameter value.
+    fn parameter_vector(t: f32) -> Vec4 {
+        Vec4::new(t * t * t, t * t, t, 1.0)
+    }
+
+    /// The cubic bezier basis matrix.
+    fn basis_matrix() -> Mat4 {
+        Mat4::new(
+            -1.0,  3.0, -3.0, 1.0,
+             3.0, -6.0,  3.0, 0.0,
+            -3.0,  3.0,  0.0, 0.0,
+             1.0,  0.0,  0.0, 0.0
+        )
+    }
+}
+
+/// Write given bezier models to a standalone patch library file, which can later be imported into
+/// other lsystems. Patch libraries conventionally use the ".patches.json" extension.
+pub fn save_patch_library(path: &str, models: &[BezierModelParameters]) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(models)
+        .map_err(|e| format!("Could not serialize bezier models: {}", e))?;
+
+    std::fs::write(path, json).map_err(|e| format!("Could not write file \"{}\": {}", path, e))
+}
+
+/// Read all bezier models stored in given patch library file.
+pub fn load_patch_library(path: &str) -> Result<Vec<BezierModelParameters>, String> {
+    let json = std::fs::read_to_string(path)
+        .map_err(|e| format!("Could not read file \"{}\": {}", path, e))?;
+
+    serde_json::from_str(&json).map_err(|e| format!("Could not load \"{}\" as patch library: {}", path, e))
+}
+
+/// The materials the patch meshes of a bezier model can be rendered with.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[repr(u32)]
+pub enum BezierMaterial {
+    /// Unlit, using only the patch colors
+    Flat = 0,
+    /// Lit using the scene lighting
+    Shaded = 1,
+    /// Displays the surface normals as colors. Useful to spot flipped patches.
+    NormalColor = 2
+}
+
+impl Default for BezierMaterial {
+    fn default() -> Self {
+        BezierMaterial::Shaded
+    }
+}
+
 /// A collection of multiple bezier patch definitions which make up a whole
 /// model which can be used as part of an L-System.
 #[derive(Serialize, Deserialize, Clone)]
@@ -119,22 +239,106 @@ pub struct BezierModelParameters {
     /// The name this model can be referenced by in the L-System
     pub symbol: Option<char>,
     /// The parameters of the patches this model is made out of
-    pub patches: Vec<BezierPatchParameters>
+    pub patches: Vec<BezierPatchParameters>,
+    /// The material the patch meshes are rendered with
+    #[serde(default)]
+    pub material: BezierMaterial,
+    /// Faces meeting at an angle bigger than this, in degrees, do not share vertex normals, which keeps
+    /// creases sharp. At 180 degrees, all normals are smoothed.
+    #[serde(default = "BezierModelParameters::default_smoothing_angle")]
+    pub smoothing_angle: f32,
+    /// Number of rows and columns each patch is tessellated into
+    #[serde(default = "BezierModelParameters::default_resolution")]
+    pub resolution: u32
 }
 
 impl BezierModelParameters {
     pub fn empty() -> BezierModelParameters {
         BezierModelParameters {
             symbol: None,
-            patches: Vec::new()
+            patches: Vec::new(),
+            material: BezierMaterial::Shaded,
+            smoothing_angle: Self::default_smoothing_angle(),
+            resolution: Self::default_resolution()
         }
     }
 
+    /// The smoothing angle used by models that were saved before it could be configured.
+    fn default_smoothing_angle() -> f32 {
+        180.0
+    }
+
+    /// The tessellation resolution used by models that were saved before it could be configured.
+    fn default_resolution() -> u32 {
+        30
+    }
+
     /// Create a bezier patch that is just a flat plane in xy.
     pub fn default() -> BezierModelParameters {
         BezierModelParameters {
             symbol: None,
-            patches: vec![BezierPatchParameters::default()]
+            patches: vec![BezierPatchParameters::default()],
+            material: BezierMaterial::Shaded,
+            smoothing_angle: Self::default_smoothing_angle(),
+            resolution: Self::default_resolution()
+        }
+    }
+
+    /// Create a copy of this model with all patches mirrored on given plane. The copy has no symbol,
+    /// since it would otherwise collide with this model.
+    pub fn clone_mirrored(&self, mirror_plane: MirrorPlane) -> BezierModelParameters {
+        BezierModelParameters {
+            symbol: None,
+            patches: self.patches.iter().map(|patch| patch.clone_mirrored(mirror_plane)).collect(),
+            material: self.material,
+            smoothing_angle: self.smoothing_angle,
+            resolution: self.resolution
+        }
+    }
+
+    /// Uniformly scale all control points of this model by given factor, relative to the origin.
+    pub fn scale(&mut self, factor: f32) {
+        for patch in &mut self.patches {
+            for curve in &mut patch.curves {
+                for point in &mut curve.control_points {
+                    *point *= factor;
+                }
+            }
+        }
+    }
+
+    /// Calculate the centroid of all control points of this model. An empty model has its centroid
+    /// at the origin.
+    pub fn centroid(&self) -> Vec3 {
+        let mut sum = Vec3::zeros();
+        let mut count = 0;
+
+        for patch in &self.patches {
+            for curve in &patch.curves {
+                for point in &curve.control_points {
+                    sum += point;
+                    count += 1;
+                }
+            }
+        }
+
+        if count == 0 {
+            sum
+        } else {
+            sum / (count as f32)
+        }
+    }
+
+    /// Translate all control points of this model so that its centroid lies at the origin.
+    pub fn center(&mut self) {
+        let centroid = self.centroid();
+
+        for patch in &mut self.patches {
+            for curve in &mut patch.curves {
+                for point in &mut curve.control_points {
+                    *point -= centroid;
+                }
+            }
         }
     }
 }
@@ -160,4 +364,81 @@ impl MirrorPlane {
             Self::None => Vec3::new(1.0, 1.0, 1.0)
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::*;
+
+    /// A patch with distinct, non-planar control points, so that every coordinate matters.
+    fn sample_patch() -> BezierPatchParameters {
+        let mut patch = BezierPatchParameters::empty();
+
+        for (i, curve) in patch.curves.iter_mut().enumerate() {
+            for (j, point) in curve.control_points.iter_mut().enumerate() {
+                let (i, j) = (i as f32, j as f32);
+                *point = Vec3::new(j + 0.3 * i * i, i - 0.5 * j, (i * j).sin());
+            }
+        }
+
+        patch
+    }
+
+    /// Evaluate given patch by first evaluating each curve at u, and then the curve through the results at v.
+    fn evaluate_polynomial(patch: &BezierPatchParameters, u: f32, v: f32) -> Vec3 {
+        let points = [
+            patch.curves[0].evaluate(u),
+            patch.curves[1].evaluate(u),
+            patch.curves[2].evaluate(u),
+            patch.curves[3].evaluate(u)
+        ];
+
+        BezierCurveParameters::from_points(points).evaluate(v)
+    }
+
+    #[test]
+    fn patch_evaluation_matches_polynomial_form() {
+        let patch = sample_patch();
+
+        for &(u, v) in &[(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0), (0.5, 0.5), (0.2, 0.7)] {
+            assert!(abs_diff_eq!(patch.evaluate(u, v), evaluate_polynomial(&patch, u, v), epsilon = 1.0e-4));
+        }
+    }
+
+    #[test]
+    fn prepared_patch_matches_polynomial_form_on_grid() {
+        let patch = sample_patch();
+        let prepared = PreparedPatch::new(&patch);
+
+        for i in 0..=10 {
+            for j in 0..=10 {
+                let (u, v) = (i as f32 / 10.0, j as f32 / 10.0);
+                let expected = evaluate_polynomial(&patch, u, v);
+                let actual = prepared.evaluate(u, v);
+
+                assert!(abs_diff_eq!(actual, expected, epsilon = 1.0e-4), "Mismatch at ({}, {}): {} != {}", u, v, actual, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn patch_corners_are_control_points() {
+        let patch = sample_patch();
+
+        assert!(abs_diff_eq!(patch.evaluate(0.0, 0.0), patch.curves[0].control_points[0], epsilon = 1.0e-5));
+        assert!(abs_diff_eq!(patch.evaluate(1.0, 0.0), patch.curves[0].control_points[3], epsilon = 1.0e-5));
+        assert!(abs_diff_eq!(patch.evaluate(0.0, 1.0), patch.curves[3].control_points[0], epsilon = 1.0e-5));
+        assert!(abs_diff_eq!(patch.evaluate(1.0, 1.0), patch.curves[3].control_points[3], epsilon = 1.0e-5));
+    }
+
+    #[test]
+    fn mirrored_patch_evaluates_mirrored() {
+        let patch = sample_patch();
+        let mirrored = patch.clone_mirrored(MirrorPlane::YZ);
+        let factors = MirrorPlane::YZ.factors();
+
+        assert!(mirrored.flip_normals);
+        assert!(abs_diff_eq!(mirrored.evaluate(0.3, 0.6), patch.evaluate(0.3, 0.6).component_mul(&factors), epsilon = 1.0e-4));
+    }
+}
@@ -4,14 +4,20 @@ use serde_derive::*;
 use serde_json::*;
 use std::string::*;
 use std::collections::*;
+use std::fs;
+use std::path::PathBuf;
 use nalgebra_glm::Vec3;
 use lsystems_core::*;
 use lsystems_core::drawing::{DrawingParameters, TurtleCommand};
 use crate::data::bezier::*;
+use crate::rendering::camera::ProjectionType;
+use crate::rendering::lighting::FogSettings;
 
 
 pub mod presets;
 pub mod bezier;
+pub mod palette;
+pub mod text_import;
 
 
 /// Enumeration describing the different line rendering modes that can be used by a
@@ -24,7 +30,90 @@ pub enum LineDrawMode {
 	/// 2D lines made out of triangle strips with arbitrary width.
 	Advanced2D = 1,
 	/// 3D, tube-like lines.
-	Advanced3D = 2
+	Advanced3D = 2,
+	/// Flat quads with arbitrary width that always face the camera.
+	Billboard = 3
+}
+
+impl Default for LineDrawMode {
+	fn default() -> Self {
+		LineDrawMode::Basic
+	}
+}
+
+/// Enumeration describing how the polygons of a lsystem are shaded.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[repr(u32)]
+pub enum PolygonShading {
+	/// Vertex normals are averaged over all adjacent faces, giving polygons a rounded look.
+	Smooth = 0,
+	/// Each triangle uses its own face normal, which keeps faceted shapes crisp.
+	Flat = 1
+}
+
+impl Default for PolygonShading {
+	fn default() -> Self {
+		PolygonShading::Smooth
+	}
+}
+
+/// Enumeration describing how consecutive 2D line segments are joined together.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[repr(u32)]
+pub enum LineJoinStyle {
+	/// Do not join segments at all. This can leave gaps at sharp corners.
+	None = 0,
+	/// Extend the segment edges until they meet.
+	Miter = 1,
+	/// Cover the joint with a disc.
+	Round = 2
+}
+
+/// Enumeration describing how polygon meshes are rasterized.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[repr(u32)]
+pub enum WireframeMode {
+	/// Only draw the filled polygons.
+	Off = 0,
+	/// Only draw the polygon edges.
+	Only = 1,
+	/// Draw the filled polygons, with their edges drawn on top.
+	Overlay = 2
+}
+
+/// Enumeration describing how the camera projects the lsystem to the screen.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[repr(u32)]
+pub enum ProjectionMode {
+	/// Perspective projection, using the field of view stored in the application settings.
+	Perspective = 0,
+	/// Orthographic projection, useful for inspecting 2D systems.
+	Orthographic = 1
+}
+
+/// Enumeration describing when the camera switches to 2D mode, in which it looks straight at the
+/// XY plane and can only be panned and zoomed.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[repr(u32)]
+pub enum FlatViewMode {
+	/// Use 2D mode if the lsystem has no depth.
+	Automatic = 0,
+	/// Always use 2D mode.
+	On = 1,
+	/// Never use 2D mode.
+	Off = 2
+}
+
+/// Enumeration describing how buffer swaps are synchronized with the display refresh.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[repr(u32)]
+pub enum VSyncMode {
+	/// Wait for the vertical blank, capping the framerate at the display refresh rate.
+	On = 0,
+	/// Swap immediately, leaving the framerate uncapped.
+	Off = 1,
+	/// Wait for the vertical blank, but swap immediately if a frame was late.
+	Adaptive = 2
 }
 
 /// A special structure used to represent a single interpretation mapping.
@@ -36,11 +125,18 @@ pub struct Interpretation {
 	pub operation: TurtleCommand
 }
 
-/// Struct containing application-wide settings
-#[derive(Serialize, Deserialize, Clone)]
+/// Struct containing application-wide settings. Fields missing from saved settings, for example
+/// because they were written by an older version, are taken from the default settings.
+/// The settings are persisted in a file in the platform specific configuration directory, separate
+/// from any lsystem files.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
 pub struct ApplicationSettings {
 	/// Whether the displayed LSystem should be refreshed on parameter change.
 	pub auto_refresh: bool,
+	/// Whether edits to the axiom, rules and interpretation symbols are only applied once Enter is
+	/// pressed or the text field loses focus, instead of after every keystroke
+	pub apply_text_on_enter: bool,
 	/// Whether to draw the bounding box around the lsystem
 	pub draw_bounding_box: bool,
 	/// Whether to automatically center camera on lsystem center on reload
@@ -48,12 +144,103 @@ pub struct ApplicationSettings {
 	/// Whether to additionally adjust the camera radius in order to have the full system in view.
 	/// Is only relevant if auto centering is active.
 	pub auto_adjust_radius: bool,
+	/// Whether to scale the rendered lsystem so that the longest axis of its bounding box has
+	/// a length of 1.0. This only affects rendering, not the geometry itself.
+	pub normalize_scale: bool,
 	/// The color of the bounding box wireframe
 	pub bounding_box_color: Vec3,
+	/// Whether to show the width, height and depth of the bounding box as labels next to its edges
+	pub show_bounding_box_dimensions: bool,
+	/// Whether to draw the bounding box on top of the lsystem, instead of letting it be occluded
+	pub bounding_box_on_top: bool,
+	/// Whether to show the world space position of the geometry under the mouse cursor in a tooltip
+	pub show_cursor_position: bool,
 	/// Whether to show the normal vectors of polygons (debug feature)
 	pub show_normals: bool,
-	/// Whether to draw polygons as wireframes (debug features)
-	pub draw_wireframe: bool
+	/// Length of the normal vectors shown by the normal vector visualization (debug feature)
+	pub normal_debug_length: f32,
+	/// Whether to render the begin and end points of all line segments as points (debug feature)
+	pub show_node_points: bool,
+	/// Whether to draw the turtle coordinate frames at all save and restore commands (debug feature)
+	pub show_turtle_frames: bool,
+	/// Whether to draw the bounding sphere the camera radius is fitted to (debug feature)
+	pub show_bounding_sphere: bool,
+	/// Whether to draw the back faces of polygons in a contrasting color (debug feature)
+	pub two_sided_debug: bool,
+	/// How to draw polygons, either filled, as wireframes or both (debug feature)
+	pub wireframe_mode: WireframeMode,
+	/// Depth offset applied to filled polygons, which avoids flickering of lines drawn on top of them
+	/// such as the bounding box or the wireframe overlay. A value of zero disables the offset.
+	pub polygon_offset: f32,
+	/// The projection used by the camera
+	pub projection_mode: ProjectionMode,
+	/// The vertical field of view used for perspective projection, in degrees
+	pub field_of_view: f32,
+	/// Distance of the camera's near clip plane
+	pub near_plane: f32,
+	/// Distance of the camera's far clip plane
+	pub far_plane: f32,
+	/// Camera rotation angle per pixel of mouse movement, in radians
+	pub rotate_sensitivity: f32,
+	/// Camera pan distance per pixel of mouse movement
+	pub pan_sensitivity: f32,
+	/// When the camera switches to 2D mode
+	pub flat_view_mode: FlatViewMode,
+	/// The highest iteration depth that can be selected
+	pub max_iteration_depth: u32,
+	/// The highest estimated number of symbols an expansion may have. Higher iteration depths are
+	/// reduced until the estimate is below this limit, to avoid running out of memory.
+	pub max_expansion_length: u32,
+	/// The highest number of line segments a line mesh is built for. Bigger lsystems keep the previous mesh.
+	pub max_line_segments: u32,
+	/// Whether to derive the clip planes from the bounding box when adjusting the camera zoom.
+	/// Is only relevant if auto adjusting of the camera radius is active.
+	pub auto_clip_planes: bool,
+	/// How segments are joined when using 2D lines
+	pub line_join_style: LineJoinStyle,
+	/// Factor converting line widths to tube radii when using 3D lines
+	pub tube_radius_scale: f32,
+	/// Factor converting line widths to quad widths when using billboard lines
+	pub billboard_width_scale: f32,
+	/// Whether to show an overlay listing all bound symbols (debug feature)
+	pub show_symbol_legend: bool,
+	/// Whether to show a graph of the most recent frame times (debug feature)
+	pub show_frame_times: bool,
+	/// Whether to draw basic lines as an indexed mesh, with coincident segment end points sharing
+	/// a single vertex. Reduces memory usage for large systems.
+	pub indexed_lines: bool,
+	/// Whether to automatically reload the current lsystem when the file it was loaded from
+	/// is modified by another program
+	pub auto_reload_file: bool,
+	/// Whether to render the lsystem gamma-correct, by shading in linear space and letting the
+	/// hardware convert the result to sRGB
+	pub srgb: bool,
+	/// How buffer swaps are synchronized with the display refresh
+	pub vsync_mode: VSyncMode,
+	/// Whether surfaces fade into the fog color with increasing distance to the camera
+	pub fog_enabled: bool,
+	/// The color surfaces fade into
+	pub fog_color: Vec3,
+	/// Distance to the camera at which the fog starts
+	pub fog_start: f32,
+	/// Distance to the camera at which surfaces are completely covered by fog
+	pub fog_end: f32,
+	/// Whether shaded surfaces facing downwards are darkened, approximating occlusion in crevices
+	pub normal_darkening_enabled: bool,
+	/// How strongly downwards facing surfaces are darkened, in [0, 1]
+	pub normal_darkening: f32,
+	/// Whether line segments are colored by the generation they were produced in, instead of the color palette
+	pub generation_gradient: bool,
+	/// The color of line segments stemming from the axiom
+	pub generation_gradient_start: Vec3,
+	/// The color of line segments produced in the last iteration
+	pub generation_gradient_end: Vec3,
+	/// Whether the scene is rendered offscreen at a fixed resolution and shown letterboxed, independent of the window size
+	pub fixed_render_target: bool,
+	/// Width of the fixed render target, in pixels
+	pub render_target_width: u32,
+	/// Height of the fixed render target, in pixels
+	pub render_target_height: u32
 }
 
 impl ApplicationSettings {
@@ -61,12 +248,92 @@ impl ApplicationSettings {
 	pub fn default_settings() -> ApplicationSettings {
 		ApplicationSettings {
 			auto_refresh: true,
+			apply_text_on_enter: false,
 			draw_bounding_box: false,
 			auto_center_camera: true,
 			auto_adjust_radius: true,
+			normalize_scale: false,
 			bounding_box_color: Vec3::new(1.0, 1.0, 1.0),
+			show_bounding_box_dimensions: false,
+			bounding_box_on_top: false,
+			show_cursor_position: false,
 			show_normals: false,
-			draw_wireframe: false
+			normal_debug_length: 0.5,
+			show_node_points: false,
+			show_turtle_frames: false,
+			show_bounding_sphere: false,
+			two_sided_debug: false,
+			wireframe_mode: WireframeMode::Off,
+			polygon_offset: 1.0,
+			projection_mode: ProjectionMode::Perspective,
+			field_of_view: 75.0,
+			near_plane: 0.0001,
+			far_plane: 1000.0,
+			auto_clip_planes: false,
+			rotate_sensitivity: 1.0 / 300.0,
+			pan_sensitivity: 0.0018,
+			flat_view_mode: FlatViewMode::Automatic,
+			max_iteration_depth: 13,
+			max_expansion_length: 50_000_000,
+			max_line_segments: 20_000_000,
+			line_join_style: LineJoinStyle::Miter,
+			tube_radius_scale: 0.001,
+			billboard_width_scale: 0.002,
+			show_symbol_legend: false,
+			show_frame_times: false,
+			indexed_lines: false,
+			auto_reload_file: false,
+			srgb: false,
+			vsync_mode: VSyncMode::On,
+			fog_enabled: false,
+			fog_color: Vec3::new(0.1, 0.1, 0.1),
+			fog_start: 1.0,
+			fog_end: 10.0,
+			normal_darkening_enabled: false,
+			normal_darkening: 0.5,
+			generation_gradient: false,
+			generation_gradient_start: Vec3::new(0.55, 0.3, 0.1),
+			generation_gradient_end: Vec3::new(0.3, 0.9, 0.2),
+			fixed_render_target: false,
+			render_target_width: 1920,
+			render_target_height: 1080
+		}
+	}
+
+	/// Retrieve the polygon offset factor and units to apply to filled polygons, if any.
+	pub fn polygon_offset(&self) -> Option<(f32, f32)> {
+		if self.polygon_offset > 0.0 {
+			Some((self.polygon_offset, self.polygon_offset))
+		} else {
+			None
+		}
+	}
+
+	/// Retrieve the fog settings used for rendering.
+	pub fn fog_settings(&self) -> FogSettings {
+		FogSettings {
+			enabled: self.fog_enabled,
+			color: self.fog_color,
+			start: self.fog_start,
+			end: self.fog_end
+		}
+	}
+
+	/// Retrieve the normal darkening intensity passed to the lighting context. This is zero if normal
+	/// darkening is disabled.
+	pub fn normal_darkening(&self) -> f32 {
+		if self.normal_darkening_enabled {
+			self.normal_darkening
+		} else {
+			0.0
+		}
+	}
+
+	/// Retrieve the camera projection described by these settings.
+	pub fn projection_type(&self) -> ProjectionType {
+		match self.projection_mode {
+			ProjectionMode::Perspective => ProjectionType::Perspective(self.field_of_view),
+			ProjectionMode::Orthographic => ProjectionType::Orthographic
 		}
 	}
 
@@ -74,11 +341,69 @@ impl ApplicationSettings {
 	pub fn from_string(input: &str) -> ApplicationSettings {
 		serde_json::from_str(input).expect("Failed to read ApplicationSettings from JSON")
 	}
+
+	/// Retrieve the path of the file the settings are persisted in, if the platform has a
+	/// configuration directory.
+	pub fn config_path() -> Option<PathBuf> {
+		dirs::config_dir().map(|dir| dir.join("lsystems-gui").join("settings.json"))
+	}
+
+	/// Load the settings from the settings file. Falls back to the default settings if there is no
+	/// such file yet or it could not be read.
+	pub fn load() -> ApplicationSettings {
+		let path = match Self::config_path() {
+			Some(path) => path,
+			None => return Self::default_settings()
+		};
+
+		match fs::read_to_string(&path) {
+			Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+				eprintln!("Could not read settings file \"{}\": {}", path.display(), e);
+				Self::default_settings()
+			}),
+			Err(_) => Self::default_settings()
+		}
+	}
+
+	/// Write the settings to the settings file, creating its directory if needed.
+	pub fn save(&self) -> std::result::Result<(), String> {
+		let path = Self::config_path().ok_or("Could not determine the configuration directory".to_string())?;
+
+		if let Some(directory) = path.parent() {
+			fs::create_dir_all(directory)
+				.map_err(|e| format!("Could not create directory \"{}\": {}", directory.display(), e))?;
+		}
+
+		let json = serde_json::to_string_pretty(self)
+			.map_err(|e| format!("Could not serialize settings: {}", e))?;
+
+		fs::write(&path, json).map_err(|e| format!("Could not write settings file \"{}\": {}", path.display(), e))
+	}
 }
 
+impl Default for ApplicationSettings {
+	fn default() -> Self {
+		Self::default_settings()
+	}
+}
+
+/// The current version of the lsystem file format. This has to be increased, and a corresponding
+/// step added to `migrate_lsystem_parameters`, whenever older files might lack a newly added field.
+pub const LSYSTEM_PARAMETERS_VERSION: u32 = 2;
+
+/// The smallest value allowed for the step and line width drawing parameters.
+pub const MIN_DRAWING_VALUE: f64 = 0.0;
+
+/// The largest value allowed for the step and line width drawing parameters.
+pub const MAX_DRAWING_VALUE: f64 = 360.0;
+
 /// A struct containing all the information that describes a single LSystem.
 #[derive(Serialize, Deserialize, Clone)]
 pub struct LSystemParameters {
+	/// The file format version. Files written before versioning was introduced lack this field
+	/// and are treated as version 0.
+	#[serde(default)]
+	pub version: u32,
     pub name: String,
 	pub drawing_parameters: DrawingParameters,
 	/// Whether the camera position will be modified when this LSystem gets loaded
@@ -87,8 +412,13 @@ pub struct LSystemParameters {
 	pub camera_phi: f64,
 	pub camera_theta: f64,
 	pub axiom: String,
+	#[serde(default)]
 	pub seed: u64,
+	#[serde(default)]
 	pub line_draw_mode: LineDrawMode,
+	/// How the polygons are shaded
+	#[serde(default)]
+	pub polygon_shading: PolygonShading,
 	pub iteration_depth: u32,
 	pub rules: Vec<String>,
 	/// The usage of a Vec instead of a associative container is done in order to preserve
@@ -96,17 +426,384 @@ pub struct LSystemParameters {
 	/// gui rendering.
 	pub interpretations: Vec<Interpretation>,
 	pub color_palette: Vec<Vec3>,
+	/// The opacity of polygons drawn with the color palette entry of the same index. Entries missing
+	/// from this list are treated as fully opaque.
+	#[serde(default)]
+	pub color_opacities: Vec<f32>,
+	#[serde(default)]
 	pub bezier_models: Vec<BezierModelParameters>
 }
 
 impl LSystemParameters {
 	/// Read a new instance from JSON string.
 	pub fn from_string(input: &str) -> LSystemParameters {
-		serde_json::from_str(input).expect("Failed to read LSystemParameters from JSON")
+		Self::from_string_migrated(input).expect("Failed to read LSystemParameters from JSON").0
+	}
+
+	/// Read a new instance from JSON string, migrating it to the current file format version if
+	/// needed. Returns the parameters together with the version of the file as it was read.
+	pub fn from_string_migrated(input: &str) -> serde_json::Result<(LSystemParameters, u32)> {
+		let mut value: serde_json::Value = serde_json::from_str(input)?;
+		let version = migrate_lsystem_parameters(&mut value);
+
+		Ok((serde_json::from_value(value)?, version))
+	}
+
+	/// Check whether given symbol appears anywhere in this lsystem: in the axiom, the rules, the interpretations
+	/// or as identifier of a bezier model. The arrows separating the predecessor and successor of each rule
+	/// do not count as symbols.
+	pub fn uses_symbol(&self, symbol: char) -> bool {
+		self.axiom.contains(symbol)
+			|| self.rules.iter().any(|rule| rule.split("->").any(|part| part.contains(symbol)))
+			|| self.interpretations.iter().any(|interp| interp.symbol == Some(symbol))
+			|| self.bezier_models.iter().any(|model| model.symbol == Some(symbol))
+	}
+
+	/// Rename given symbol everywhere it appears: in the axiom, the rules, the interpretations and as
+	/// identifier of bezier models. Only single symbols are replaced, the arrows separating the predecessor
+	/// and successor of each rule are left untouched. Renaming onto a symbol that is already in use would
+	/// merge the two symbols, so it is rejected and nothing is changed.
+	pub fn rename_symbol(&mut self, old: char, new: char) -> std::result::Result<(), String> {
+		if old == new {
+			return Ok(());
+		}
+
+		if self.uses_symbol(new) {
+			return Err(format!("The symbol '{}' is already in use. Rename it first, or choose another symbol.", new));
+		}
+
+		let rename = |s: &str| -> String {
+			s.chars().map(|c| if c == old { new } else { c }).collect()
+		};
+
+		self.axiom = rename(&self.axiom);
+
+		for rule in &mut self.rules {
+			*rule = match rule.find("->") {
+				Some(pos) => format!("{}->{}", rename(&rule[..pos]), rename(&rule[pos + 2..])),
+				None => rename(rule)
+			};
+		}
+
+		for interp in &mut self.interpretations {
+			if interp.symbol == Some(old) {
+				interp.symbol = Some(new);
+			}
+		}
+
+		for model in &mut self.bezier_models {
+			if model.symbol == Some(old) {
+				model.symbol = Some(new);
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Estimate the number of symbols the expansion of this lsystem has after given number of iterations,
+	/// without actually iterating it. Only the symbol counts are tracked: each rule is treated as replacing
+	/// the first symbol of its predecessor, and multiple rules for the same symbol are averaged. Context and
+	/// parameters are ignored, so this is only a rough estimate.
+	pub fn estimate_expansion_length(&self, iterations: u32) -> u64 {
+		let count_symbols = |s: &str| -> HashMap<char, f64> {
+			let mut counts = HashMap::new();
+			for c in s.chars().filter(|c| !c.is_whitespace()) {
+				*counts.entry(c).or_insert(0.0) += 1.0;
+			}
+			counts
+		};
+
+		// The successor symbol counts of all rules, grouped by the replaced symbol
+		let mut successors: HashMap<char, Vec<HashMap<char, f64>>> = HashMap::new();
+
+		for rule in &self.rules {
+			if let Some(pos) = rule.find("->") {
+				if let Some(symbol) = rule[..pos].chars().find(|c| !c.is_whitespace()) {
+					successors.entry(symbol).or_insert_with(Vec::new).push(count_symbols(&rule[pos + 2..]));
+				}
+			}
+		}
+
+		let mut counts = count_symbols(&self.axiom);
+
+		for _ in 0..iterations {
+			let mut next = HashMap::new();
+
+			for (symbol, count) in counts {
+				match successors.get(&symbol) {
+					Some(alternatives) => {
+						let weight = count / alternatives.len() as f64;
+
+						for alternative in alternatives {
+							for (successor, n) in alternative {
+								*next.entry(*successor).or_insert(0.0) += n * weight;
+							}
+						}
+					},
+					None => *next.entry(symbol).or_insert(0.0) += count
+				}
+			}
+
+			counts = next;
+		}
+
+		// Float to integer conversion saturates, so huge estimates end up as u64::MAX
+		counts.values().sum::<f64>().round() as u64
+	}
+
+	/// Clamp the drawing parameters to the ranges the GUI allows and normalize all angles to [0, 2π).
+	/// Loaded files might contain values outside of these ranges, which can lead to degenerate or
+	/// invisible renders. Returns a description of every adjustment that was made.
+	pub fn sanitize_drawing_parameters(&mut self) -> Vec<String> {
+		let mut adjustments = Vec::new();
+		let params = &mut self.drawing_parameters;
+
+		let mut clamp = |name: &str, value: &mut f64, min: f64, max: f64| {
+			// NaN is not finite either, and ends up at the lower bound
+			let clamped = if value.is_finite() { value.max(min).min(max) } else { min };
+
+			if clamped != *value {
+				adjustments.push(format!("Clamped {} from {} to {}", name, value, clamped));
+				*value = clamped;
+			}
+		};
+
+		clamp("step", &mut params.step, MIN_DRAWING_VALUE, MAX_DRAWING_VALUE);
+		clamp("initial line width", &mut params.initial_line_width, MIN_DRAWING_VALUE, MAX_DRAWING_VALUE);
+		clamp("line width delta", &mut params.line_width_delta, MIN_DRAWING_VALUE, MAX_DRAWING_VALUE);
+
+		let mut normalize = |name: &str, value: &mut f64| {
+			let two_pi = 2.0 * std::f64::consts::PI;
+			let normalized = if value.is_finite() { value.rem_euclid(two_pi) } else { 0.0 };
+
+			if normalized != *value {
+				adjustments.push(format!("Normalized {} from {} to {}", name, value, normalized));
+				*value = normalized;
+			}
+		};
+
+		normalize("start angle", &mut params.start_angle);
+		normalize("angle delta", &mut params.angle_delta);
+
+		adjustments
+	}
+
+	/// Bind all symbols used in the axiom or the rules that are not interpreted yet, but have a conventional
+	/// meaning, to their turtle commands, see `standard_turtle_command`. New interpretations are appended in
+	/// the order their symbols are first used. Returns how many interpretations were added.
+	pub fn add_standard_interpretations(&mut self) -> usize {
+		let count = self.interpretations.len();
+
+		// The arrows separating predecessor and successor must not be mistaken for turn commands
+		let rule_symbols: String = self.rules.iter()
+			.map(|rule| rule.replacen("->", "", 1))
+			.collect();
+
+		for symbol in self.axiom.chars().chain(rule_symbols.chars()) {
+			if self.interpretations.iter().any(|interp| interp.symbol == Some(symbol)) {
+				continue;
+			}
+
+			if let Some(operation) = standard_turtle_command(symbol) {
+				self.interpretations.push(Interpretation {
+					symbol: Some(symbol),
+					operation: operation
+				});
+			}
+		}
+
+		self.interpretations.len() - count
+	}
+
+	/// Retrieve the opacity associated with the color palette entry of given index.
+	pub fn color_opacity(&self, index: usize) -> f32 {
+		self.color_opacities.get(index).cloned().unwrap_or(1.0)
+	}
+
+	/// Determine all symbols that have a meaning in this lsystem: symbols that are interpreted, bound
+	/// to a bezier model or replaced by a production rule. All other symbols are treated as constants.
+	pub fn known_symbols(&self) -> HashSet<char> {
+		let mut known: HashSet<char> = self.interpretations.iter()
+			.filter_map(|interp| interp.symbol)
+			.chain(self.bezier_models.iter().filter_map(|model| model.symbol))
+			.collect();
+
+		for rule in &self.rules {
+			if let Some(pos) = rule.find("->") {
+				known.extend(rule[..pos].chars().filter(|c| !c.is_whitespace()));
+			}
+		}
+
+		known
+	}
+
+	/// Check the axiom and all production rules for common authoring mistakes: rules without
+	/// an arrow or predecessor, unbalanced brackets and symbols that are neither interpreted, bound
+	/// to a bezier model nor replaced by any rule.
+	pub fn validate_rules(&self) -> Vec<RuleProblem> {
+		let mut problems = Vec::new();
+
+		// Symbols that have a meaning in this lsystem
+		let known = self.known_symbols();
+
+		// The strings that are expanded, together with the index of the rule they belong to
+		let mut successors = vec![(None, self.axiom.as_str())];
+
+		for (i, rule) in self.rules.iter().enumerate() {
+			// Rules that were just added are still empty, which is not worth complaining about
+			if rule.trim().is_empty() {
+				continue;
+			}
+
+			match rule.find("->") {
+				Some(pos) => {
+					let predecessor = rule[..pos].trim();
+
+					if predecessor.is_empty() {
+						problems.push(RuleProblem::new(Some(i), "The rule has no predecessor symbol"));
+					}
+
+					successors.push((Some(i), &rule[pos + 2..]));
+				},
+				None => problems.push(RuleProblem::new(Some(i), "The rule is missing \"->\""))
+			}
+		}
+
+		for (rule, successor) in successors {
+			let mut depth = 0;
+
+			for c in successor.chars() {
+				match c {
+					'[' => depth += 1,
+					']' => {
+						depth -= 1;
+
+						if depth < 0 {
+							break;
+						}
+					},
+					_ => {}
+				}
+			}
+
+			if depth < 0 {
+				problems.push(RuleProblem::new(rule, "Closing bracket \"]\" without matching \"[\""));
+			} else if depth > 0 {
+				problems.push(RuleProblem::new(rule, &format!("{} unclosed bracket(s) \"[\"", depth)));
+			}
+
+			let mut unknown: Vec<char> = successor.chars()
+				.filter(|c| !c.is_whitespace() && !known.contains(c))
+				.collect();
+
+			unknown.sort();
+			unknown.dedup();
+
+			if !unknown.is_empty() {
+				let symbols: Vec<String> = unknown.iter().map(|c| format!("'{}'", c)).collect();
+				problems.push(RuleProblem::new(rule, &format!("Symbols without meaning: {}", symbols.join(", "))));
+			}
+		}
+
+		problems
 	}
 }
 
+/// Retrieve the turtle command conventionally bound to given symbol, as used in "The Algorithmic Beauty
+/// of Plants". Returns None if there is no such convention for the symbol.
+pub fn standard_turtle_command(symbol: char) -> Option<TurtleCommand> {
+	match symbol {
+		'F' | 'G' => Some(TurtleCommand::Forward),
+		'f' => Some(TurtleCommand::ForwardNoDraw),
+		'+' => Some(TurtleCommand::TurnLeft),
+		'-' => Some(TurtleCommand::TurnRight),
+		'|' => Some(TurtleCommand::TurnAround),
+		'&' => Some(TurtleCommand::PitchDown),
+		'^' => Some(TurtleCommand::PitchUp),
+		'\\' => Some(TurtleCommand::RollLeft),
+		'/' => Some(TurtleCommand::RollRight),
+		'[' => Some(TurtleCommand::SaveState),
+		']' => Some(TurtleCommand::LoadState),
+		'{' => Some(TurtleCommand::BeginPolygon),
+		'}' => Some(TurtleCommand::EndPolygon),
+		'.' => Some(TurtleCommand::SubmitVertex),
+		'!' => Some(TurtleCommand::DecrementLineWidth),
+		'\'' => Some(TurtleCommand::IncrementColor),
+		_ => None
+	}
+}
 
+/// A problem found while validating the axiom and production rules of an lsystem.
+pub struct RuleProblem {
+	/// Index of the offending rule. If this is None, the problem was found in the axiom.
+	pub rule: Option<usize>,
+	/// Human-readable description of the problem
+	pub message: String
+}
 
+impl RuleProblem {
+	/// Create a new rule problem instance
+	pub fn new(rule: Option<usize>, message: &str) -> RuleProblem {
+		RuleProblem {
+			rule: rule,
+			message: message.to_string()
+		}
+	}
+}
+
+/// Bring the JSON representation of lsystem parameters up to the current file format version by
+/// filling in defaults for all fields the stored version did not have yet. Returns the version
+/// the value had before migration.
+fn migrate_lsystem_parameters(value: &mut serde_json::Value) -> u32 {
+	let version = value.get("version")
+		.and_then(serde_json::Value::as_u64)
+		.unwrap_or(0) as u32;
+
+	if let serde_json::Value::Object(map) = value {
+		if version < 1 {
+			// Version 0 files predate the seed, line drawing modes, bezier models and the palette opacities.
+			map.entry("seed").or_insert(serde_json::Value::from(0));
+			map.entry("line_draw_mode").or_insert(serde_json::to_value(LineDrawMode::default()).unwrap());
+			map.entry("bezier_models").or_insert(serde_json::Value::Array(Vec::new()));
+			map.entry("color_opacities").or_insert(serde_json::Value::Array(Vec::new()));
+		}
+
+		if version < 2 {
+			// Version 1 files predate the choice of polygon shading.
+			map.entry("polygon_shading").or_insert(serde_json::to_value(PolygonShading::default()).unwrap());
+		}
+
+		map.insert("version".to_string(), serde_json::Value::from(LSYSTEM_PARAMETERS_VERSION));
+	}
+
+	version
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn rename_symbol_replaces_all_occurrences() {
+		let mut params = LSystemParameters::from_string(presets::KOCH_SNOWFLAKE);
 
+		assert!(params.rename_symbol('F', 'G').is_ok());
+
+		assert_eq!(params.axiom, "G--G--G");
+		assert_eq!(params.rules, vec!["G -> G+G--G+G".to_string()]);
+		assert!(params.interpretations.iter().any(|interp| interp.symbol == Some('G')));
+		assert!(!params.uses_symbol('F'));
+	}
 
+	#[test]
+	fn rename_symbol_rejects_symbols_in_use() {
+		let mut params = LSystemParameters::from_string(presets::KOCH_SNOWFLAKE);
+
+		assert!(params.rename_symbol('F', '+').is_err());
+
+		// Nothing may be changed, since the two symbols can not be told apart afterwards
+		assert_eq!(params.axiom, "F--F--F");
+		assert_eq!(params.rules, vec!["F -> F+F--F+F".to_string()]);
+		assert_eq!(params.interpretations.iter().filter(|interp| interp.symbol == Some('+')).count(), 1);
+	}
+}
@@ -0,0 +1,182 @@
+use nalgebra_glm::{Vec3, mix};
+use std::fs::read_to_string;
+use image::Pixel;
+
+/// The different kinds of color palettes that can be generated by the palette generator.
+#[derive(Clone, Copy)]
+#[repr(u32)]
+pub enum GradientMode {
+    /// Linearly interpolate between a start and an end color
+    Linear = 0,
+    /// Walk once around the hue circle in HSV space
+    Rainbow = 1
+}
+
+/// Settings used to procedurally generate a color palette. This is used by the GUI to remember
+/// the user's choices between frames.
+#[derive(Clone)]
+pub struct PaletteGenerator {
+    /// What kind of gradient to generate
+    pub mode: GradientMode,
+    /// First color of a linear gradient
+    pub start_color: Vec3,
+    /// Last color of a linear gradient
+    pub end_color: Vec3,
+    /// How many colors to generate
+    pub count: u32,
+    /// Whether the generated colors replace the current palette, or are appended to it
+    pub replace: bool
+}
+
+impl PaletteGenerator {
+    /// Create palette generator with default settings
+    pub fn new() -> PaletteGenerator {
+        PaletteGenerator {
+            mode: GradientMode::Linear,
+            start_color: Vec3::new(0.2, 0.4, 0.1),
+            end_color: Vec3::new(0.6, 0.9, 0.3),
+            count: 8,
+            replace: true
+        }
+    }
+
+    /// Generate the palette colors described by the current settings.
+    pub fn generate(&self) -> Vec<Vec3> {
+        match self.mode {
+            GradientMode::Linear => linear_gradient(&self.start_color, &self.end_color, self.count),
+            GradientMode::Rainbow => rainbow(self.count)
+        }
+    }
+}
+
+/// Create a gradient of given size that linearly interpolates between the two given colors.
+/// Both the start and the end color are part of the result.
+pub fn linear_gradient(start: &Vec3, end: &Vec3, count: u32) -> Vec<Vec3> {
+    match count {
+        0 => Vec::new(),
+        1 => vec![start.clone()],
+        _ => (0..count)
+            .map(|i| {
+                let t = (i as f32) / ((count - 1) as f32);
+                mix(start, end, t)
+            })
+            .collect()
+    }
+}
+
+/// Create a palette of given size with colors evenly distributed around the hue circle.
+pub fn rainbow(count: u32) -> Vec<Vec3> {
+    (0..count)
+        .map(|i| hsv_to_rgb((i as f32) / (count as f32), 0.85, 0.95))
+        .collect()
+}
+
+/// Retrieve the Okabe-Ito palette, whose eight colors stay distinguishable for people with the
+/// common forms of color vision deficiency.
+pub fn color_blind_safe() -> Vec<Vec3> {
+    [
+        (0, 0, 0),
+        (230, 159, 0),
+        (86, 180, 233),
+        (0, 158, 115),
+        (240, 228, 66),
+        (0, 114, 178),
+        (213, 94, 0),
+        (204, 121, 167)
+    ].iter()
+        .map(|&(r, g, b)| Vec3::new((r as f32) / 255.0, (g as f32) / 255.0, (b as f32) / 255.0))
+        .collect()
+}
+
+/// Convert given color in HSV color space to RGB. All components are expected to be in [0, 1].
+pub fn hsv_to_rgb(h: f32, s: f32, v: f32) -> Vec3 {
+    let h = (h.fract() + 1.0).fract() * 6.0;
+    let sector = h.floor();
+    let f = h - sector;
+
+    let p = v * (1.0 - s);
+    let q = v * (1.0 - s * f);
+    let t = v * (1.0 - s * (1.0 - f));
+
+    match sector as u32 {
+        0 => Vec3::new(v, t, p),
+        1 => Vec3::new(q, v, p),
+        2 => Vec3::new(p, v, t),
+        3 => Vec3::new(p, q, v),
+        4 => Vec3::new(t, p, v),
+        _ => Vec3::new(v, p, q)
+    }
+}
+
+/// Load a color palette from a GIMP palette (.gpl) file.
+pub fn load_palette_from_gpl(path: &str) -> Result<Vec<Vec3>, String> {
+    let contents = read_to_string(path).map_err(|e| format!("Could not read palette file: {}", e))?;
+    let mut lines = contents.lines().map(|l| l.trim()).filter(|l| !l.is_empty());
+
+    match lines.next() {
+        Some("GIMP Palette") => {},
+        _ => return Err("Not a GIMP palette file: missing \"GIMP Palette\" header".to_string())
+    }
+
+    let mut colors = Vec::new();
+
+    for line in lines {
+        // Skip comments and the optional header fields
+        if line.starts_with('#') || line.starts_with("Name:") || line.starts_with("Columns:") {
+            continue;
+        }
+
+        // Each entry consists of the three color components, optionally followed by a name
+        let components: Vec<&str> = line.split_whitespace().take(3).collect();
+
+        if components.len() != 3 {
+            return Err(format!("Malformed palette entry: \"{}\"", line));
+        }
+
+        let mut rgb = [0.0f32; 3];
+
+        for (i, component) in components.iter().enumerate() {
+            let value = component.parse::<u8>()
+                .map_err(|_| format!("Malformed color component \"{}\" in palette entry \"{}\"", component, line))?;
+
+            rgb[i] = (value as f32) / 255.0;
+        }
+
+        colors.push(Vec3::new(rgb[0], rgb[1], rgb[2]));
+    }
+
+    if colors.is_empty() {
+        return Err("The palette file does not contain any colors".to_string());
+    }
+
+    Ok(colors)
+}
+
+/// Load a color palette from an image file by sampling given number of evenly spaced columns
+/// along the vertical center of the image.
+pub fn load_palette_from_image(path: &str, count: u32) -> Result<Vec<Vec3>, String> {
+    let image = image::open(path).map_err(|e| format!("Could not load image: {}", e))?.to_rgb();
+    let (width, height) = image.dimensions();
+
+    if width == 0 || height == 0 {
+        return Err("The image is empty".to_string());
+    }
+
+    let y = height / 2;
+
+    let colors = (0..count)
+        .map(|i| {
+            // Sample the center of each of the columns
+            let x = ((((i as f32) + 0.5) / (count as f32)) * (width as f32)) as u32;
+            let channels = image.get_pixel(x.min(width - 1), y).channels();
+
+            Vec3::new(
+                (channels[0] as f32) / 255.0,
+                (channels[1] as f32) / 255.0,
+                (channels[2] as f32) / 255.0
+            )
+        })
+        .collect();
+
+    Ok(colors)
+}
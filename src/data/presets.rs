@@ -5,4 +5,6 @@ use std::include_str;
 
 pub const EMPTY: &'static str = include_str!("presets/empty.json");
 pub const KOCH_SNOWFLAKE: &'static str = include_str!("presets/koch.json");
-pub const PENROSE: &'static str = include_str!("presets/penrose.json");
\ No newline at end of file
+pub const PENROSE: &'static str = include_str!("presets/penrose.json");
+pub const HILBERT_3D: &'static str = include_str!("presets/hilbert3d.json");
+pub const BUSH_3D: &'static str = include_str!("presets/bush.json");
\ No newline at end of file
@@ -0,0 +1,96 @@
+use crate::data::*;
+
+/// The angle used if a text file does not specify one, in degrees.
+const DEFAULT_ANGLE: f64 = 90.0;
+
+/// The iteration depth used if a text file does not specify one.
+const DEFAULT_ITERATIONS: u32 = 3;
+
+/// Parse an lsystem from the plain text format L-system collections are commonly shared in.
+/// The format is line based:
+///
+/// - Empty lines and lines starting with `#` are ignored.
+/// - `axiom: ...`, `angle: ...` and `iterations: ...` set the axiom, the turning angle in degrees
+///   and the iteration depth. `=` can be used instead of `:`, and the keys are case insensitive.
+/// - Lines containing `=` or `->` are production rules, like `F=FF` or `F -> FF`.
+/// - The first line that is none of the above is taken as the axiom, if no axiom was set explicitly.
+///
+/// All other parameters are taken from the empty preset. Symbols used in the axiom or the rules are
+/// bound to their conventional turtle commands, see `LSystemParameters::add_standard_interpretations`. For example, `F` and
+/// `G` draw forward, `f` moves without drawing, `+` and `-` turn left and right, and `[` and `]` save
+/// and restore the turtle state.
+pub fn parse_text_lsystem(input: &str, name: &str) -> Result<LSystemParameters, String> {
+    let mut axiom: Option<String> = None;
+    let mut angle = DEFAULT_ANGLE;
+    let mut iterations = DEFAULT_ITERATIONS;
+    let mut rules = Vec::new();
+
+    for (number, line) in input.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        // Line numbers are reported starting at one
+        let error = |message: &str| format!("Line {}: {}", number + 1, message);
+
+        if let Some((key, value)) = split_setting(line) {
+            match key.as_str() {
+                "axiom" => axiom = Some(value.to_string()),
+                "angle" => {
+                    angle = value.parse().map_err(|_| error(&format!("\"{}\" is not a valid angle", value)))?;
+                },
+                "iterations" => {
+                    iterations = value.parse().map_err(|_| error(&format!("\"{}\" is not a valid iteration count", value)))?;
+                },
+                _ => unreachable!()
+            }
+        } else if let Some((predecessor, successor)) = split_rule(line) {
+            if predecessor.is_empty() {
+                return Err(error("The rule has no predecessor symbol"));
+            }
+
+            rules.push(format!("{} -> {}", predecessor, successor));
+        } else if axiom.is_none() {
+            axiom = Some(line.to_string());
+        } else {
+            return Err(error(&format!("Could not understand \"{}\"", line)));
+        }
+    }
+
+    let axiom = axiom.ok_or("The file does not contain an axiom".to_string())?;
+
+    let mut params = LSystemParameters::from_string(presets::EMPTY);
+    params.name = name.to_string();
+    params.axiom = axiom;
+    params.rules = rules;
+    params.iteration_depth = iterations;
+    params.drawing_parameters.angle_delta = angle.to_radians();
+    params.interpretations.clear();
+    params.add_standard_interpretations();
+
+    Ok(params)
+}
+
+/// Split a line of the form `key: value` or `key = value` into the lowercase key and the value, if the
+/// key is one of the supported settings.
+fn split_setting(line: &str) -> Option<(String, &str)> {
+    let pos = line.find(|c: char| c == ':' || c == '=')?;
+    let key = line[..pos].trim().to_lowercase();
+
+    match key.as_str() {
+        "axiom" | "angle" | "iterations" => Some((key, line[pos + 1..].trim())),
+        _ => None
+    }
+}
+
+/// Split a rule line of the form `F=FF` or `F -> FF` into its predecessor and successor.
+fn split_rule(line: &str) -> Option<(&str, &str)> {
+    let (pos, len) = match line.find("->") {
+        Some(pos) => (pos, 2),
+        None => (line.find('=')?, 1)
+    };
+
+    Some((line[..pos].trim(), line[pos + len..].trim()))
+}
@@ -1,4 +1,6 @@
 use imgui::*;
+use nalgebra_glm::{Vec3, Vec4, project};
+use crate::rendering::camera::*;
 
 /// Draw a help marker of the form (?) which shows a help text when hovered over.
 pub fn help_marker(ui: &Ui, text: &ImStr) {
@@ -8,6 +10,100 @@ pub fn help_marker(ui: &Ui, text: &ImStr) {
     }
 }
 
+/// Show a tooltip with the world space position of the geometry under the mouse cursor. The position
+/// is divided by given scale, to account for scaling applied while rendering. Nothing is shown if the
+/// cursor is over a GUI window, outside of the screen or over the background. This reads the depth
+/// buffer, so it has to be called after the scene was rendered.
+pub fn cursor_position_tooltip(ui: &Ui, camera: &Camera, scale: f32) {
+    if ui.io().want_capture_mouse {
+        return;
+    }
+
+    let [x, y] = ui.io().mouse_pos;
+
+    if !camera.contains(x, y) {
+        return;
+    }
+
+    let (depth, position) = camera.read_position(x as _, y as _);
+
+    if depth < 1.0 {
+        let position = position / scale;
+        ui.tooltip_text(format!("X: {:.3}\nY: {:.3}\nZ: {:.3}", position.x, position.y, position.z));
+    }
+}
+
+/// Show a small overlay in the top right corner of the screen indicating that the camera is locked.
+pub fn camera_lock_indicator(ui: &Ui, screen_width: u32) {
+    Window::new(im_str!("Camera Lock"))
+        .title_bar(false)
+        .resizable(false)
+        .movable(false)
+        .always_auto_resize(true)
+        .position([(screen_width as f32) - 140.0, 25.0], Condition::Always)
+        .build(ui, || {
+            ui.text_colored([1.0, 0.8, 0.0, 1.0], im_str!("Camera locked (L)"));
+        });
+}
+
+/// Draw text labels at the screen positions of given world space positions, as seen by given camera.
+/// Labels behind the camera are skipped. The labels are drawn in an invisible full screen window that
+/// ignores mouse input, which has to be given a unique name.
+pub fn world_space_labels(ui: &Ui, name: &ImStr, camera: &Camera, width: u32, height: u32, labels: &[(Vec3, String)]) {
+    let viewport = Vec4::new(0.0, 0.0, width as f32, height as f32);
+
+    Window::new(name)
+        .title_bar(false)
+        .resizable(false)
+        .movable(false)
+        .scroll_bar(false)
+        .mouse_inputs(false)
+        .draw_background(false)
+        .focus_on_appearing(false)
+        .position([0.0, 0.0], Condition::Always)
+        .size([width as f32, height as f32], Condition::Always)
+        .build(ui, || {
+            let draw_list = ui.get_window_draw_list();
+
+            for (position, text) in labels {
+                let screen = project(position, &camera.view, &camera.projection, viewport);
+
+                // Skip positions behind the camera or outside of the depth range
+                if screen.z < 0.0 || screen.z > 1.0 {
+                    continue;
+                }
+
+                // Window coordinates have their origin at the bottom left, imgui uses the top left
+                let x = screen.x + 4.0;
+                let y = height as f32 - screen.y - 4.0;
+
+                draw_list.add_text([x, y], [0.85, 0.85, 0.85, 1.0], text);
+            }
+        });
+}
+
+/// Show a row of buttons snapping given camera to the front, right and top views.
+pub fn axis_view_buttons(ui: &Ui, camera: &mut Camera) {
+    let views = [
+        (im_str!("Front"), AxisView::Front),
+        (im_str!("Right"), AxisView::Right),
+        (im_str!("Top"), AxisView::Top)
+    ];
+
+    for (i, (label, view)) in views.iter().enumerate() {
+        if i > 0 {
+            ui.same_line(0.0);
+        }
+
+        if ui.button(label, [0.0, 0.0]) {
+            camera.snap_to_axis(*view);
+        }
+    }
+
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("Rotates the camera to look along one of the coordinate axes. Works best with orthographic projection.\n\
+                             The views can also be selected using the 1, 3 and 7 keys, holding Ctrl selects the opposite view."));
+}
 
 /// Enumeration describing the different button types supported by the popup function.
 #[derive(Clone, Copy)]
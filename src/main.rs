@@ -12,7 +12,7 @@ use serde_json::*;
 
 use rendering::traits::*;
 use rendering::camera::*;
-use rendering::{Viewport};
+use rendering::{Viewport, pixel_scale, gui_font_scale};
 
 mod rendering;
 mod data;
@@ -23,10 +23,32 @@ use crate::data::*;
 use crate::scene::*;
 use crate::scene::lsystem::*;
 
+/// Determine the swap interval corresponding to given vsync mode
+fn swap_interval(mode: VSyncMode) -> SwapInterval {
+    match mode {
+        VSyncMode::On => SwapInterval::Sync(1),
+        VSyncMode::Off => SwapInterval::None,
+        VSyncMode::Adaptive => SwapInterval::Adaptive
+    }
+}
+
+/// Tell imgui about the ratio between screen coordinates and framebuffer pixels, and scale the
+/// GUI font according to the content scale of the monitor the window is on.
+fn apply_gui_scale(imgui: &mut ImContext, window: &glfw::Window) {
+    let (w, h) = window.get_size();
+    let (fw, fh) = window.get_framebuffer_size();
+    let scale = pixel_scale((w as _, h as _), (fw as _, fh as _));
+
+    let io = imgui.io_mut();
+    io.display_framebuffer_scale = [scale.0, scale.1];
+    io.font_global_scale = gui_font_scale(window.get_content_scale(), scale);
+}
+
 fn main() {
 	let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
     glfw.window_hint(glfw::WindowHint::ContextVersion(3, 3)); 
     glfw.window_hint(glfw::WindowHint::Samples(Some(4u32)));
+    glfw.window_hint(glfw::WindowHint::SRgbCapable(true));
 
     let (mut window, events) = glfw
         .create_window(
@@ -49,12 +71,15 @@ fn main() {
         gl::ClearColor(0.1, 0.1, 0.1, 1.0);
     }
 
-    // Limit to 60 fps
-    glfw.set_swap_interval(SwapInterval::Sync(1));
+    let settings = ApplicationSettings::load();
+
+    glfw.set_swap_interval(swap_interval(settings.vsync_mode));
 
+    // The OpenGL viewport is given in framebuffer pixels, which can differ from the window size
+    // reported in screen coordinates on HiDPI displays.
     let mut viewport;
     {
-        let (w, h) = window.get_size();
+        let (w, h) = window.get_framebuffer_size();
         viewport = Viewport::for_window(w as _, h as _);
     }
 
@@ -62,6 +87,9 @@ fn main() {
 
     let mut imgui_glfw = ImguiGLFW::new(&mut imgui, &mut window);
 
+    apply_gui_scale(&mut imgui, &window);
+
+    // Whether the scene GUI is shown. Toggled using the M key.
     let mut show_menu = true;
 
     // ======== Scene setup =================
@@ -75,7 +103,7 @@ fn main() {
             make_rc_cell(
                 LSystemScene::new(
                     &LSystemParameters::from_string(data::presets::PENROSE),
-                    &ApplicationSettings::default_settings(),
+                    &settings,
                     w as _,
                     h as _
                 )
@@ -93,13 +121,29 @@ fn main() {
         
         // The scene manager action emitted by the folling scene render.
         let action;
+        // Interaction with the document tab bar during this frame
+        let tab_event;
+        // Whether the user tried to close the application window
+        let mut close_requested = false;
+
+        // Scenes covered by the current one might have to react to changes it made
+        scene_manager.do_background_logic();
+
         {
-            // Borrow mutable reference to the current scene for this frame
-            let mut scene = scene_manager.current_scene().borrow_mut();
+            // Borrow mutable reference to the current scene for this frame. The scene is borrowed through
+            // its own reference count, which leaves the scene manager free to be modified.
+            let current = scene_manager.current_scene().clone();
+            let mut scene = current.borrow_mut();
 
             // Perform logic
+            scene.set_gui_visible(show_menu);
+            scene.set_time(glfw.get_time());
             scene.do_logic();
 
+            if let Some(title) = scene.title() {
+                scene_manager.set_document_title(title);
+            }
+
             // Render scene to screen
             scene.render();
 
@@ -107,6 +151,7 @@ fn main() {
             {
                 let ui = imgui_glfw.frame(&mut window, &mut imgui);
                 action = scene.do_gui(&ui);
+                tab_event = scene_manager.do_document_tabs(&ui);
                 imgui_glfw.draw(ui, &mut window);
             }      
             
@@ -124,24 +169,95 @@ fn main() {
                 }
 
                 match event {
-                    glfw::WindowEvent::Key(glfw::Key::M, _, Action::Press, _) => {
+                    // The documents are asked whether they may be closed once the current scene is no longer borrowed
+                    glfw::WindowEvent::Close => {
+                        close_requested = true;
+                    },
+                    // Typing into a text field must not hide the GUI
+                    glfw::WindowEvent::Key(glfw::Key::M, _, Action::Press, _) if !imgui.io().want_capture_keyboard => {
                         show_menu = !show_menu;
                     },
                     glfw::WindowEvent::Size(w, h) => {
-                        viewport.update(w as _, h as _);
-                        viewport.enable();
-
                         // Notify the scene that the screen size has changed. This is important
                         // to update internal state, such as cameras.
                         scene.handle_resize(w as _, h as _);
                     },
+                    glfw::WindowEvent::FramebufferSize(w, h) => {
+                        viewport.update(w as _, h as _);
+                        viewport.enable();
+
+                        apply_gui_scale(&mut imgui, &window);
+                    },
+                    glfw::WindowEvent::ContentScale(_, _) => {
+                        apply_gui_scale(&mut imgui, &window);
+                    },
                     _ => {},
                 }
             }
 
         }
 
+        // Give the documents the chance to veto the close request, for example if there are unsaved changes.
+        if close_requested && !scene_manager.request_close_all() {
+            window.set_should_close(false);
+        }
+
+        // Whether the scene shown next frame might not know the current window size
+        let mut switched = false;
+
         // Process action
-        scene_manager.process_action(action);
+        match action {
+            SceneAction::Quit => {
+                // A document only asks to quit once the user agreed to discard its changes. If other documents
+                // are open, only this one is closed, and the others get the chance to ask as well.
+                if scene_manager.document_count() > 1 {
+                    scene_manager.close_document();
+                    switched = true;
+
+                    if scene_manager.request_close_all() {
+                        window.set_should_close(true);
+                    }
+                } else {
+                    window.set_should_close(true);
+                }
+            },
+            // Closing the last document quits the application
+            SceneAction::CloseDocument if scene_manager.document_count() == 1 => window.set_should_close(true),
+            SceneAction::CloseDocument => {
+                scene_manager.close_document();
+                switched = true;
+            },
+            SceneAction::SetVSyncMode(mode) => glfw.set_swap_interval(swap_interval(mode)),
+            _ => scene_manager.process_action(action)
+        }
+
+        match tab_event {
+            Some(DocumentTabEvent::Select(i)) => {
+                scene_manager.set_active_document(i);
+                switched = true;
+            },
+            Some(DocumentTabEvent::Close(i)) => {
+                scene_manager.set_active_document(i);
+                switched = true;
+
+                // Documents with an open bezier editor are only switched to, since the editor has to be
+                // left before the lsystem can ask about unsaved changes.
+                if !scene_manager.has_pushed_scenes(i) {
+                    let scene = scene_manager.current_scene().clone();
+                    let close = scene.borrow_mut().request_close_document();
+
+                    if close {
+                        scene_manager.close_document();
+                    }
+                }
+            },
+            None => {}
+        }
+
+        // Inactive documents do not receive resize events
+        if switched {
+            let (w, h) = window.get_size();
+            scene_manager.current_scene().borrow_mut().handle_resize(w as _, h as _);
+        }
     }
 }
@@ -9,39 +9,48 @@ pub struct BezierGeometry {
 
 impl BezierGeometry {
     /// Create a new bezier patch geometry based on given patch parameters and resolution values.
-    pub fn new(parameters: &BezierPatchParameters, rows: u32, cols: u32) -> BezierGeometry {
+    /// Faces meeting at an angle bigger than given smoothing angle, in degrees, do not share vertex normals.
+    pub fn new(parameters: &BezierPatchParameters, rows: u32, cols: u32, smoothing_angle: f32) -> BezierGeometry {
         let mut plane = PlaneGeometry::new(rows, cols, parameters.color);
 
+        // The matrix form of the patch only has to be computed once for the whole grid
+        let prepared = PreparedPatch::new(parameters);
+
         // u
         for x in 0..=cols {
             let u = (x as f32) / (cols) as f32;
 
-            // Evaluate all control point curves
-            let pt0 = parameters.curves[0].evaluate(u);
-            let pt1 = parameters.curves[1].evaluate(u);
-            let pt2 = parameters.curves[2].evaluate(u);
-            let pt3 = parameters.curves[3].evaluate(u);
-
             // v
             for y in 0..=rows {
                 let v = (y as f32) / (rows) as f32;
                 let index_base = y * (cols + 1);
                 let index = (index_base + x) as usize;
 
-                // Blend points together for this particular v
-                let vertex = BezierCurveParameters::from_points([pt0, pt1, pt2, pt3]).evaluate(v);
-
                 // Set vertex in plane geometry
-                plane.set_vertex(index, vertex);
+                plane.set_vertex(index, prepared.evaluate(u, v));
             }
         }
 
-        plane.regenerate_normals();
+        // Creased normals require duplicated vertices, which is not needed if everything is smoothed anyways
+        if smoothing_angle < 180.0 {
+            plane.regenerate_normals_creased(smoothing_angle);
+        } else {
+            plane.regenerate_normals();
+        }
+
+        if parameters.flip_normals {
+            plane.flip_normals();
+        }
 
         BezierGeometry{
             plane: plane
         }
     }
+
+    /// Retrieve the primitive type the indices of this geometry have to be drawn with.
+    pub fn primitive_type(&self) -> PrimitiveType {
+        self.plane.primitive_type()
+    }
 }
 
 impl IndexedGeometry for BezierGeometry {
@@ -1,6 +1,6 @@
-use nalgebra_glm::{Mat4, IVec2, Vec3, perspective_fov, ortho, look_at, two_pi, pi};
-use glfw::{Window, WindowEvent, MouseButton, Action};
-use crate::rendering::RenderParameters;
+use nalgebra_glm::{Mat4, IVec2, Vec3, Vec4, perspective_fov, ortho, look_at, unproject, two_pi, pi};
+use glfw::{Window, WindowEvent, MouseButton, Action, Key, Modifiers};
+use crate::rendering::{RenderParameters, Viewport, screen_to_framebuffer};
 
 /// An enumeration describing how the camera projects the scene to the screen.
 #[derive(Clone, Copy)]
@@ -12,10 +12,60 @@ pub enum ProjectionType {
 }
 
 
+/// The canonical views along the coordinate axes the camera can be snapped to.
+#[derive(Clone, Copy)]
+pub enum AxisView {
+    /// Looking down the negative Z axis
+    Front,
+    /// Looking down the positive Z axis
+    Back,
+    /// Looking down the negative X axis
+    Right,
+    /// Looking down the positive X axis
+    Left,
+    /// Looking down the negative Y axis
+    Top,
+    /// Looking down the positive Y axis
+    Bottom
+}
+
+impl AxisView {
+    /// Map numpad-style hotkeys to axis views: 1 is the front, 3 the right and 7 the top view.
+    /// Holding control selects the opposite view. Both the numpad and the number row are accepted.
+    pub fn from_key(key: Key, modifiers: Modifiers) -> Option<AxisView> {
+        let opposite = modifiers.contains(Modifiers::Control);
+
+        match key {
+            Key::Kp1 | Key::Num1 => Some(if opposite { AxisView::Back } else { AxisView::Front }),
+            Key::Kp3 | Key::Num3 => Some(if opposite { AxisView::Left } else { AxisView::Right }),
+            Key::Kp7 | Key::Num7 => Some(if opposite { AxisView::Bottom } else { AxisView::Top }),
+            _ => None
+        }
+    }
+
+    /// Retrieve the camera angles (phi, theta) for this view, in radians. The top and bottom views are
+    /// slightly tilted, since the up vector can't be parallel to the viewing direction.
+    fn angles(&self) -> (f64, f64) {
+        let half_pi = pi::<f64>() / 2.0;
+        let tilt = 0.0001;
+
+        match self {
+            AxisView::Front => (half_pi, 0.0),
+            AxisView::Back => (half_pi, pi::<f64>()),
+            AxisView::Right => (half_pi, half_pi),
+            AxisView::Left => (half_pi, -half_pi),
+            AxisView::Top => (tilt, 0.0),
+            AxisView::Bottom => (pi::<f64>() - tilt, 0.0)
+        }
+    }
+}
+
 /// A set of vectors describing the current camera state. This does not contain any
 /// matrices, since both the projection and view matrix are derived from this.
-/// External navigation algorithms can supply this data and thus cause the camera view
-/// to change.
+/// The state itself is derived from the orbit target, radius and rotation angles of the camera.
+/// External navigation code should therefore not modify it directly, but use the navigation
+/// methods of `Camera` instead: `recenter`, `set_radius` and `set_orientation`, together with
+/// their getters `target`, `radius` and `orientation`.
 #[derive(Clone, Copy, Debug)]
 pub struct CameraState {
     /// The cameras position in 3D space
@@ -73,6 +123,19 @@ pub struct Camera {
     phi: f64,
     /// The trackball camera radius. Can be modified by zooming.
     radius: f64,
+    /// Distance of the near clip plane
+    near: f32,
+    /// Distance of the far clip plane
+    far: f32,
+    /// Rotation angle per pixel of mouse movement, in radians
+    rotate_sensitivity: f64,
+    /// Pan distance per pixel of mouse movement
+    pan_sensitivity: f32,
+    /// Whether user input is ignored. The camera can still be moved programmatically.
+    locked: bool,
+    /// Whether the camera is in 2D mode, looking straight at the XY plane. In this mode rotation
+    /// is disabled and an orthographic projection is used regardless of the projection type.
+    flat: bool,
     /// The current move mode
     move_mode: MoveMode
 }
@@ -89,6 +152,7 @@ impl Camera {
 
         cam.update_state();
         cam.update_view();
+        cam.update_proj();
 
         cam
     }
@@ -112,6 +176,12 @@ impl Camera {
             theta: 0.0,
             phi: pi::<f64>() / 2.0,
             radius: 1.0,
+            near: 0.0001,
+            far: 1000.0,
+            rotate_sensitivity: 1.0 / 300.0,
+            pan_sensitivity: 0.0018,
+            locked: false,
+            flat: false,
             move_mode: MoveMode::None
         };
 
@@ -125,7 +195,14 @@ impl Camera {
 
     /// Extract relevant camera information to use in a rendering operation
     pub fn to_render_parameters(&self) -> RenderParameters {
-        RenderParameters::new(self.camera_position(), self.view, self.projection)
+        let mut params = RenderParameters::new(self.camera_position(), self.view, self.projection);
+
+        // The same basis the view matrix is built from
+        let forward = (self.state.target - self.state.position).normalize();
+        params.camera_right = forward.cross(&self.state.up).normalize();
+        params.camera_up = params.camera_right.cross(&forward);
+
+        params
     }
 
     /// An internal method used to update the view matrix after camera state
@@ -141,17 +218,23 @@ impl Camera {
     /// Update projection matrix. This is required when either the viewport or the projection
     /// type changes.
     fn update_proj(&mut self) {
-        match self.proj_type {
+        let proj_type = if self.flat { ProjectionType::Orthographic } else { self.proj_type };
+
+        match proj_type {
             ProjectionType::Orthographic => {
                 let aspect = self.width as f32 / self.height as f32;
 
+                // Zooming has no effect on an orthographic projection, so the visible area is
+                // scaled with the camera radius instead.
+                let extent = (self.radius as f32).max(0.0001) / 2.0;
+
                 self.projection = ortho(
-                    -aspect/2.0,
-                    aspect/2.0,
-                    0.5,
-                    -0.5, 
-                    0.1,
-                    1000.0
+                    -aspect * extent,
+                    aspect * extent,
+                    -extent,
+                    extent, 
+                    self.near,
+                    self.far
                 );
             },
             ProjectionType::Perspective(fov) => {
@@ -159,8 +242,8 @@ impl Camera {
                     fov.to_radians(),   // The field of view, in radians
                     self.width as _,    // Width of the screen
                     self.height as _,   // Height of the screen
-                    0.0001,                // Near clip plane  
-                    1000.0              // Far clip plane
+                    self.near,          // Near clip plane  
+                    self.far            // Far clip plane
                 );
             }
         }
@@ -207,17 +290,108 @@ impl Camera {
         }
     }
 
-    pub fn recenter(&mut self, center: &Vec3) {
-        self.state.target = center.clone();
+    /// Move the point the camera orbits around to given position, keeping the current angles and radius.
+    /// This is part of the navigation API and updates the view matrix immediately.
+    pub fn recenter(&mut self, target: &Vec3) {
+        self.state.target = target.clone();
         self.update_state();
         self.update_view();
     }
 
+    /// Retrieve the point the camera currently orbits around.
+    pub fn target(&self) -> Vec3 {
+        self.state.target
+    }
+
+    /// Set the distance between the camera and the point it orbits around. This is part of the
+    /// navigation API and updates both the view and projection matrix immediately, since the
+    /// orthographic projection depends on the radius.
     pub fn set_radius(&mut self, radius: f64) {
         self.radius = radius;
 
         self.update_state();
         self.update_view();
+        self.update_proj();
+    }
+
+    /// Retrieve the distance between the camera and the point it orbits around.
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    /// Switch the projection used by this camera.
+    pub fn set_projection(&mut self, proj: ProjectionType) {
+        self.proj_type = proj;
+        self.update_proj();
+    }
+
+    /// Set the distances of the near and far clip planes.
+    pub fn set_clip_planes(&mut self, near: f32, far: f32) {
+        self.near = near;
+        self.far = far;
+        self.update_proj();
+    }
+
+    /// Retrieve the distance of the near clip plane.
+    pub fn near(&self) -> f32 {
+        self.near
+    }
+
+    /// Retrieve the distance of the far clip plane.
+    pub fn far(&self) -> f32 {
+        self.far
+    }
+
+    /// Set the rotation angle per pixel of mouse movement, in radians.
+    pub fn set_rotate_sensitivity(&mut self, sensitivity: f64) {
+        self.rotate_sensitivity = sensitivity;
+    }
+
+    /// Set the pan distance per pixel of mouse movement.
+    pub fn set_pan_sensitivity(&mut self, sensitivity: f32) {
+        self.pan_sensitivity = sensitivity;
+    }
+
+    /// Lock or unlock the camera. A locked camera ignores all mouse input, which avoids accidental
+    /// changes of the view. Any drag in progress is ended.
+    pub fn set_locked(&mut self, locked: bool) {
+        self.locked = locked;
+        self.is_dragging = false;
+        self.move_mode = MoveMode::None;
+    }
+
+    /// Check whether the camera is locked.
+    pub fn locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Retrieve the projection currently used by this camera.
+    pub fn projection_type(&self) -> ProjectionType {
+        self.proj_type
+    }
+
+    /// Enable or disable 2D mode. In 2D mode, the camera looks straight down the negative Z axis at
+    /// the XY plane using an orthographic projection. Dragging with either mouse button pans the view,
+    /// and scrolling zooms. Enabling it resets the rotation angles, disabling it keeps them.
+    pub fn set_flat(&mut self, flat: bool) {
+        if self.flat == flat {
+            return;
+        }
+
+        self.flat = flat;
+        self.is_dragging = false;
+        self.move_mode = MoveMode::None;
+
+        if flat {
+            self.set_orientation(pi::<f64>() / 2.0, 0.0);
+        }
+
+        self.update_proj();
+    }
+
+    /// Check whether the camera is in 2D mode.
+    pub fn flat(&self) -> bool {
+        self.flat
     }
 
     /// Check if camera is currently being dragged by the user
@@ -225,6 +399,45 @@ impl Camera {
         self.is_dragging
     }
 
+    /// Unproject given window position and depth value to a point in world space.
+    pub fn unproject(&self, x: u32, y: u32, depth: f32) -> Vec3 {
+        unproject(
+            &Vec3::new(x as _, (self.height - y) as _, depth),
+            &self.view,
+            &self.projection,
+            Vec4::new(0.0, 0.0, self.width as _, self.height as _)
+        )
+    }
+
+    /// Read the depth buffer at given window position and unproject it. Returns the depth value
+    /// and the resulting world space position. A depth value of 1.0 means nothing was rendered there.
+    /// The position is given in screen coordinates, which are converted to the pixels of the current viewport,
+    /// since both can differ on HiDPI displays.
+    pub fn read_position(&self, x: u32, y: u32) -> (f32, Vec3) {
+        let framebuffer_size = Viewport::current_size();
+        let (px, py) = screen_to_framebuffer(x as _, y as _, (self.width, self.height), framebuffer_size);
+
+        let mut depth: f32 = 0.0;
+        unsafe {
+            gl::ReadPixels(
+                px as _,
+                (framebuffer_size.1 as f32 - py) as _,
+                1 as _,
+                1 as _,
+                gl::DEPTH_COMPONENT,
+                gl::FLOAT,
+                &mut depth as *mut f32 as _
+            );
+        }
+
+        (depth, self.unproject(x, y, depth))
+    }
+
+    /// Check whether given window position lies inside the screen.
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= 0.0 && y >= 0.0 && x < self.width as f32 && y < self.height as f32
+    }
+
     /// Notify camera of updated screen dimensions
     pub fn update(&mut self, w: u32, h: u32) {
         self.width = w;
@@ -237,12 +450,24 @@ impl Camera {
     fn pan(&mut self, pos: &IVec2) {
         let dif =  self.drag_start - pos;
 
+        // In 2D mode, the view follows the cursor exactly. The orthographic projection shows
+        // an area of one radius in height.
+        if self.flat {
+            let world_per_pixel = self.radius as f32 / self.height.max(1) as f32;
+
+            self.state.target += Vec3::new(dif.x as f32 * world_per_pixel, -dif.y as f32 * world_per_pixel, 0.0);
+
+            self.update_state();
+            self.update_view();
+            return;
+        }
+
         let look = (self.state.target - self.camera_position()).normalize();
 
         let right = look.cross(&self.state.up);
         let up = look.cross(&right);
 
-        self.state.target += (right * (dif.x as f32 * 0.0018)) + (up * (dif.y as f32 * 0.0018));
+        self.state.target += (right * (dif.x as f32 * self.pan_sensitivity)) + (up * (dif.y as f32 * self.pan_sensitivity));
 
         self.update_state();
         self.update_view();
@@ -250,6 +475,17 @@ impl Camera {
 
     /// Zoom camera
     fn zoom(&mut self, delta: f64) {
+        // Zooming in 2D mode is relative to the current zoom level, which keeps it usable for both
+        // tiny and huge systems.
+        if self.flat {
+            self.radius *= 0.9f64.powf(delta / 0.2);
+
+            self.update_state();
+            self.update_view();
+            self.update_proj();
+            return;
+        }
+
         self.radius -= delta;
 
         // The radius is not allowed to become negative!
@@ -263,6 +499,7 @@ impl Camera {
 
         self.update_state();
         self.update_view();
+        self.update_proj();
     }
 
     /// Rotate camera
@@ -270,8 +507,8 @@ impl Camera {
         let dif =  self.drag_start - pos;
 
         // Calculate delta angles
-        let delta_theta = dif.x as f64 / 300.0;
-        let delta_phi = dif.y as f64 / 300.0;
+        let delta_theta = dif.x as f64 * self.rotate_sensitivity;
+        let delta_phi = dif.y as f64 * self.rotate_sensitivity;
 
         if self.state.up == Vec3::new(0.0, 1.0, 0.0) {
             self.theta += delta_theta;
@@ -281,7 +518,13 @@ impl Camera {
 
         self.phi += delta_phi;
 
-        // Keep angles in interval -2PI to +2PI
+        self.update_up();
+        self.update_state();
+        self.update_view();
+    }
+
+    /// Keep phi in the interval -2PI to +2PI and flip the up vector if the camera went over one of the poles.
+    fn update_up(&mut self) {
         if self.phi > two_pi() {
             self.phi -= two_pi::<f64>();
         } else if self.phi < -two_pi::<f64>() {
@@ -293,11 +536,35 @@ impl Camera {
         } else {
             self.state.up = Vec3::new(0.0, -1.0, 0.0);
         }
+    }
+
+    /// Retrieve the current rotation angles of the camera as (phi, theta), in radians.
+    pub fn orientation(&self) -> (f64, f64) {
+        (self.phi, self.theta)
+    }
 
+    /// Set the rotation angles of the camera, in radians. The camera keeps looking at the same point.
+    /// This is part of the navigation API and updates the view matrix immediately.
+    pub fn set_orientation(&mut self, phi: f64, theta: f64) {
+        self.phi = phi;
+        self.theta = theta;
+
+        self.update_up();
         self.update_state();
         self.update_view();
     }
 
+    /// Rotate the camera to look at its target along one of the coordinate axes. Has no effect in 2D mode,
+    /// which always uses the front view.
+    pub fn snap_to_axis(&mut self, view: AxisView) {
+        if self.flat {
+            return;
+        }
+
+        let (phi, theta) = view.angles();
+        self.set_orientation(phi, theta);
+    }
+
     /// Helper function thats extracts integral mouse position from window
     fn retrieve_mouse_pos(window: &Window) -> IVec2 {
         let (x, y) = window.get_cursor_pos();
@@ -306,11 +573,15 @@ impl Camera {
 
     /// Handle input event in order to implement trackball controls
     pub fn handle_event(&mut self, window: &Window, event: &WindowEvent) {
+        if self.locked {
+            return;
+        }
+
         match event {
             WindowEvent::MouseButton(MouseButton::Button1, Action::Press, _) => {
                 let pos = Self::retrieve_mouse_pos(window);
                 self.drag_start(&pos);
-                self.move_mode = MoveMode::Rotate;
+                self.move_mode = if self.flat { MoveMode::Pan } else { MoveMode::Rotate };
             },
             WindowEvent::MouseButton(MouseButton::Button2, Action::Press, _) => {
                 let pos = Self::retrieve_mouse_pos(window);
@@ -0,0 +1,153 @@
+use gl::types::*;
+use image::{RgbaImage, imageops};
+use crate::rendering::types::GlHandle;
+
+/// An offscreen render target with a color and a depth attachment, which can be used to render
+/// the scene at a resolution independent of the window size and read back the result.
+pub struct Framebuffer {
+    /// The framebuffer object handle
+    handle: GlHandle,
+    /// Renderbuffer used as the color attachment
+    color_buffer: GlHandle,
+    /// Renderbuffer used as the depth attachment
+    depth_buffer: GlHandle,
+    /// Width of the attachments, in pixels
+    width: u32,
+    /// Height of the attachments, in pixels
+    height: u32,
+    /// Whether the color attachment stores sRGB encoded colors
+    srgb: bool
+}
+
+impl Framebuffer {
+    /// Create a new framebuffer with attachments of given size. If `srgb` is set, the color attachment
+    /// stores sRGB encoded colors, matching a window framebuffer created as sRGB capable.
+    pub fn new(width: u32, height: u32, srgb: bool) -> Result<Framebuffer, &'static str> {
+        let mut handle: GLuint = 0;
+        let mut buffers: [GLuint; 2] = [0; 2];
+
+        let status = unsafe {
+            gl::GenFramebuffers(1, &mut handle);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, handle);
+
+            gl::GenRenderbuffers(2, buffers.as_mut_ptr());
+
+            gl::BindRenderbuffer(gl::RENDERBUFFER, buffers[0]);
+            let color_format = if srgb { gl::SRGB8_ALPHA8 } else { gl::RGBA8 };
+            gl::RenderbufferStorage(gl::RENDERBUFFER, color_format, width as _, height as _);
+            gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::RENDERBUFFER, buffers[0]);
+
+            gl::BindRenderbuffer(gl::RENDERBUFFER, buffers[1]);
+            gl::RenderbufferStorage(gl::RENDERBUFFER, gl::DEPTH_COMPONENT24, width as _, height as _);
+            gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, gl::RENDERBUFFER, buffers[1]);
+
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+
+            status
+        };
+
+        // Constructing the instance first makes sure all objects get deleted on failure
+        let framebuffer = Framebuffer {
+            handle: handle,
+            color_buffer: buffers[0],
+            depth_buffer: buffers[1],
+            width: width,
+            height: height,
+            srgb: srgb
+        };
+
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err("Framebuffer is incomplete");
+        }
+
+        Ok(framebuffer)
+    }
+
+    /// Bind this framebuffer as the render target and set the viewport to cover it.
+    /// NOTE: The viewport has to be restored by the caller after rendering!
+    pub fn enable(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.handle);
+            gl::Viewport(0, 0, self.width as _, self.height as _);
+        }
+    }
+
+    /// Retrieve the size of the attachments, in pixels.
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Check whether this framebuffer was created with given size and color encoding.
+    pub fn matches(&self, width: u32, height: u32, srgb: bool) -> bool {
+        self.width == width && self.height == height && self.srgb == srgb
+    }
+
+    /// Copy the contents of the color attachment into given rectangle of the default framebuffer, which is
+    /// given as x, y, width and height in pixels. The image is scaled to fit the rectangle.
+    pub fn blit_to_screen(&self, rect: (u32, u32, u32, u32)) {
+        let (x, y, w, h) = rect;
+
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.handle);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, 0);
+
+            gl::BlitFramebuffer(
+                0, 0, self.width as _, self.height as _,
+                x as _, y as _, (x + w) as _, (y + h) as _,
+                gl::COLOR_BUFFER_BIT,
+                gl::LINEAR
+            );
+
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    /// Bind the default framebuffer again.
+    pub fn disable(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    /// Read back the contents of the color attachment. The rows are flipped, so that the first
+    /// row of the returned image is the top of the rendered frame. sRGB encoded colors are returned
+    /// as they are stored, i.e. just like they appear on screen.
+    pub fn read_pixels(&self) -> RgbaImage {
+        let mut data: Vec<u8> = vec![0; (self.width * self.height * 4) as usize];
+
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.handle);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+
+            gl::ReadPixels(
+                0,
+                0,
+                self.width as _,
+                self.height as _,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                data.as_mut_ptr() as *mut GLvoid
+            );
+
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+        }
+
+        let image = RgbaImage::from_raw(self.width, self.height, data)
+            .expect("Framebuffer pixel data has unexpected size");
+
+        // OpenGL stores the bottom row first
+        imageops::flip_vertical(&image)
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteRenderbuffers(2, [self.color_buffer, self.depth_buffer].as_ptr());
+            gl::DeleteFramebuffers(1, &self.handle);
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use nalgebra_glm::Vec3;
+use crate::rendering::shaders::Program;
 
 /// A struct containing all information used to cast light into the scene
 #[derive(Clone)]
@@ -8,7 +9,10 @@ pub struct LightingContext {
     /// Directional light angle
     pub directional_light: Vec3,
     /// Directional light intensity
-    pub directional_intensity: Vec3
+    pub directional_intensity: Vec3,
+    /// How strongly surfaces facing downwards are darkened, in [0, 1]. This cheaply approximates the
+    /// shadowing in crevices of plant-like models. Zero disables the effect.
+    pub normal_darkening: f32
 }
 
 impl LightingContext {
@@ -17,7 +21,44 @@ impl LightingContext {
         LightingContext {
             ambient_intensity: Vec3::new(0.4, 0.4, 0.4),
             directional_light: Vec3::new(0.0, 1.0, 1.0),
-            directional_intensity: Vec3::new(0.8, 0.8, 0.8)
+            directional_intensity: Vec3::new(0.8, 0.8, 0.8),
+            normal_darkening: 0.0
         }
     }
-}
\ No newline at end of file
+}
+
+/// Settings of the depth based fog, which blends surfaces into the fog color based on their distance
+/// to the camera.
+#[derive(Clone)]
+pub struct FogSettings {
+    /// Whether fog is applied at all
+    pub enabled: bool,
+    /// The color surfaces fade into
+    pub color: Vec3,
+    /// View space distance at which the fog starts
+    pub start: f32,
+    /// View space distance at which surfaces are completely covered by fog
+    pub end: f32
+}
+
+impl FogSettings {
+    /// Constructs disabled fog settings
+    pub fn disabled() -> FogSettings {
+        FogSettings {
+            enabled: false,
+            color: Vec3::new(0.1, 0.1, 0.1),
+            start: 0.0,
+            end: 1.0
+        }
+    }
+
+    /// Set the fog uniforms of given shader program. Missing uniforms are reported and skipped.
+    pub fn apply(&self, program: &Program) {
+        program.report_uniform_errors(&[
+            program.set_uniform_int("FogEnabled", self.enabled as _),
+            program.set_uniform_vec3("FogColor", &self.color),
+            program.set_uniform_float("FogStart", self.start),
+            program.set_uniform_float("FogEnd", self.end)
+        ]);
+    }
+}
@@ -1,6 +1,6 @@
 use gl::types::*;
 use std::any::*;
-use crate::rendering::RenderParameters;
+use crate::rendering::{RenderParameters, RenderError};
 use crate::rendering::shaders::Program;
 use crate::rendering::uniforms::*;
 use nalgebra_glm::{Mat4, Vec3};
@@ -13,7 +13,9 @@ pub trait Material {
     /// Enable this material and prepare it for rendering.
     /// This function will cause all matrices to be extracted from the provided render parameters, as well
     /// as all shader-specific uniforms 
-    fn enable_material(&self, params: &mut RenderParameters);
+    /// Missing uniforms are reported and skipped, so that the remaining uniforms are still set. An error is
+    /// only returned if the material can not be used at all, in which case the mesh should not be drawn.
+    fn enable_material(&self, params: &mut RenderParameters) -> Result<(), RenderError>;
     /// Retrieve this instance as a reference to Any. This is used for downcasting.
     fn as_any(&self) -> &dyn Any;
     /// Retrieve this instance as a mutable reference to Any. This is used for downcasting.
@@ -24,7 +26,9 @@ pub trait Material {
 /// A simple material that applies no shading.
 pub struct SimpleMaterial {
     /// The shader program associated with this material
-    program: Program
+    program: Program,
+    /// The opacity of the rendered surface, in [0, 1].
+    pub opacity: f32
 }
 
 /// Construction
@@ -32,18 +36,26 @@ impl SimpleMaterial {
     /// Create a new simple material instance
     pub fn new() -> SimpleMaterial {
         SimpleMaterial {
-            program: Program::from_source(Self::VERTEX_SHADER_SOURCE, Self::FRAGMENT_SHADER_SOURCE).unwrap()
+            program: Program::from_source(Self::VERTEX_SHADER_SOURCE, Self::FRAGMENT_SHADER_SOURCE).unwrap(),
+            opacity: 1.0
         }
     }
 }
 
 impl Material for SimpleMaterial {
-    fn enable_material(&self, params: &mut RenderParameters) {
+    fn enable_material(&self, params: &mut RenderParameters) -> Result<(), RenderError> {
         self.program.use_program();
 
-        self.program.set_uniform_mat4("projection", &params.projection);
-        self.program.set_uniform_mat4("view", &params.view);
-        self.program.set_uniform_mat4("model", &params.model);
+        self.program.report_uniform_errors(&[
+            self.program.set_uniform_mat4("projection", &params.projection),
+            self.program.set_uniform_mat4("view", &params.view),
+            self.program.set_uniform_mat4("model", &params.model),
+            self.program.set_uniform_float("Opacity", self.opacity),
+            self.program.set_uniform_float("ColorGamma", params.color_gamma)
+        ]);
+        params.fog.apply(&self.program);
+
+        Ok(())
     }
 
     /// Retrieve this instance as a reference to Any. This is used for downcasting.
@@ -73,12 +85,15 @@ impl SimpleMaterial {
 
         out VS_OUTPUT {
             vec3 Color;
+            float ViewDepth;
         } OUT;
 
         void main()
         {
-            gl_Position = projection * view * model * vec4(Position, 1.0);
+            vec4 viewPos = view * model * vec4(Position, 1.0);
+            gl_Position = projection * viewPos;
             OUT.Color = Color;
+            OUT.ViewDepth = -viewPos.z;
         }
     "#;
 
@@ -86,15 +101,110 @@ impl SimpleMaterial {
     const FRAGMENT_SHADER_SOURCE: &'static str = r#"
         #version 330 core
 
+        uniform float Opacity;
+        uniform float ColorGamma;
+        uniform int FogEnabled;
+        uniform vec3 FogColor;
+        uniform float FogStart;
+        uniform float FogEnd;
+
         in VS_OUTPUT {
             vec3 Color;
+            float ViewDepth;
         } IN;
 
         out vec4 Color;
 
         void main()
         {
-            Color = vec4(IN.Color, 1.0f);
+            vec3 result = pow(IN.Color, vec3(ColorGamma));
+
+            if (FogEnabled != 0)
+            {
+                float fog = clamp((IN.ViewDepth - FogStart) / max(FogEnd - FogStart, 0.0001), 0.0, 1.0);
+                result = mix(result, pow(FogColor, vec3(ColorGamma)), fog);
+            }
+
+            Color = vec4(result, Opacity);
+        }
+    "#;
+}
+
+/// A material that draws all geometry in a single flat color, ignoring the vertex colors. This is used to
+/// draw the edges of wireframe overlays, which would otherwise be hard to tell apart from the filled polygons.
+pub struct EdgeMaterial {
+    /// The shader program associated with this material
+    program: Program,
+    /// The color all geometry is drawn in
+    pub color: Vec3
+}
+
+impl EdgeMaterial {
+    /// Create a new edge material drawing in given color
+    pub fn new(color: Vec3) -> EdgeMaterial {
+        EdgeMaterial {
+            program: Program::from_source(Self::VERTEX_SHADER_SOURCE, Self::FRAGMENT_SHADER_SOURCE).unwrap(),
+            color: color
+        }
+    }
+}
+
+impl Material for EdgeMaterial {
+    fn enable_material(&self, params: &mut RenderParameters) -> Result<(), RenderError> {
+        self.program.use_program();
+
+        self.program.report_uniform_errors(&[
+            self.program.set_uniform_mat4("projection", &params.projection),
+            self.program.set_uniform_mat4("view", &params.view),
+            self.program.set_uniform_mat4("model", &params.model),
+            self.program.set_uniform_vec3("EdgeColor", &self.color),
+            self.program.set_uniform_float("ColorGamma", params.color_gamma)
+        ]);
+
+        Ok(())
+    }
+
+    /// Retrieve this instance as a reference to Any. This is used for downcasting.
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    /// Retrieve this instance as a mutable reference to Any. This is used for downcasting.
+    fn as_mut_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Shader source code
+impl EdgeMaterial {
+    /// The vertex shader source for this material
+    const VERTEX_SHADER_SOURCE: &'static str = r#"
+        #version 330 core
+
+        layout (location = 0) in vec3 Position;
+
+        uniform mat4 projection;
+        uniform mat4 view;
+        uniform mat4 model;
+
+        void main()
+        {
+            gl_Position = projection * view * model * vec4(Position, 1.0);
+        }
+    "#;
+
+    /// The fragment shader source for this material
+    const FRAGMENT_SHADER_SOURCE: &'static str = r#"
+        #version 330 core
+
+        uniform vec3 EdgeColor;
+        uniform float ColorGamma;
+
+        out vec4 Color;
+
+        void main()
+        {
+            Color = vec4(pow(EdgeColor, vec3(ColorGamma)), 1.0);
         }
     "#;
 }
@@ -104,7 +214,9 @@ impl SimpleMaterial {
 /// apply diffuse and specular lighting to the object.
 pub struct ShadedMaterial {
     /// The shader program associated with this material
-    program: Program,/*
+    program: Program,
+    /// The opacity of the rendered surface, in [0, 1].
+    pub opacity: f32,/*
     /// How the surface reacts to diffuse lighting. This basically is the base color.
     pub diffuse_reflectivity: Vec3,
     /// How the surface reacts to ambient lighting.
@@ -120,7 +232,8 @@ impl ShadedMaterial {
     /// Create a new simple material instance
     pub fn new(/*diffuse: Vec3, ambient: Vec3, specular: Vec3, shininess: f32*/) -> ShadedMaterial {
         ShadedMaterial {
-            program: Program::from_source(Self::VERTEX_SHADER_SOURCE, Self::FRAGMENT_SHADER_SOURCE).unwrap()//,
+            program: Program::from_source(Self::VERTEX_SHADER_SOURCE, Self::FRAGMENT_SHADER_SOURCE).unwrap(),
+            opacity: 1.0//,
             /*diffuse_reflectivity: diffuse,
             ambient_reflectivity: ambient,
             specular_reflectivity: specular,
@@ -130,20 +243,28 @@ impl ShadedMaterial {
 }
 
 impl Material for ShadedMaterial {
-    fn enable_material(&self, params: &mut RenderParameters) {
+    fn enable_material(&self, params: &mut RenderParameters) -> Result<(), RenderError> {
         self.program.use_program();
 
-        self.program.set_uniform_mat4("projection", &params.projection);
-        self.program.set_uniform_mat4("view", &params.view);
-        self.program.set_uniform_mat4("model", &params.model);
-        //self.program.set_uniform_vec3("Kd", &self.diffuse_reflectivity);
-        //self.program.set_uniform_vec3("Ka", &self.ambient_reflectivity);
-        //self.program.set_uniform_vec3("Ks", &self.specular_reflectivity);
-        //self.program.set_uniform_float("Shininess", self.specular_shininess);
-
-        self.program.set_uniform_vec3("AmbientIntensity", &params.lighting.ambient_intensity);
-        self.program.set_uniform_vec3("DirectionalIntensity", &params.lighting.directional_intensity);
-        self.program.set_uniform_vec3("DirectionalLight", &params.lighting.directional_light);
+        self.program.report_uniform_errors(&[
+            self.program.set_uniform_mat4("projection", &params.projection),
+            self.program.set_uniform_mat4("view", &params.view),
+            self.program.set_uniform_mat4("model", &params.model),
+            //self.program.set_uniform_vec3("Kd", &self.diffuse_reflectivity);
+            //self.program.set_uniform_vec3("Ka", &self.ambient_reflectivity);
+            //self.program.set_uniform_vec3("Ks", &self.specular_reflectivity);
+            //self.program.set_uniform_float("Shininess", self.specular_shininess);
+            self.program.set_uniform_float("Opacity", self.opacity),
+            self.program.set_uniform_float("ColorGamma", params.color_gamma),
+
+            self.program.set_uniform_vec3("AmbientIntensity", &params.lighting.ambient_intensity),
+            self.program.set_uniform_vec3("DirectionalIntensity", &params.lighting.directional_intensity),
+            self.program.set_uniform_vec3("DirectionalLight", &params.lighting.directional_light),
+            self.program.set_uniform_float("NormalDarkening", params.lighting.normal_darkening)
+        ]);
+        params.fog.apply(&self.program);
+
+        Ok(())
     }
 
     /// Retrieve this instance as a reference to Any. This is used for downcasting.
@@ -175,6 +296,7 @@ impl ShadedMaterial {
             vec3 FragPos;
             vec3 Normal;
             vec3 Color;
+            float ViewDepth;
         } OUT;
 
         void main()
@@ -195,6 +317,7 @@ impl ShadedMaterial {
             }
             
             OUT.Color = Color;
+            OUT.ViewDepth = -posView.z;
         }
     "#;
 
@@ -205,11 +328,19 @@ impl ShadedMaterial {
         uniform vec3 AmbientIntensity;
         uniform vec3 DirectionalIntensity;
         uniform vec3 DirectionalLight;
+        uniform float NormalDarkening;
+        uniform float Opacity;
+        uniform float ColorGamma;
+        uniform int FogEnabled;
+        uniform vec3 FogColor;
+        uniform float FogStart;
+        uniform float FogEnd;
 
         in VS_OUTPUT {
             vec3 FragPos;
             vec3 Normal;
             vec3 Color;
+            float ViewDepth;
         } IN;
 
         out vec4 Color;
@@ -221,9 +352,20 @@ impl ShadedMaterial {
             float diff = max(dot(normalize(IN.Normal), normalize(DirectionalLight)), 0.0);
             vec3 diffuse = diff * DirectionalIntensity;
 
-            vec3 result = (diffuse + ambient) * IN.Color;
+            // Surfaces facing away from the world up direction are darkened, which approximates the
+            // occlusion in crevices without any additional data
+            float facing = dot(normalize(IN.Normal), vec3(0.0, 1.0, 0.0));
+            float occlusion = 1.0 - NormalDarkening * (1.0 - facing) * 0.5;
+
+            vec3 result = (diffuse + ambient) * occlusion * pow(IN.Color, vec3(ColorGamma));
+
+            if (FogEnabled != 0)
+            {
+                float fog = clamp((IN.ViewDepth - FogStart) / max(FogEnd - FogStart, 0.0001), 0.0, 1.0);
+                result = mix(result, pow(FogColor, vec3(ColorGamma)), fog);
+            }
 
-            Color = vec4(result, 1.0f);
+            Color = vec4(result, Opacity);
         }
     "#;
 }
\ No newline at end of file
@@ -27,6 +27,7 @@
 use nalgebra_glm::{Vec3, Mat4, UVec3};
 use std::mem::*;
 use std::any::Any;
+use std::cell::{Cell, RefCell};
 use std::f32::*;
 use std::cmp::*;
 use gl::types::*;
@@ -38,6 +39,7 @@ use crate::rendering::RenderParameters;
 use crate::rendering::buffers::{VertexArray, Buffer, BufferBase};
 use crate::rendering::materials::*;
 use crate::rendering::traits::*;
+use crate::data::WireframeMode;
 
 /// The data of single vertex. In general OpenGL applications, the vertex format can significantly vary
 /// from use case to use case, but in this project, we always use the exact same structure. This means
@@ -326,9 +328,15 @@ impl NormalGenerator {
                 }
             },
             // A strip uses a sliding window of width 3 to assign triangles to vertices.
+            // Every second triangle has its winding order flipped, just like OpenGL does,
+            // since otherwise neighbouring face normals would point in opposite directions.
             PrimitiveType::TriangleStrip => {
                 for i in 2..indices.len() {
-                    faces.push(UVec3::new(indices[i-2], indices[i-1], indices[i]));
+                    if i % 2 == 0 {
+                        faces.push(UVec3::new(indices[i-2], indices[i-1], indices[i]));
+                    } else {
+                        faces.push(UVec3::new(indices[i-1], indices[i-2], indices[i]));
+                    }
                 }
             },
             _ => panic!("Primitive type not supported by calculate_indexed_faces!")
@@ -366,9 +374,14 @@ impl NormalGenerator {
                 }
             },
             // A strip uses a sliding window of width 3 to assign triangles to vertices.
+            // Every second triangle has its winding order flipped, just like OpenGL does.
             PrimitiveType::TriangleStrip => {
                 for i in 2..num_vertices {
-                    faces.push(UVec3::new((i-2) as _, (i-1) as _, i as _));
+                    if i % 2 == 0 {
+                        faces.push(UVec3::new((i-2) as _, (i-1) as _, i as _));
+                    } else {
+                        faces.push(UVec3::new((i-1) as _, (i-2) as _, i as _));
+                    }
                 }
             },
             _ => panic!("Primitive type not supported by calculate_faces!")
@@ -377,6 +390,16 @@ impl NormalGenerator {
         faces
     }
 
+    /// Normalize accumulated vertex normals. Vertices that are not part of any non-degenerated
+    /// face end up with a zero vector, which is left untouched instead of turning into NaNs.
+    fn normalize_all(normals: &mut [Vec3]) {
+        for normal in normals.iter_mut() {
+            if normal.norm() > std::f32::EPSILON {
+                normal.normalize_mut();
+            }
+        }
+    }
+
     /// Generates normal vectors for given geometry faces.
     fn generate_face_normals(positions: &[Vec3], faces: &[UVec3]) -> Vec<Vec3> {
         let mut face_normals = Vec::with_capacity(faces.len());
@@ -425,10 +448,7 @@ impl NormalGenerator {
             }
         }
 
-        // Finally, the normal vectors need to be normalized
-        for i in 0..normals.len() {
-            *normals[i] = *normals[i].normalize();
-        }
+        Self::normalize_all(&mut normals);
 
         normals
     }
@@ -455,20 +475,86 @@ impl NormalGenerator {
             // Check that the norm of the calculated normal is at least approximately 1,
             // since otherwise the triangle was degenerated.
             if abs_diff_eq!(normal.norm(), 1.0) {
-                // TODO normally this would be +=, why is it not working with +=?
-                normals[face.x as usize] = normal;
-                normals[face.y as usize] = normal;
-                normals[face.z as usize] = normal;
+                normals[face.x as usize] += normal;
+                normals[face.y as usize] += normal;
+                normals[face.z as usize] += normal;
             }
         }
 
-        // Finally, the normal vectors need to be normalized
-        for i in 0..normals.len() {
-            *normals[i] = *normals[i].normalize();
-        }
+        Self::normalize_all(&mut normals);
 
         normals
     }
+
+    /// Generate vertex normals for given indexed vertices interpreted as given primitive type, keeping
+    /// creases sharp: the normal of a face corner only averages the adjacent faces whose normals differ
+    /// from the one of the face by at most given angle, in degrees. Since a vertex can thus require
+    /// multiple normals, vertices are duplicated as needed.
+    ///
+    /// Returns, for each resulting vertex, the index of the original vertex it is a copy of, together
+    /// with the vertex normals and the indices of the resulting triangle list. Degenerated faces are dropped.
+    pub fn generate_creased_normals(pt: PrimitiveType, positions: &[Vec3], indices: &[u32], crease_angle: f32) -> (Vec<u32>, Vec<Vec3>, Vec<u32>) {
+        let faces = Self::calculate_indexed_faces(pt, indices);
+        let face_normals = Self::generate_face_normals(positions, &faces);
+
+        // Only non-degenerated faces take part, see generate_indexed_normals
+        let valid: Vec<bool> = face_normals.iter().map(|n| abs_diff_eq!(n.norm(), 1.0)).collect();
+
+        // The faces each vertex is part of
+        let mut adjacent_faces = vec![Vec::new(); positions.len()];
+
+        for (i, face) in faces.iter().enumerate() {
+            if valid[i] {
+                adjacent_faces[face.x as usize].push(i);
+                adjacent_faces[face.y as usize].push(i);
+                adjacent_faces[face.z as usize].push(i);
+            }
+        }
+
+        let min_cos = crease_angle.to_radians().cos();
+
+        let mut sources = Vec::new();
+        let mut normals = Vec::new();
+        let mut new_indices = Vec::new();
+
+        // Face corners of the same vertex that end up with the same normal keep sharing a single vertex.
+        // The normals are quantized to make them usable as key.
+        let mut unique: HashMap<(u32, [i32; 3]), u32> = HashMap::new();
+
+        for (i, face) in faces.iter().enumerate() {
+            if !valid[i] {
+                continue;
+            }
+
+            for &vertex in &[face.x, face.y, face.z] {
+                let mut normal = Vec3::zeros();
+
+                for &other in &adjacent_faces[vertex as usize] {
+                    if face_normals[i].dot(&face_normals[other]) >= min_cos {
+                        normal += face_normals[other];
+                    }
+                }
+
+                normal.normalize_mut();
+
+                let key = (vertex, [
+                    (normal.x * 10000.0).round() as i32,
+                    (normal.y * 10000.0).round() as i32,
+                    (normal.z * 10000.0).round() as i32
+                ]);
+
+                let index = *unique.entry(key).or_insert_with(|| {
+                    sources.push(vertex);
+                    normals.push(normal);
+                    (sources.len() - 1) as u32
+                });
+
+                new_indices.push(index);
+            }
+        }
+
+        (sources, normals, new_indices)
+    }
 }
 
 /// A struct storing vertex information for basic drawing operations: position, color and
@@ -520,6 +606,39 @@ impl BasicGeometry {
         geometry
     }
 
+    /// Construct geometry from given slice of vertices interpreted as given primitive type, assigning each
+    /// triangle its face normal. Since vertices can not be shared between faces with different normals, the
+    /// resulting geometry is an unconnected triangle soup that has to be drawn as `PrimitiveType::Triangles`.
+    /// Degenerated triangles are dropped.
+    pub fn with_flat_normals(pt: PrimitiveType, vertices: &[Vertex]) -> BasicGeometry {
+        let positions: Vec<Vec3> = vertices.iter().map(|v| v.position).collect();
+
+        let faces = NormalGenerator::calculate_faces(pt, vertices.len());
+        let face_normals = NormalGenerator::generate_face_normals(&positions, &faces);
+
+        let mut geometry = BasicGeometry {
+            positions: AttributeArray::with_capacity(0, "position", faces.len() * 3),
+            colors: AttributeArray::with_capacity(1, "color", faces.len() * 3),
+            normals: AttributeArray::with_capacity(2, "normal", faces.len() * 3)
+        };
+
+        for (face, normal) in faces.iter().zip(face_normals.iter()) {
+            if !abs_diff_eq!(normal.norm(), 1.0) {
+                continue;
+            }
+
+            for &index in &[face.x, face.y, face.z] {
+                let vertex = &vertices[index as usize];
+
+                geometry.positions.local_buffer.push(vertex.position);
+                geometry.colors.local_buffer.push(vertex.color);
+                geometry.normals.local_buffer.push(*normal);
+            }
+        }
+
+        geometry
+    }
+
     /// Construct empty geometry instance
     pub fn new() -> BasicGeometry {
         BasicGeometry {
@@ -580,6 +699,45 @@ impl BasicIndexedGeometry {
             current_vertex = current_vertex + 1;
         }
     }
+
+    /// Construct indexed geometry from given slice of line vertices, as they would be used for
+    /// GL_LINES. Vertices at the same position and with the same color are merged into a single
+    /// one. Positions are quantized before comparison, which causes end points that only differ
+    /// due to floating point errors to be merged as well. Normal vectors are discarded.
+    pub fn from_line_vertices(vertices: &[Vertex]) -> BasicIndexedGeometry {
+        // Scale applied to positions before rounding them to integers
+        const QUANTIZATION: f32 = 100000.0;
+
+        let mut geometry = Self::new();
+        geometry.indices.reserve(vertices.len());
+
+        // Maps quantized position and color bit patterns to vertex indices
+        let mut lookup: HashMap<([i64; 3], [u32; 3]), u32> = HashMap::with_capacity(vertices.len());
+
+        for v in vertices {
+            let key = (
+                [
+                    (v.position.x * QUANTIZATION).round() as i64,
+                    (v.position.y * QUANTIZATION).round() as i64,
+                    (v.position.z * QUANTIZATION).round() as i64
+                ],
+                [v.color.x.to_bits(), v.color.y.to_bits(), v.color.z.to_bits()]
+            );
+
+            let next_index = geometry.positions.len() as u32;
+            let index = *lookup.entry(key).or_insert(next_index);
+
+            if index == next_index {
+                geometry.positions.local_buffer.push(v.position);
+                geometry.colors.local_buffer.push(v.color);
+                geometry.normals.local_buffer.push(Vec3::zeros());
+            }
+
+            geometry.indices.push(index);
+        }
+
+        geometry
+    }
 }
 
 impl Geometry for BasicIndexedGeometry {
@@ -692,7 +850,9 @@ pub struct PlaneGeometry {
     positions: AttributeArray<Vec3>,
     colors: AttributeArray<Vec3>,
     normals: AttributeArray<Vec3>,
-    indices: Vec<u32>
+    indices: Vec<u32>,
+    /// How the indices have to be interpreted. This is a triangle strip, unless creased normals were generated.
+    primitive_type: PrimitiveType
 }
 
 impl Geometry for PlaneGeometry {
@@ -741,12 +901,41 @@ impl PlaneGeometry {
     /// Regenerate all vertex normals.
     pub fn regenerate_normals(&mut self) {
         self.normals.local_buffer = NormalGenerator::generate_indexed_normals(
-            PrimitiveType::TriangleStrip,
+            self.primitive_type,
             &self.positions.local_buffer,
             &self.indices
         );
     }
 
+    /// Regenerate all vertex normals, keeping creases with an angle bigger than given one, in degrees, sharp.
+    /// This turns the plane into an indexed triangle list, so vertices can no longer be set afterwards.
+    pub fn regenerate_normals_creased(&mut self, crease_angle: f32) {
+        let (sources, normals, indices) = NormalGenerator::generate_creased_normals(
+            self.primitive_type,
+            &self.positions.local_buffer,
+            &self.indices,
+            crease_angle
+        );
+
+        self.positions.local_buffer = sources.iter().map(|&i| self.positions.local_buffer[i as usize]).collect();
+        self.colors.local_buffer = sources.iter().map(|&i| self.colors.local_buffer[i as usize]).collect();
+        self.normals.local_buffer = normals;
+        self.indices = indices;
+        self.primitive_type = PrimitiveType::Triangles;
+    }
+
+    /// Retrieve the primitive type the indices of this plane have to be drawn with.
+    pub fn primitive_type(&self) -> PrimitiveType {
+        self.primitive_type
+    }
+
+    /// Invert all vertex normals.
+    pub fn flip_normals(&mut self) {
+        for normal in &mut self.normals.local_buffer {
+            *normal = -*normal;
+        }
+    }
+
     /// Create a new plane geometry
     pub fn new(rows: u32, cols: u32, color: Vec3) -> PlaneGeometry {
         let total_vertices = (rows + 1) * (cols + 1);
@@ -795,7 +984,8 @@ impl PlaneGeometry {
             positions: AttributeArray::new(0, "position"),
             colors: AttributeArray::new(1, "color"),
             normals: AttributeArray::new(2, "normal"),
-            indices: indices
+            indices: indices,
+            primitive_type: PrimitiveType::TriangleStrip
         };
 
         geometry.colors.local_buffer = vec![color; vertices.len()];
@@ -836,16 +1026,31 @@ pub struct Mesh {
     material: Box<dyn Material>,
     /// Number of vertices supplied
     num_vertices: usize,
-    /// Whether to draw this mesh as a wireframe
-    pub draw_wireframe: bool,
+    /// Whether to draw this mesh as a wireframe, optionally on top of the filled polygons
+    pub wireframe_mode: WireframeMode,
     /// Index buffer, which is only present if the geometry was indexed.
     index_buffer: Option<Box<dyn BufferBase>>,
     /// Size of rendered points. Only used if primitive type is "Points".
     pub point_size: f32,
+    /// Whether the point size is set by the material's vertex shader instead of `point_size`.
+    /// Only used if primitive type is "Points".
+    pub program_point_size: bool,
     /// Width of lines. Only used if primitve type is any of the line types.
     pub line_width: f32,
     /// Controls primitive restart. If this is None, primitive restart will be disabled.
-    pub primitive_restart_index: Option<u32>
+    pub primitive_restart_index: Option<u32>,
+    /// Factor and units passed to glPolygonOffset when drawing filled polygons. This pushes the surfaces
+    /// away from the camera, so that lines lying on them are drawn cleanly. Line and point primitives
+    /// themselves are not affected by polygon offsets. If this is None, no offset is applied.
+    pub polygon_offset: Option<(f32, f32)>,
+    /// The color of the edges drawn in wireframe overlay mode
+    pub edge_color: Vec3,
+    /// The material the edges of the wireframe overlay are drawn with. It is only created once the overlay
+    /// is drawn for the first time.
+    edge_material: RefCell<Option<EdgeMaterial>>,
+    /// Whether a failure to enable the material was already logged. Since meshes are rendered every
+    /// frame, the error is only reported once.
+    error_reported: Cell<bool>
 }
 
 impl Mesh {
@@ -858,12 +1063,17 @@ impl Mesh {
             material: mat,
             vao: VertexArray::new(),
             buffers: Vec::new(),
-            draw_wireframe: false,
+            wireframe_mode: WireframeMode::Off,
             num_vertices: Self::retrieve_vertex_count(&attributes).expect("Geometry attribute buffer sizes inconsistent"),
             index_buffer: None,
             point_size: 1.0,
+            program_point_size: false,
             line_width: 1.0,
-            primitive_restart_index: None
+            primitive_restart_index: None,
+            polygon_offset: None,
+            edge_color: Vec3::new(0.05, 0.05, 0.05),
+            edge_material: RefCell::new(None),
+            error_reported: Cell::new(false)
         };
 
         // Create buffers and register attributes with vao for each attribute in the geometry
@@ -890,12 +1100,17 @@ impl Mesh {
             material: mat,
             vao: VertexArray::new(),
             buffers: Vec::new(),
-            draw_wireframe: false,
+            wireframe_mode: WireframeMode::Off,
             num_vertices: indices.len(),
             index_buffer: None,
             point_size: 1.0,
+            program_point_size: false,
             line_width: 1.0,
-            primitive_restart_index: None
+            primitive_restart_index: None,
+            polygon_offset: None,
+            edge_color: Vec3::new(0.05, 0.05, 0.05),
+            edge_material: RefCell::new(None),
+            error_reported: Cell::new(false)
         };
 
         // Create buffers and register attributes with vao for each attribute in the geometry
@@ -943,6 +1158,12 @@ impl Mesh {
         concrete_ref
     }
 
+    /// Retrieve mutable downcasted material reference, if the material is of given type
+    pub fn try_retrieve_material_mut_ref<T: Material + 'static>(&mut self) -> Option<&mut T> {
+        let rf = &mut *self.material;
+        rf.as_mut_any().downcast_mut::<T>()
+    }
+
     /// Try to retrieve the total vertex count for given set of attributes.
     /// This can fail if the number of entries in each of the attribute arrays is not the same.
     fn retrieve_vertex_count(attributes: &Vec<&dyn AttributeArrayBase>) -> Option<usize> {
@@ -964,20 +1185,19 @@ impl Mesh {
     }
 }
 
-impl Render for Mesh {
-    fn render(&self, params: &mut RenderParameters) {
-        self.material.enable_material(params);
-        self.vao.enable_array();
-
-        unsafe{
-            if self.draw_wireframe {
-                gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
-            }
-
+impl Mesh {
+    /// Issue the actual draw call, using whatever polygon mode is currently active.
+    /// NOTE: The material and vertex array must already be enabled!
+    fn draw(&self) {
+        unsafe {
             // Set special state based on primitive type
             match self.primitive_type {
                 PrimitiveType::Points => {
-                    gl::PointSize(self.point_size as _);
+                    if self.program_point_size {
+                        gl::Enable(gl::PROGRAM_POINT_SIZE);
+                    } else {
+                        gl::PointSize(self.point_size as _);
+                    }
                 },
                 PrimitiveType::LineLoop | PrimitiveType::Lines | PrimitiveType::LineStrip => {
                     gl::LineWidth(self.line_width as _);
@@ -1012,7 +1232,11 @@ impl Render for Mesh {
             // Reset special state based on primitive type
             match self.primitive_type {
                 PrimitiveType::Points => {
-                    gl::PointSize(1.0);
+                    if self.program_point_size {
+                        gl::Disable(gl::PROGRAM_POINT_SIZE);
+                    } else {
+                        gl::PointSize(1.0);
+                    }
                 },
                 PrimitiveType::LineLoop | PrimitiveType::Lines | PrimitiveType::LineStrip => {
                     gl::LineWidth(1.0);
@@ -1020,11 +1244,116 @@ impl Render for Mesh {
                 _ => {}
             }
 
-            if self.draw_wireframe {
+        }
+    }
+}
+
+impl Render for Mesh {
+    fn render(&self, params: &mut RenderParameters) {
+        // A material that can not be used at all would produce garbage, so the mesh is skipped instead
+        if let Err(e) = self.material.enable_material(params) {
+            if !self.error_reported.replace(true) {
+                eprintln!("Skipping mesh because its material could not be enabled: {}", e);
+            }
+
+            return;
+        }
+
+        self.vao.enable_array();
+
+        if let Some((factor, units)) = self.polygon_offset {
+            unsafe {
+                gl::Enable(gl::POLYGON_OFFSET_FILL);
+                gl::PolygonOffset(factor, units);
+            }
+        }
+
+        match self.wireframe_mode {
+            WireframeMode::Off => self.draw(),
+            WireframeMode::Only => unsafe {
+                gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
+                self.draw();
                 gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+            },
+            WireframeMode::Overlay => unsafe {
+                self.draw();
+
+                // The edges are drawn in a flat color, so they stand out from the shaded polygons
+                let mut edge_material = self.edge_material.borrow_mut();
+                let edge_material = edge_material.get_or_insert_with(|| EdgeMaterial::new(self.edge_color));
+                edge_material.color = self.edge_color;
+
+                match edge_material.enable_material(params) {
+                    Ok(()) => {
+                        // Pull the edges slightly towards the camera to avoid z-fighting with the
+                        // filled polygons drawn beforehand.
+                        gl::Enable(gl::POLYGON_OFFSET_LINE);
+                        gl::PolygonOffset(-1.0, -1.0);
+                        gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
+
+                        self.draw();
+
+                        gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+                        gl::Disable(gl::POLYGON_OFFSET_LINE);
+                    },
+                    Err(e) => {
+                        if !self.error_reported.replace(true) {
+                            eprintln!("Skipping wireframe overlay because its material could not be enabled: {}", e);
+                        }
+                    }
+                }
             }
         }
 
+        // Both the fill offset and the overlay offset have to be reset, so that they do not leak into other meshes
+        unsafe {
+            if let Some(_) = self.polygon_offset {
+                gl::Disable(gl::POLYGON_OFFSET_FILL);
+            }
+
+            gl::PolygonOffset(0.0, 0.0);
+        }
+
         self.vao.disable_array();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flat_strip_normals_are_consistent() {
+        let plane = PlaneGeometry::new(3, 3, Vec3::repeat(1.0));
+        let normals = NormalGenerator::generate_indexed_normals(plane.primitive_type(), &plane.positions.local_buffer, &plane.indices);
+
+        // Without flipping every second triangle of the strip, neighbouring faces would cancel out
+        for normal in &normals {
+            assert!(abs_diff_eq!(*normal, normals[0], epsilon = 1.0e-5));
+            assert!(abs_diff_eq!(normal.z.abs(), 1.0, epsilon = 1.0e-5));
+        }
+    }
+
+    #[test]
+    fn shared_vertices_are_averaged() {
+        // A roof with one slope on each side of the middle column of vertices
+        let plane = PlaneGeometry::with_displacement(1, 2, Vec3::repeat(1.0), &|u, v| Vec3::new(u, v, 0.5 - (u - 0.5).abs()));
+        let normals = &plane.normals.local_buffer;
+
+        // The strip is 0 3 1 4 2 5. Vertex 0 only touches the left slope, vertex 2 only the right one.
+        let left = normals[0];
+        let right = normals[2];
+
+        assert!(abs_diff_eq!(left.x, -right.x, epsilon = 1.0e-5));
+        assert!(abs_diff_eq!(left.z, right.z, epsilon = 1.0e-5));
+        assert!(left.x.abs() > 0.1);
+
+        // Vertex 1 is part of two triangles of the left slope and one of the right one, vertex 4 vice versa
+        assert!(abs_diff_eq!(normals[1], (left * 2.0 + right).normalize(), epsilon = 1.0e-5));
+        assert!(abs_diff_eq!(normals[4], (left + right * 2.0).normalize(), epsilon = 1.0e-5));
+
+        for normal in normals.iter() {
+            assert!(abs_diff_eq!(normal.norm(), 1.0, epsilon = 1.0e-5));
+        }
+    }
+}
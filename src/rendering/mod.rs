@@ -11,7 +11,9 @@ pub mod model;
 pub mod lighting;
 pub mod bezier;
 pub mod primitives;
+pub mod framebuffer;
 
+use std::fmt::Display;
 use crate::rendering::lighting::*;
 use nalgebra_glm::{Mat4, Vec3};
 
@@ -23,6 +25,43 @@ pub enum ValueType {
     UInt = gl::UNSIGNED_INT,
 }
 
+/// Errors that can occur while rendering a frame. These are not fatal: the affected operation is
+/// skipped and the error is logged, instead of aborting the whole application.
+#[derive(Debug, Clone)]
+pub enum RenderError {
+    /// The shader program has no active uniform with given name
+    UniformNotFound(String),
+    /// The matrix stack was popped more often than it was pushed
+    EmptyMatrixStack
+}
+
+impl Display for RenderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            &RenderError::UniformNotFound(name) => write!(f, "Could not find uniform location for uniform name \"{}\"", name),
+            &RenderError::EmptyMatrixStack => write!(f, "Matrix stack popped while empty")
+        }
+    }
+}
+
+/// Execute given drawing operations with depth testing disabled if requested, which draws them on top
+/// of all geometry regardless of depth. Depth testing is enabled again afterwards.
+pub fn draw_on_top<F: FnOnce()>(on_top: bool, draw: F) {
+    if on_top {
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+        }
+    }
+
+    draw();
+
+    if on_top {
+        unsafe {
+            gl::Enable(gl::DEPTH_TEST);
+        }
+    }
+}
+
 /// A class holding important rendering parameters, such as the view and projection matrices.
 /// It also allows the implementation of hierachical rendering by offering methods to accumulate
 /// model transformations.
@@ -39,7 +78,17 @@ pub struct RenderParameters {
     /// The current lighting context
     pub lighting: LightingContext,
     /// The position of the camera, in world space.
-    pub camera_position: Vec3
+    pub camera_position: Vec3,
+    /// The direction pointing to the right of the screen, in world space.
+    pub camera_right: Vec3,
+    /// The direction pointing to the top of the screen, in world space.
+    pub camera_up: Vec3,
+    /// Exponent applied to vertex colors by materials before shading. Colors are picked in sRGB, so
+    /// when rendering to an sRGB framebuffer they have to be decoded to linear space using a value of 2.2.
+    /// Otherwise, this is 1.0 and colors are used as-is.
+    pub color_gamma: f32,
+    /// The depth based fog applied by materials that support it
+    pub fog: FogSettings
 }
 
 impl RenderParameters {
@@ -51,7 +100,11 @@ impl RenderParameters {
             matrix_stack: Vec::new(),
             model: Mat4::identity(),
             lighting: LightingContext::new_default(),
-            camera_position: pos
+            camera_position: pos,
+            camera_right: Vec3::new(1.0, 0.0, 0.0),
+            camera_up: Vec3::new(0.0, 1.0, 0.0),
+            color_gamma: 1.0,
+            fog: FogSettings::disabled()
         }
     }
 
@@ -67,8 +120,10 @@ impl RenderParameters {
     }
 
     /// Restore the model matrix by replacing it with the top element of the matrix stack. 
-    pub fn pop_matrix(&mut self) {
-        self.model = self.matrix_stack.pop().expect("pop_matrix called on empty matrix stack");
+    /// Fails if the stack is empty, in which case the model matrix is left unchanged.
+    pub fn pop_matrix(&mut self) -> Result<(), RenderError> {
+        self.model = self.matrix_stack.pop().ok_or(RenderError::EmptyMatrixStack)?;
+        Ok(())
     }
 
     /// Add given translation to the model matrix.
@@ -120,4 +175,107 @@ impl Viewport {
             gl::Viewport(self.x as _, self.y as _, self.w as _, self.h as _);
         }
     }
+
+    /// Retrieve the dimensions of the OpenGL viewport that is currently active, in pixels.
+    pub fn current_size() -> (u32, u32) {
+        let mut viewport: [gl::types::GLint; 4] = [0; 4];
+
+        unsafe {
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+        }
+
+        (viewport[2].max(0) as _, viewport[3].max(0) as _)
+    }
+}
+
+/// Determine how many framebuffer pixels one screen coordinate spans along each axis. On HiDPI displays,
+/// GLFW reports window sizes and cursor positions in screen coordinates, which can be smaller than the
+/// framebuffer size in pixels. A minimized window has a size of zero, in which case a scale of one is assumed.
+pub fn pixel_scale(screen_size: (u32, u32), framebuffer_size: (u32, u32)) -> (f32, f32) {
+    let axis = |screen: u32, framebuffer: u32| {
+        if screen == 0 || framebuffer == 0 {
+            1.0
+        } else {
+            framebuffer as f32 / screen as f32
+        }
+    };
+
+    (axis(screen_size.0, framebuffer_size.0), axis(screen_size.1, framebuffer_size.1))
+}
+
+/// Convert given position in screen coordinates, such as the cursor position, to framebuffer pixels.
+pub fn screen_to_framebuffer(x: f32, y: f32, screen_size: (u32, u32), framebuffer_size: (u32, u32)) -> (f32, f32) {
+    let (sx, sy) = pixel_scale(screen_size, framebuffer_size);
+    (x * sx, y * sy)
+}
+
+/// Determine the scale the GUI font has to be drawn with for given window content scale. Platforms that
+/// already report screen coordinates in logical units, like macOS, scale the framebuffer instead, which
+/// must not be applied twice.
+pub fn gui_font_scale(content_scale: (f32, f32), pixel_scale: (f32, f32)) -> f32 {
+    let scale = content_scale.1 / pixel_scale.1;
+
+    if scale.is_finite() && scale > 0.0 {
+        scale
+    } else {
+        1.0
+    }
+}
+
+/// Determine the largest rectangle with the aspect ratio of given content size that fits into given screen
+/// size, centered on the screen. The result is given as x, y, width and height. The remaining area forms
+/// bars either above and below or left and right of the rectangle.
+pub fn letterbox(content_size: (u32, u32), screen_size: (u32, u32)) -> (u32, u32, u32, u32) {
+    let (cw, ch) = (content_size.0.max(1) as f64, content_size.1.max(1) as f64);
+    let (sw, sh) = (screen_size.0 as f64, screen_size.1 as f64);
+
+    let scale = (sw / cw).min(sh / ch);
+    let (w, h) = ((cw * scale).round(), (ch * scale).round());
+
+    (((sw - w) / 2.0) as _, ((sh - h) / 2.0) as _, w as _, h as _)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixel_scale_matches_framebuffer_ratio() {
+        assert_eq!(pixel_scale((800, 600), (800, 600)), (1.0, 1.0));
+        assert_eq!(pixel_scale((800, 600), (1600, 1200)), (2.0, 2.0));
+        assert_eq!(pixel_scale((800, 600), (1200, 1200)), (1.5, 2.0));
+    }
+
+    #[test]
+    fn pixel_scale_of_minimized_window_is_one() {
+        assert_eq!(pixel_scale((0, 0), (0, 0)), (1.0, 1.0));
+        assert_eq!(pixel_scale((800, 0), (1600, 0)), (2.0, 1.0));
+    }
+
+    #[test]
+    fn screen_positions_are_scaled_to_pixels() {
+        assert_eq!(screen_to_framebuffer(100.0, 50.0, (800, 600), (800, 600)), (100.0, 50.0));
+        assert_eq!(screen_to_framebuffer(100.0, 50.0, (800, 600), (1600, 1200)), (200.0, 100.0));
+        assert_eq!(screen_to_framebuffer(100.0, 50.0, (800, 600), (1200, 1200)), (150.0, 100.0));
+    }
+
+    #[test]
+    fn font_scale_is_not_applied_twice() {
+        // Windows and Linux report screen coordinates in pixels, but a content scale of two
+        assert_eq!(gui_font_scale((2.0, 2.0), (1.0, 1.0)), 2.0);
+        // macOS scales the framebuffer instead
+        assert_eq!(gui_font_scale((2.0, 2.0), (2.0, 2.0)), 1.0);
+        assert_eq!(gui_font_scale((1.0, 1.0), (1.0, 1.0)), 1.0);
+        assert_eq!(gui_font_scale((0.0, 0.0), (1.0, 1.0)), 1.0);
+    }
+
+    #[test]
+    fn letterbox_keeps_aspect_ratio() {
+        // Wider screen: bars left and right
+        assert_eq!(letterbox((1920, 1080), (1600, 600)), (266, 0, 1067, 600));
+        // Taller screen: bars above and below
+        assert_eq!(letterbox((1920, 1080), (960, 1000)), (0, 230, 960, 540));
+        // Matching aspect ratio at twice the size
+        assert_eq!(letterbox((800, 600), (1600, 1200)), (0, 0, 1600, 1200));
+    }
 }
@@ -115,7 +115,9 @@ impl Render for Model {
 
         self.storage.render(params);
 
-        params.pop_matrix();
+        if let Err(e) = params.pop_matrix() {
+            eprintln!("Rendering error: {}", e);
+        }
     }
 }
 
@@ -159,6 +161,8 @@ impl Render for MultiModel {
             model.render(params);
         }
         
-        params.pop_matrix();
+        if let Err(e) = params.pop_matrix() {
+            eprintln!("Rendering error: {}", e);
+        }
     }
 }
@@ -1,5 +1,7 @@
 use nalgebra_glm::{Mat4, Vec3, Vec2};
 use std::any::*;
+use std::collections::HashMap;
+use crate::data::LineJoinStyle;
 use crate::rendering::*;
 use crate::rendering::meshes::*;
 use crate::rendering::materials::*;
@@ -41,6 +43,49 @@ impl LineGeometry {
         self.indices.push(index0 as _);
         self.indices.push(index1 as _);
     }
+
+    /// Rebuild the indices in order to be rendered as lines with adjacency information. Each segment
+    /// gets extended by the begin vertex of a segment ending where it begins, and the end vertex of a
+    /// segment beginning where it ends. If there is no such neighbour, the segment's own vertex is used.
+    pub fn build_adjacency(&mut self) {
+        let positions = &self.positions.local_buffer;
+
+        // Positions are quantized in order to be usable as hash map keys
+        let key = |p: &Vec3| -> (i64, i64, i64) {
+            let scale = 100000.0;
+            ((p.x * scale).round() as _, (p.y * scale).round() as _, (p.z * scale).round() as _)
+        };
+
+        // Map from position to the first segment beginning/ending there
+        let mut begins = HashMap::new();
+        let mut ends = HashMap::new();
+
+        for segment in (0..positions.len()).step_by(2) {
+            begins.entry(key(&positions[segment])).or_insert(segment);
+            ends.entry(key(&positions[segment + 1])).or_insert(segment);
+        }
+
+        let mut indices = Vec::with_capacity(positions.len() * 2);
+
+        for segment in (0..positions.len()).step_by(2) {
+            let previous = match ends.get(&key(&positions[segment])) {
+                Some(&other) if other != segment => other,
+                _ => segment
+            };
+
+            let next = match begins.get(&key(&positions[segment + 1])) {
+                Some(&other) if other != segment => other + 1,
+                _ => segment + 1
+            };
+
+            indices.push(previous as _);
+            indices.push(segment as _);
+            indices.push((segment + 1) as _);
+            indices.push(next as _);
+        }
+
+        self.indices = indices;
+    }
 }
 
 impl Geometry for LineGeometry {
@@ -57,17 +102,20 @@ impl IndexedGeometry for LineGeometry {
 
 
 /// A material that uses a geometry shader to turn line segments into 2D lines
-/// based on triangle strips.
+/// based on triangle strips. The segments are expected to be supplied as lines with
+/// adjacency information, which is used to join consecutive segments.
 pub struct Line2DMaterial {
     /// The underlying shader program
     program: Program,
     /// The dimensions of the screen
-    pub screen_dimensions: (u32, u32)
+    pub screen_dimensions: (u32, u32),
+    /// How consecutive segments are joined
+    pub join_style: LineJoinStyle
 }
 
 impl Line2DMaterial {
     /// Create a new instance of this material.
-    pub fn new(screen_dimensions: (u32, u32)) -> Line2DMaterial {
+    pub fn new(screen_dimensions: (u32, u32), join_style: LineJoinStyle) -> Line2DMaterial {
         let mut shaders = vec![
             Shader::new_vertex(Self::VERTEX_SHADER_SOURCE).unwrap(),
             Shader::new_fragment(Self::FRAGMENT_SHADER_SOURCE).unwrap(),
@@ -78,21 +126,29 @@ impl Line2DMaterial {
             program: Program::from_shaders(
                 &mut shaders
             ).unwrap(),
-            screen_dimensions: screen_dimensions
+            screen_dimensions: screen_dimensions,
+            join_style: join_style
         }
     }
 }
 
 impl Material for Line2DMaterial {
-    fn enable_material(&self, params: &mut RenderParameters) {
+    fn enable_material(&self, params: &mut RenderParameters) -> Result<(), RenderError> {
         self.program.use_program();
 
-        self.program.set_uniform_mat4("projection", &params.projection);
-        self.program.set_uniform_mat4("view", &params.view);
-        self.program.set_uniform_mat4("model", &params.model);
-
         let dims = Vec2::new(self.screen_dimensions.0 as _, self.screen_dimensions.1 as _);
-        self.program.set_uniform_vec2("viewport", &dims);
+
+        self.program.report_uniform_errors(&[
+            self.program.set_uniform_mat4("projection", &params.projection),
+            self.program.set_uniform_mat4("view", &params.view),
+            self.program.set_uniform_mat4("model", &params.model),
+
+            self.program.set_uniform_vec2("viewport", &dims),
+            self.program.set_uniform_int("join_style", self.join_style as _),
+            self.program.set_uniform_float("ColorGamma", params.color_gamma)
+        ]);
+
+        Ok(())
     }
 
     /// Retrieve this instance as a reference to Any. This is used for downcasting.
@@ -137,11 +193,14 @@ impl Line2DMaterial {
     const GEOMETRY_SHADER_SOURCE: &'static str = r#"
         #version 330 core
 
-        layout(lines) in;
-        layout(triangle_strip, max_vertices = 4) out;
+        layout(lines_adjacency) in;
+        layout(triangle_strip, max_vertices = 38) out;
 
         // The screen dimensions in pixels
-        uniform vec2 viewport;   
+        uniform vec2 viewport;
+
+        // 0: No joins, 1: Miter joins, 2: Round joins
+        uniform int join_style;
 
         in Vertex 
         {
@@ -151,39 +210,95 @@ impl Line2DMaterial {
 
         out vec4 vertex_color;
 
-        
+        // Signed distance to the line center, relative to the line half width. Used for antialiasing.
+        out float edge_distance;
+
+        const int ROUND_SEGMENTS = 16;
+
+        // Position of given input vertex in pixels, relative to the screen center
+        vec2 toScreen(int i)
+        {
+            return (gl_in[i].gl_Position.xy / gl_in[i].gl_Position.w) * viewport * 0.5;
+        }
+
+        // Emit given input vertex, offset by given amount of pixels
+        void emit(int i, vec2 offset, float edge)
+        {
+            vec4 pos = gl_in[i].gl_Position;
+            gl_Position = vec4(pos.xy + (offset / (viewport * 0.5)) * pos.w, pos.z, pos.w);
+            vertex_color = vertex[i].color;
+            edge_distance = edge;
+            EmitVertex();
+        }
+
+        // Calculate the offset of the line edge at the joint b between segments a-b and b-c
+        vec2 miterOffset(vec2 a, vec2 b, vec2 c, vec2 normal, float halfWidth)
+        {
+            vec2 d0 = b - a;
+            vec2 d1 = c - b;
+
+            if (length(d0) < 1e-6 || length(d1) < 1e-6)
+                return normal * halfWidth;
+
+            vec2 tangent = normalize(d0) + normalize(d1);
+
+            // The segments point in opposite directions, there is no sensible miter
+            if (length(tangent) < 1e-6)
+                return normal * halfWidth;
+
+            tangent = normalize(tangent);
+            vec2 miter = vec2(-tangent.y, tangent.x);
+
+            // Limit the miter length for very sharp angles
+            return miter * (halfWidth / max(dot(miter, normal), 0.25));
+        }
+
         void main()
         {
-            float line_width = vertex[0].width;
+            vec2 s0 = toScreen(0);
+            vec2 s1 = toScreen(1);
+            vec2 s2 = toScreen(2);
+            vec2 s3 = toScreen(3);
 
-            vec3 ndc0 = gl_in[0].gl_Position.xyz / gl_in[0].gl_Position.w;
-            vec3 ndc1 = gl_in[1].gl_Position.xyz / gl_in[1].gl_Position.w;
+            vec2 direction = s2 - s1;
 
-            vec2 lineScreenForward = normalize(ndc1.xy - ndc0.xy);
-            vec2 lineScreenRight = vec2(-lineScreenForward.y, lineScreenForward.x);
-            vec2 lineScreenOffset = (vec2(line_width) / viewport) * lineScreenRight;
+            if (length(direction) < 1e-6)
+                return;
 
-            vec4 cpos0 = gl_in[0].gl_Position;
-            gl_Position = vec4(cpos0.xy + lineScreenOffset*cpos0.w, cpos0.z, cpos0.w);
-            vertex_color = vertex[0].color;
-            EmitVertex();
+            direction = normalize(direction);
+            vec2 normal = vec2(-direction.y, direction.x);
 
-            vec4 cpos1 = gl_in[0].gl_Position;
-            gl_Position = vec4(cpos1.xy - lineScreenOffset*cpos1.w, cpos1.z, cpos1.w);
-            vertex_color = vertex[0].color;
-            EmitVertex();
+            float halfWidth1 = vertex[1].width * 0.5;
+            float halfWidth2 = vertex[2].width * 0.5;
 
-            vec4 cpos2 = gl_in[1].gl_Position;
-            gl_Position = vec4(cpos2.xy + lineScreenOffset*cpos2.w, cpos2.z, cpos2.w);
-            vertex_color = vertex[1].color;
-            EmitVertex();
+            vec2 offset1 = normal * halfWidth1;
+            vec2 offset2 = normal * halfWidth2;
 
-            vec4 cpos3 = gl_in[1].gl_Position;
-            gl_Position = vec4(cpos3.xy - lineScreenOffset*cpos3.w, cpos3.z, cpos3.w);
-            vertex_color = vertex[1].color;
-            EmitVertex();
+            if (join_style == 1)
+            {
+                offset1 = miterOffset(s0, s1, s2, normal, halfWidth1);
+                offset2 = miterOffset(s1, s2, s3, normal, halfWidth2);
+            }
 
+            emit(1, offset1, 1.0);
+            emit(1, -offset1, -1.0);
+            emit(2, offset2, 1.0);
+            emit(2, -offset2, -1.0);
             EndPrimitive();
+
+            // Cover the joint with the next segment using a disc
+            if (join_style == 2 && length(s3 - s2) > 1e-6)
+            {
+                for (int i = 0; i <= ROUND_SEGMENTS; ++i)
+                {
+                    float angle = (float(i) / float(ROUND_SEGMENTS)) * 6.28318530718;
+
+                    emit(2, vec2(0.0), 0.0);
+                    emit(2, vec2(cos(angle), sin(angle)) * halfWidth2, 1.0);
+                }
+
+                EndPrimitive();
+            }
         }
     "#;
 
@@ -191,12 +306,20 @@ impl Line2DMaterial {
     const FRAGMENT_SHADER_SOURCE: &'static str = r#"
         #version 330 core
 
+        uniform float ColorGamma;
+
         in vec4 vertex_color;
+        in float edge_distance;
         out vec4 Out_Color;
 
         void main()
         {
-            Out_Color = vertex_color;
+            // Fade out the outermost pixel of the line edges
+            float distance = abs(edge_distance);
+            float smoothing = fwidth(distance);
+            float alpha = 1.0 - smoothstep(1.0 - smoothing, 1.0, distance);
+
+            Out_Color = vec4(pow(vertex_color.rgb, vec3(ColorGamma)), vertex_color.a * alpha);
         }
     "#;
 }
@@ -207,15 +330,17 @@ impl Line2DMaterial {
 
 
 /// A material that uses a geometry shader to turn line segments into 3D lines
-/// based on triangle strips.
+/// based on triangle strips. Each segment becomes a closed tube.
 pub struct Line3DMaterial {
     /// The underlying shader program
-    program: Program
+    program: Program,
+    /// Factor converting the line width attribute to the tube radius
+    pub radius_scale: f32
 }
 
 impl Line3DMaterial {
     /// Create a new instance of this material.
-    pub fn new() -> Line3DMaterial {
+    pub fn new(radius_scale: f32) -> Line3DMaterial {
         let mut shaders = vec![
             Shader::new_vertex(Self::VERTEX_SHADER_SOURCE).unwrap(),
             Shader::new_fragment(Self::FRAGMENT_SHADER_SOURCE).unwrap(),
@@ -225,22 +350,29 @@ impl Line3DMaterial {
         Line3DMaterial {
             program: Program::from_shaders(
                 &mut shaders
-            ).unwrap()
+            ).unwrap(),
+            radius_scale: radius_scale
         }
     }
 }
 
 impl Material for Line3DMaterial {
-    fn enable_material(&self, params: &mut RenderParameters) {
+    fn enable_material(&self, params: &mut RenderParameters) -> Result<(), RenderError> {
         self.program.use_program();
 
-        self.program.set_uniform_mat4("projection", &params.projection);
-        self.program.set_uniform_mat4("view", &params.view);
-        self.program.set_uniform_mat4("model", &params.model);
+        self.program.report_uniform_errors(&[
+            self.program.set_uniform_mat4("projection", &params.projection),
+            self.program.set_uniform_mat4("view", &params.view),
+            self.program.set_uniform_mat4("model", &params.model),
+            self.program.set_uniform_float("radius_scale", self.radius_scale),
+            self.program.set_uniform_float("ColorGamma", params.color_gamma),
+
+            self.program.set_uniform_vec3("AmbientIntensity", &params.lighting.ambient_intensity),
+            self.program.set_uniform_vec3("DirectionalIntensity", &params.lighting.directional_intensity),
+            self.program.set_uniform_vec3("DirectionalLight", &params.lighting.directional_light)
+        ]);
 
-        self.program.set_uniform_vec3("AmbientIntensity", &params.lighting.ambient_intensity);
-        self.program.set_uniform_vec3("DirectionalIntensity", &params.lighting.directional_intensity);
-        self.program.set_uniform_vec3("DirectionalLight", &params.lighting.directional_light);
+        Ok(())
     }
 
     /// Retrieve this instance as a reference to Any. This is used for downcasting.
@@ -282,12 +414,15 @@ impl Line3DMaterial {
         #version 330 core
 
         layout(lines) in;
-        layout(triangle_strip, max_vertices = 32) out;
+        layout(triangle_strip, max_vertices = 62) out;
 
         uniform mat4 projection;
         uniform mat4 view;
         uniform mat4 model;
 
+        // Factor converting line widths to tube radii
+        uniform float radius_scale;
+
         in Vertex 
         {
             vec4 color;
@@ -318,8 +453,8 @@ impl Line3DMaterial {
             vec3 perpx = normalize(createPerp(gl_in[1].gl_Position.xyz, gl_in[0].gl_Position.xyz));
             vec3 perpy = cross(normalize(axis), perpx);
 
-            float r1 = vertex[0].width / 1000.0;
-            float r2 = vertex[0].width / 1000.0;
+            float r1 = vertex[0].width * radius_scale;
+            float r2 = vertex[1].width * radius_scale;
 
             int segs = 16;
             for(int i=0; i<segs; i++) {
@@ -345,6 +480,29 @@ impl Line3DMaterial {
                 EmitVertex();       
             }
             EndPrimitive();   
+
+            // Close both tube ends with a cap. The rim vertices are emitted in zig-zag order,
+            // which allows a convex polygon to be drawn as a single triangle strip.
+            vec3 direction = normalize(axis);
+            int rim = segs - 1;
+
+            for(int end=0; end<2; end++) {
+                vec3 center = gl_in[end].gl_Position.xyz;
+                float r = (end == 0) ? r1 : r2;
+                vec3 cap_normal = (end == 0) ? -direction : direction;
+
+                for(int k=0; k<rim; k++) {
+                    int i = (k % 2 == 1) ? (k + 1) / 2 : (rim - k / 2) % rim;
+                    float a = i/float(rim) * 2.0 * 3.14159;
+                    vec3 offset = cos(a)*perpx + sin(a)*perpy;
+
+                    gl_Position = mvp * vec4(center + r*offset, 1.0);
+                    vertex_color = vertex[end].color;
+                    normal_vector = cap_normal;
+                    EmitVertex();
+                }
+                EndPrimitive();
+            }
         }
     "#;
 
@@ -355,6 +513,7 @@ impl Line3DMaterial {
         uniform vec3 AmbientIntensity;
         uniform vec3 DirectionalIntensity;
         uniform vec3 DirectionalLight;
+        uniform float ColorGamma;
 
         in vec4 vertex_color;
         in vec3 normal_vector;
@@ -368,9 +527,178 @@ impl Line3DMaterial {
             float diff = max(dot(normalize(normal_vector), normalize(DirectionalLight)), 0.0);
             vec3 diffuse = diff * DirectionalIntensity;
 
-            vec3 result = (diffuse + ambient) * vertex_color.xyz;
+            vec3 result = (diffuse + ambient) * pow(vertex_color.xyz, vec3(ColorGamma));
 
             Color = vec4(result, 1.0f);
         }
     "#;
 }
+
+
+/// A material that uses a geometry shader to turn line segments into flat quads that always
+/// face the camera. Unlike 2D lines, the width is given in world units, so lines get thinner
+/// with increasing distance to the camera.
+pub struct LineBillboardMaterial {
+    /// The underlying shader program
+    program: Program,
+    /// Factor converting the line width attribute to the quad width
+    pub width_scale: f32
+}
+
+impl LineBillboardMaterial {
+    /// Create a new instance of this material.
+    pub fn new(width_scale: f32) -> LineBillboardMaterial {
+        let mut shaders = vec![
+            Shader::new_vertex(Self::VERTEX_SHADER_SOURCE).unwrap(),
+            Shader::new_fragment(Self::FRAGMENT_SHADER_SOURCE).unwrap(),
+            Shader::new_geometry(Self::GEOMETRY_SHADER_SOURCE).unwrap()
+        ];
+
+        LineBillboardMaterial {
+            program: Program::from_shaders(
+                &mut shaders
+            ).unwrap(),
+            width_scale: width_scale
+        }
+    }
+}
+
+impl Material for LineBillboardMaterial {
+    fn enable_material(&self, params: &mut RenderParameters) -> Result<(), RenderError> {
+        self.program.use_program();
+
+        self.program.report_uniform_errors(&[
+            self.program.set_uniform_mat4("projection", &params.projection),
+            self.program.set_uniform_mat4("view", &params.view),
+            self.program.set_uniform_mat4("model", &params.model),
+            self.program.set_uniform_vec3("camera_right", &params.camera_right),
+            self.program.set_uniform_vec3("camera_up", &params.camera_up),
+            self.program.set_uniform_float("width_scale", self.width_scale),
+            self.program.set_uniform_float("ColorGamma", params.color_gamma)
+        ]);
+
+        Ok(())
+    }
+
+    /// Retrieve this instance as a reference to Any. This is used for downcasting.
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    /// Retrieve this instance as a mutable reference to Any. This is used for downcasting.
+    fn as_mut_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl LineBillboardMaterial {
+    /// The vertex shader source for this material
+    const VERTEX_SHADER_SOURCE: &'static str = r#"
+        #version 330 core
+
+        layout (location = 0) in vec3 Position;
+        layout (location = 1) in vec3 Color;
+        layout (location = 2) in float Width; 
+
+        out Vertex
+        {
+            vec4 color;
+            float width;
+        } vertex;
+
+        void main()
+        {
+            gl_Position = vec4(Position, 1.0);
+            vertex.color = vec4(Color, 1.0);
+            vertex.width = Width;
+        }
+    "#;
+
+    /// The geometry shader source for this material
+    const GEOMETRY_SHADER_SOURCE: &'static str = r#"
+        #version 330 core
+
+        layout(lines) in;
+        layout(triangle_strip, max_vertices = 4) out;
+
+        uniform mat4 projection;
+        uniform mat4 view;
+        uniform mat4 model;
+
+        // Screen axes in world space. The model matrix only scales and translates, so they
+        // can be used in model space as well.
+        uniform vec3 camera_right;
+        uniform vec3 camera_up;
+
+        // Factor converting line widths to quad widths
+        uniform float width_scale;
+
+        in Vertex 
+        {
+            vec4 color;
+            float width;
+        } vertex[];
+
+        out vec4 vertex_color;
+
+        // Signed distance to the line center, relative to the line half width. Used for antialiasing.
+        out float edge_distance;
+
+        void emit(int i, vec3 offset, float edge, mat4 mvp)
+        {
+            gl_Position = mvp * vec4(gl_in[i].gl_Position.xyz + offset, 1.0);
+            vertex_color = vertex[i].color;
+            edge_distance = edge;
+            EmitVertex();
+        }
+
+        void main()
+        {
+            mat4 mvp = projection * view * model;
+
+            vec3 axis = gl_in[1].gl_Position.xyz - gl_in[0].gl_Position.xyz;
+
+            // Direction of the segment in the screen plane
+            vec2 direction = vec2(dot(axis, camera_right), dot(axis, camera_up));
+
+            // Segments pointing straight at the camera have no visible extent
+            if (length(direction) < 1e-6)
+                return;
+
+            direction = normalize(direction);
+
+            // The quad is widened perpendicular to the segment, within the screen plane
+            vec3 side = -direction.y * camera_right + direction.x * camera_up;
+
+            vec3 offset0 = side * vertex[0].width * width_scale * 0.5;
+            vec3 offset1 = side * vertex[1].width * width_scale * 0.5;
+
+            emit(0, offset0, 1.0, mvp);
+            emit(0, -offset0, -1.0, mvp);
+            emit(1, offset1, 1.0, mvp);
+            emit(1, -offset1, -1.0, mvp);
+            EndPrimitive();
+        }
+    "#;
+
+    /// The fragment shader source for this material
+    const FRAGMENT_SHADER_SOURCE: &'static str = r#"
+        #version 330 core
+
+        uniform float ColorGamma;
+
+        in vec4 vertex_color;
+        in float edge_distance;
+        out vec4 Out_Color;
+
+        void main()
+        {
+            // Fade out the outermost pixel of the quad edges
+            float distance = abs(edge_distance);
+            float smoothing = fwidth(distance);
+            float alpha = 1.0 - smoothstep(1.0 - smoothing, 1.0, distance);
+
+            Out_Color = vec4(pow(vertex_color.rgb, vec3(ColorGamma)), vertex_color.a * alpha);
+        }
+    "#;
+}
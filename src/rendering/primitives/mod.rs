@@ -1 +1,2 @@
-pub mod line;
\ No newline at end of file
+pub mod line;
+pub mod point;
\ No newline at end of file
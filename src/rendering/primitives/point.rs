@@ -0,0 +1,105 @@
+use std::any::*;
+use crate::rendering::*;
+use crate::rendering::materials::*;
+use crate::rendering::shaders::*;
+use crate::rendering::uniforms::*;
+
+/// A material rendering round, anti-aliased points. Meshes using it have to enable
+/// `program_point_size`, since the point size is set in the vertex shader.
+pub struct PointMaterial {
+    /// The underlying shader program
+    program: Program,
+    /// Diameter of the rendered points, in pixels
+    pub point_size: f32
+}
+
+impl PointMaterial {
+    /// Create a new instance of this material.
+    pub fn new(point_size: f32) -> PointMaterial {
+        PointMaterial {
+            program: Program::from_source(Self::VERTEX_SHADER_SOURCE, Self::FRAGMENT_SHADER_SOURCE).unwrap(),
+            point_size: point_size
+        }
+    }
+}
+
+impl Material for PointMaterial {
+    fn enable_material(&self, params: &mut RenderParameters) -> Result<(), RenderError> {
+        self.program.use_program();
+
+        self.program.report_uniform_errors(&[
+            self.program.set_uniform_mat4("projection", &params.projection),
+            self.program.set_uniform_mat4("view", &params.view),
+            self.program.set_uniform_mat4("model", &params.model),
+            self.program.set_uniform_float("PointSize", self.point_size),
+            self.program.set_uniform_float("ColorGamma", params.color_gamma)
+        ]);
+
+        Ok(())
+    }
+
+    /// Retrieve this instance as a reference to Any. This is used for downcasting.
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    /// Retrieve this instance as a mutable reference to Any. This is used for downcasting.
+    fn as_mut_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl PointMaterial {
+    /// The vertex shader source for this material
+    const VERTEX_SHADER_SOURCE: &'static str = r#"
+        #version 330 core
+
+        layout (location = 0) in vec3 Position;
+        layout (location = 1) in vec3 Color;
+
+        uniform mat4 projection;
+        uniform mat4 view;
+        uniform mat4 model;
+        uniform float PointSize;
+
+        out VS_OUTPUT {
+            vec3 Color;
+        } OUT;
+
+        void main()
+        {
+            gl_Position = projection * view * model * vec4(Position, 1.0);
+            gl_PointSize = PointSize;
+            OUT.Color = Color;
+        }
+    "#;
+
+    /// The fragment shader source for this material. Fragments outside of the unit circle
+    /// are discarded, and the edge of the circle is smoothed over the width of one pixel.
+    const FRAGMENT_SHADER_SOURCE: &'static str = r#"
+        #version 330 core
+
+        in VS_OUTPUT {
+            vec3 Color;
+        } IN;
+
+        uniform float ColorGamma;
+
+        out vec4 Color;
+
+        void main()
+        {
+            // Map point coordinates from [0, 1] to [-1, 1]
+            vec2 coord = gl_PointCoord * 2.0 - 1.0;
+            float dist = length(coord);
+
+            // Derivatives have to be computed before any fragment might be discarded
+            float alpha = 1.0 - smoothstep(1.0 - fwidth(dist), 1.0, dist);
+
+            if(dist > 1.0)
+                discard;
+
+            Color = vec4(pow(IN.Color, vec3(ColorGamma)), alpha);
+        }
+    "#;
+}
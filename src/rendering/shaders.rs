@@ -2,6 +2,8 @@ use std::ffi::CString;
 use std::ptr;
 use std::fmt::Display;
 use std::string::*;
+use std::cell::RefCell;
+use std::collections::HashSet;
 use gl::types::*;
 use crate::rendering::types::{GlHandle};
 
@@ -118,7 +120,10 @@ impl Drop for Shader {
 /// and a vertex shader.
 pub struct Program {
     /// The program object handle
-    pub handle: GlHandle
+    pub handle: GlHandle,
+    /// Error messages of failed uniform updates that were already logged. Since uniforms are set every
+    /// frame, each failure is only reported once.
+    pub(crate) reported_errors: RefCell<HashSet<String>>
 }
 
 /// Creation
@@ -162,7 +167,8 @@ impl Program {
                 }
             } else {
                 return Ok(Program {
-                    handle: handle
+                    handle: handle,
+                    reported_errors: RefCell::new(HashSet::new())
                 })
             }
         }
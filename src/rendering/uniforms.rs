@@ -3,13 +3,14 @@ use gl::types::*;
 use std::ffi::CString;
 use std::ptr;
 use crate::rendering::shaders::*;
+use crate::rendering::RenderError;
 
 /// Uniform handling
 impl Program {
     /// Set 4x4 matrix uniform on this program object
     /// TODO: Taking adress of the matrix like this might not work!
-    pub fn set_uniform_mat4(&self, name: &str, matrix: &Mat4) {
-        let loc = self.query_location(name);
+    pub fn set_uniform_mat4(&self, name: &str, matrix: &Mat4) -> Result<(), RenderError> {
+        let loc = self.query_location(name)?;
 
         unsafe {
             gl::UniformMatrix4fv(
@@ -19,11 +20,13 @@ impl Program {
                 matrix as *const Mat4 as *const _
             );
         }
+
+        Ok(())
     }
 
     /// Set Vec3 uniform on this program object
-    pub fn set_uniform_vec3(&self, name: &str, vec: &Vec3) {
-        let loc = self.query_location(name);
+    pub fn set_uniform_vec3(&self, name: &str, vec: &Vec3) -> Result<(), RenderError> {
+        let loc = self.query_location(name)?;
 
         unsafe {
             gl::Uniform3fv(
@@ -32,11 +35,13 @@ impl Program {
                 vec as *const Vec3 as *const _
             );
         }
+
+        Ok(())
     }
 
     /// Set Vec2 uniform on this program object
-    pub fn set_uniform_vec2(&self, name: &str, vec: &Vec2) {
-        let loc = self.query_location(name);
+    pub fn set_uniform_vec2(&self, name: &str, vec: &Vec2) -> Result<(), RenderError> {
+        let loc = self.query_location(name)?;
 
         unsafe {
             gl::Uniform2fv(
@@ -45,11 +50,13 @@ impl Program {
                 vec as *const Vec2 as *const _
             );
         }
+
+        Ok(())
     }
 
     /// Set f32 uniform on this program object
-    pub fn set_uniform_float(&self, name: &str, value: f32) {
-        let loc = self.query_location(name);
+    pub fn set_uniform_float(&self, name: &str, value: f32) -> Result<(), RenderError> {
+        let loc = self.query_location(name)?;
 
         unsafe {
             gl::Uniform1fv(
@@ -58,18 +65,44 @@ impl Program {
                 &value as *const f32 as *const _
             );
         }
+
+        Ok(())
+    }
+
+    /// Set i32 uniform on this program object
+    pub fn set_uniform_int(&self, name: &str, value: i32) -> Result<(), RenderError> {
+        let loc = self.query_location(name)?;
+
+        unsafe {
+            gl::Uniform1i(loc, value);
+        }
+
+        Ok(())
+    }
+
+    /// Log the errors of given uniform updates. This is used by materials to skip missing uniforms instead of
+    /// aborting, so that the remaining uniforms are still set. Each error is only logged once per program.
+    pub fn report_uniform_errors(&self, results: &[Result<(), RenderError>]) {
+        for result in results {
+            if let Err(e) = result {
+                if self.reported_errors.borrow_mut().insert(e.to_string()) {
+                    eprintln!("Skipping uniform update: {}", e);
+                }
+            }
+        }
     }
 
-    /// Retrieve uniform location for given name string
-    fn query_location(&self, name: &str) -> GLint {
+    /// Retrieve uniform location for given name string. Fails if the program has no active uniform
+    /// with that name, which also happens if the shader compiler optimized an unused uniform away.
+    fn query_location(&self, name: &str) -> Result<GLint, RenderError> {
         unsafe {
             let name_cstr = CString::new(name.as_bytes()).unwrap();
             let loc = gl::GetUniformLocation(self.handle, name_cstr.as_ptr());
 
             if loc == -1 {
-                panic!("Could not find uniform location for uniform name \"{}\"", name);
+                Err(RenderError::UniformNotFound(name.to_string()))
             } else {
-                loc
+                Ok(loc)
             }
         }
     }
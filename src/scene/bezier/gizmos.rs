@@ -1,21 +1,33 @@
-use nalgebra_glm::Vec3;
+use nalgebra_glm::{Vec3, Mat4};
+use ncollide3d::*;
+use ncollide3d::math::*;
+use ncollide3d::query::*;
+extern crate nalgebra;
 
 use crate::rendering::*;
 use crate::rendering::meshes::*;
 use crate::rendering::materials::*;
 use crate::rendering::traits::*;
+use crate::rendering::model::*;
 use crate::rendering::shaders::*;
 
-/// A gizmo visualizing the coordinate system origin and the three cardinal axises
+/// A gizmo visualizing the coordinate system origin and the three cardinal axises. Optionally, tick marks
+/// are drawn along the axises at regular intervals to give a sense of scale.
 pub struct OriginGizmo {
     /// The mesh containing the line data for this gizmo
-    mesh: Mesh
+    mesh: Mesh,
+    /// Position and coordinate value of each tick mark
+    ticks: Vec<(Vec3, f32)>,
+    /// Whether the gizmo is drawn on top of all other geometry, instead of being occluded by it
+    pub always_on_top: bool
 }
 
 impl OriginGizmo {
-    /// Create new origin gizmo with the given axis length and thickness
-    pub fn new(axis_length: f32, axis_thickness: f32) -> OriginGizmo {
+    /// Create new origin gizmo with the given axis length and thickness. If a tick spacing is given,
+    /// tick marks are placed along the axises at multiples of it.
+    pub fn new(axis_length: f32, axis_thickness: f32, tick_spacing: Option<f32>) -> OriginGizmo {
         let mut vertices = Vec::new();
+        let mut ticks = Vec::new();
 
         // x Axis is red
         vertices.push(Vertex::new(Vec3::zeros(), Vec3::new(1.0, 0.0, 0.0)));
@@ -29,6 +41,30 @@ impl OriginGizmo {
         vertices.push(Vertex::new(Vec3::zeros(), Vec3::new(0.0, 0.0, 1.0)));
         vertices.push(Vertex::new(Vec3::z() * axis_length, Vec3::new(0.0, 0.0, 1.0)));
 
+        if let Some(spacing) = tick_spacing.filter(|s| *s > 0.0) {
+            let tick_size = axis_length * 0.04;
+            let count = (axis_length / spacing + 0.0001).floor() as u32;
+
+            // Each tick is a short line perpendicular to its axis
+            let axises = [
+                (Vec3::x(), Vec3::y(), Vec3::new(1.0, 0.0, 0.0)),
+                (Vec3::y(), Vec3::x(), Vec3::new(0.0, 1.0, 0.0)),
+                (Vec3::z(), Vec3::x(), Vec3::new(0.0, 0.0, 1.0))
+            ];
+
+            for (direction, perpendicular, color) in &axises {
+                for i in 1..=count {
+                    let value = spacing * i as f32;
+                    let position = direction * value;
+
+                    vertices.push(Vertex::new(position - perpendicular * tick_size, *color));
+                    vertices.push(Vertex::new(position + perpendicular * tick_size, *color));
+
+                    ticks.push((position, value));
+                }
+            }
+        }
+
         let geometry = BasicGeometry::from_vertices(&vertices);
 
         let material = Box::new(SimpleMaterial::new());
@@ -37,13 +73,233 @@ impl OriginGizmo {
         mesh.line_width = axis_thickness;
 
         OriginGizmo {
-            mesh: mesh
+            mesh: mesh,
+            ticks: ticks,
+            always_on_top: false
         }
     }
+
+    /// Retrieve the world space position and coordinate value of each tick mark, which can be used to
+    /// draw labels next to them.
+    pub fn ticks(&self) -> &[(Vec3, f32)] {
+        &self.ticks
+    }
 }
 
 impl Render for OriginGizmo {
     fn render(&self, rp: &mut RenderParameters) {
-        self.mesh.render(rp);
+        draw_on_top(self.always_on_top, || self.mesh.render(rp));
     }
-}
\ No newline at end of file
+}
+
+/// An interactive gizmo that allows translating a bezier patch along the cardinal axises. It consists of
+/// three axis lines starting at the anchor point, with a draggable handle at the end of each of them.
+pub struct TransformGizmo {
+    /// The mesh containing the axis lines
+    axis_mesh: Mesh,
+    /// The handle models, one for each axis
+    handles: Vec<Model>,
+    /// Length of the axis lines
+    axis_length: f32,
+    /// Radius of the spherical handles
+    handle_radius: f32,
+    /// The point the gizmo is anchored at, in world space
+    pub anchor: Vec3,
+    /// Whether the gizmo is drawn on top of all other geometry, instead of being occluded by it. The handles
+    /// can then no longer be clicked through other geometry, since they do not write to the depth buffer.
+    pub always_on_top: bool
+}
+
+impl TransformGizmo {
+    /// Create new transform gizmo with given axis length and handle radius
+    pub fn new(axis_length: f32, handle_radius: f32) -> TransformGizmo {
+        let mut vertices = Vec::new();
+        let mut handles = Vec::new();
+
+        for axis in 0..3 {
+            let color = Self::axis_color(axis);
+            let direction = Self::axis_direction(axis);
+
+            vertices.push(Vertex::new(Vec3::zeros(), color));
+            vertices.push(Vertex::new(direction * axis_length, color));
+
+            let geometry = SphereGeometry::new(handle_radius, 20, 20, color);
+            let mesh = Mesh::new_indexed(PrimitiveType::TriangleStrip, Box::new(SimpleMaterial::new()), &geometry);
+
+            handles.push(Model::from_mesh_transformed(mesh, Mat4::new_translation(&(direction * axis_length))));
+        }
+
+        let mut axis_mesh = Mesh::new(PrimitiveType::Lines, Box::new(SimpleMaterial::new()), &BasicGeometry::from_vertices(&vertices));
+        axis_mesh.line_width = 2.5;
+
+        TransformGizmo {
+            axis_mesh: axis_mesh,
+            handles: handles,
+            axis_length: axis_length,
+            handle_radius: handle_radius,
+            anchor: Vec3::zeros(),
+            always_on_top: false
+        }
+    }
+
+    /// Retrieve the unit direction vector of the axis with given index
+    pub fn axis_direction(axis: usize) -> Vec3 {
+        match axis {
+            0 => Vec3::x(),
+            1 => Vec3::y(),
+            _ => Vec3::z()
+        }
+    }
+
+    /// Retrieve the color used for the axis with given index. This matches the origin gizmo.
+    fn axis_color(axis: usize) -> Vec3 {
+        match axis {
+            0 => Vec3::new(1.0, 0.0, 0.0),
+            1 => Vec3::new(0.0, 1.0, 0.0),
+            _ => Vec3::new(0.0, 0.0, 1.0)
+        }
+    }
+
+    /// Retrieve the world space position of the handle for the axis with given index
+    pub fn handle_position(&self, axis: usize) -> Vec3 {
+        self.anchor + Self::axis_direction(axis) * self.axis_length
+    }
+
+    /// Determine which handle, if any, was clicked, given the unprojected world space position
+    /// of the click.
+    pub fn find_clicked_handle(&self, position: &Vec3) -> Option<usize> {
+        let sphere = shape::Ball::<f32>::new(self.handle_radius);
+        let position_isometry = Isometry::new(position.clone(), nalgebra::zero());
+
+        (0..3).find(|&axis| {
+            let translation = Isometry::new(self.handle_position(axis), nalgebra::zero());
+
+            match proximity(&position_isometry, &sphere, &translation, &sphere, 0.01) {
+                Proximity::Intersecting => true,
+                _ => false
+            }
+        })
+    }
+}
+
+impl Render for TransformGizmo {
+    fn render(&self, rp: &mut RenderParameters) {
+        rp.push_matrix();
+        rp.translate(&self.anchor);
+
+        draw_on_top(self.always_on_top, || {
+            self.axis_mesh.render(rp);
+
+            for handle in &self.handles {
+                handle.render(rp);
+            }
+        });
+
+        if let Err(e) = rp.pop_matrix() {
+            eprintln!("Rendering error: {}", e);
+        }
+    }
+}
+
+/// An interactive gizmo visualizing the direction of the directional light. It consists of a line starting
+/// at the anchor point and pointing towards the light, with a draggable handle at its end.
+pub struct LightGizmo {
+    /// The mesh containing the direction line
+    line_mesh: Mesh,
+    /// The handle model
+    handle: Model,
+    /// Length of the direction line
+    length: f32,
+    /// Radius of the spherical handle
+    handle_radius: f32,
+    /// Normalized direction towards the light
+    direction: Vec3,
+    /// The point the gizmo is anchored at, in world space
+    pub anchor: Vec3,
+    /// Whether the gizmo is drawn on top of all other geometry, instead of being occluded by it
+    pub always_on_top: bool
+}
+
+impl LightGizmo {
+    /// The color used for the line and the handle
+    const COLOR: [f32; 3] = [1.0, 0.85, 0.2];
+
+    /// Create new light gizmo with given line length and handle radius, pointing in given direction.
+    pub fn new(length: f32, handle_radius: f32, direction: &Vec3) -> LightGizmo {
+        let color = Vec3::from(Self::COLOR);
+
+        let geometry = SphereGeometry::new(handle_radius, 20, 20, color);
+        let mesh = Mesh::new_indexed(PrimitiveType::TriangleStrip, Box::new(SimpleMaterial::new()), &geometry);
+
+        let mut gizmo = LightGizmo {
+            line_mesh: Self::create_line_mesh(&Vec3::y(), length),
+            handle: Model::from_mesh(mesh),
+            length: length,
+            handle_radius: handle_radius,
+            direction: Vec3::y(),
+            anchor: Vec3::zeros(),
+            always_on_top: false
+        };
+
+        gizmo.set_direction(direction);
+        gizmo
+    }
+
+    /// Point the gizmo in given direction. Directions of zero length are ignored.
+    pub fn set_direction(&mut self, direction: &Vec3) {
+        if direction.norm() <= 0.0 {
+            return;
+        }
+
+        self.direction = direction.normalize();
+        self.line_mesh = Self::create_line_mesh(&self.direction, self.length);
+        self.handle.transform = Mat4::new_translation(&(self.direction * self.length));
+    }
+
+    /// Retrieve the world space position of the handle
+    pub fn handle_position(&self) -> Vec3 {
+        self.anchor + self.direction * self.length
+    }
+
+    /// Check whether the handle was clicked, given the unprojected world space position of the click.
+    pub fn is_handle_clicked(&self, position: &Vec3) -> bool {
+        let sphere = shape::Ball::<f32>::new(self.handle_radius);
+        let position_isometry = Isometry::new(position.clone(), nalgebra::zero());
+        let translation = Isometry::new(self.handle_position(), nalgebra::zero());
+
+        match proximity(&position_isometry, &sphere, &translation, &sphere, 0.01) {
+            Proximity::Intersecting => true,
+            _ => false
+        }
+    }
+
+    /// Create the mesh for the direction line
+    fn create_line_mesh(direction: &Vec3, length: f32) -> Mesh {
+        let color = Vec3::from(Self::COLOR);
+
+        let vertices = vec![
+            Vertex::new(Vec3::zeros(), color),
+            Vertex::new(direction * length, color)
+        ];
+
+        let mut mesh = Mesh::new(PrimitiveType::Lines, Box::new(SimpleMaterial::new()), &BasicGeometry::from_vertices(&vertices));
+        mesh.line_width = 2.5;
+        mesh
+    }
+}
+
+impl Render for LightGizmo {
+    fn render(&self, rp: &mut RenderParameters) {
+        rp.push_matrix();
+        rp.translate(&self.anchor);
+
+        draw_on_top(self.always_on_top, || {
+            self.line_mesh.render(rp);
+            self.handle.render(rp);
+        });
+
+        if let Err(e) = rp.pop_matrix() {
+            eprintln!("Rendering error: {}", e);
+        }
+    }
+}
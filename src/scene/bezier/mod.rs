@@ -17,16 +17,49 @@ use crate::rendering::traits::*;
 use crate::rendering::model::*;
 use crate::rendering::lighting::*;
 use crate::scene::lsystem::normal_test_material::*;
+use crate::scene::lsystem::normal_color_test_material::*;
+use crate::scene::lsystem::bounding_box::*;
 use crate::scene::bezier::gizmos::*;
 use crate::gui_utils::*;
 extern crate glfw;
 
 mod gizmos;
 
+/// Radius of the spheres visualizing the control points. Picking uses the same radius, so that
+/// clicks register where the points are actually drawn.
+const CONTROL_POINT_RADIUS: f32 = 0.01;
+
+/// Number of slices and tiles of the control point spheres. They are tiny on screen, so a low
+/// resolution is sufficient.
+const CONTROL_POINT_RESOLUTION: u32 = 12;
+
+/// How long the confirmation messages of the editor are shown, in seconds.
+const FLASH_DURATION: f64 = 2.0;
+
+/// The state shared between a bezier editor and the scene that opened it. The owning scene checks it
+/// every frame, even while the editor is shown on top of it.
+pub struct BezierModelEdit {
+    /// The model as it was last saved by the editor. It is committed once the editor is closed.
+    pub saved: Option<BezierModelParameters>,
+    /// Model parameters the owning scene should show in its preview. This acts as a dirty flag and
+    /// is taken by the owning scene once the preview was refreshed.
+    pub preview: Option<BezierModelParameters>
+}
+
+impl BezierModelEdit {
+    /// Create a new, unmodified edit state.
+    pub fn new() -> BezierModelEdit {
+        BezierModelEdit {
+            saved: None,
+            preview: None
+        }
+    }
+}
+
 pub struct BezierEditorScene {
-    /// Reference to the model to edit. This will only be modifed once the user
-    /// hit "save".
-    model: RcCell<BezierModelParameters>,
+    /// Reference to the edit state shared with the scene owning the model. The model will only be
+    /// modifed once the user hit "save".
+    edit: RcCell<BezierModelEdit>,
     /// The copy of the model parameters the GUI operates on.
     working_copy: BezierModelParameters,
     /// Camera used to view the patches
@@ -41,28 +74,80 @@ pub struct BezierEditorScene {
     normal_vector_vis: Vec<Mesh>,
     /// Whether to draw the control curves
     draw_control_curves: bool,
+    /// Whether to draw the full control net, connecting the control points across the curves as well.
+    /// This overrides the control curves setting.
+    draw_control_cage: bool,
     /// Whether to draw the normal vectors
     draw_normal_vectors: bool,
+    /// Length of the drawn normal vectors
+    normal_length: f32,
     /// Screen width
     width: u32,
     /// Screen height
     height: u32,
     /// The sphere mesh used to visualize the control points. Its shared with all control point models.
     sphere_mesh: Rc<Mesh>,
+    /// The sphere mesh used to visualize selected control points. It only differs in color.
+    selected_sphere_mesh: Rc<Mesh>,
     /// Where the mouse drag started
     drag_begin: Option<(u32, u32)>,
     /// Depth of the point we are dragging
     drag_depth: Option<f32>,
-    /// The indices of the patch, curve and point that is currently being dragged.
+    /// The indices of the patch, curve and point that is currently being dragged. This stays set
+    /// after the mouse button is released, so that the point can be nudged using the keyboard.
     dragged_point: Option<(usize, usize, usize)>,
+    /// The indices of the control point that mirrors the currently dragged one, if mirror editing
+    /// is active and such a partner point exists.
+    mirrored_point: Option<(usize, usize, usize)>,
+    /// The plane used for live mirror editing. If this is None, mirror editing is disabled.
+    mirror_mode: Option<MirrorPlane>,
+    /// Grid step used to snap dragged control points. If this is None, snapping is disabled.
+    snap_step: Option<f32>,
+    /// The indices of all currently selected control points. Dragging one of them will move
+    /// all of them by the same world space delta.
+    selected_points: Vec<(usize, usize, usize)>,
+    /// The factor used by the "Scale" model operation
+    scale_factor: f32,
+    /// A patch copied using the "Copy" button, whose control points can be pasted into other patches or which
+    /// can be added as a new patch.
+    clipboard: Option<BezierPatchParameters>,
     /// Whether we are currently dragging
     in_drag: bool,
     /// The scenes lights
     lights: LightingContext,
     /// The gizmo visualizing the cardinal axises
     axis_gizmo: OriginGizmo,
+    /// Whether the axis gizmo shows tick marks and labels
+    show_axis_ticks: bool,
+    /// Whether all gizmos are drawn on top of the patches
+    gizmos_on_top: bool,
+    /// Whether to show the world space position under the mouse cursor in a tooltip
+    show_cursor_position: bool,
+    /// The gizmo used to translate a whole patch
+    transform_gizmo: TransformGizmo,
+    /// The index of the patch the transform gizmo is attached to. If this is None, the gizmo is hidden.
+    gizmo_patch: Option<usize>,
+    /// The axis of the gizmo handle currently being dragged, together with the depth and the last world
+    /// space position of the drag.
+    gizmo_drag: Option<(usize, f32, Vec3)>,
+    /// The gizmo visualizing and controlling the direction of the directional light
+    light_gizmo: LightGizmo,
+    /// Whether the light gizmo is shown
+    show_light_gizmo: bool,
+    /// The depth at which the light gizmo handle is being dragged, if it currently is.
+    light_drag: Option<f32>,
+    /// Whether the editor window is shown
+    gui_visible: bool,
+    /// The current time in seconds, as provided by the scene manager.
+    current_time: f64,
+    /// A short confirmation message shown below the editor buttons, together with the time at which
+    /// it disappears.
+    flash: Option<(String, f64)>,
     /// Flags describing whether the subpatches are shown in the viewport or not
     active: Vec<bool>,
+    /// The index of the currently soloed patch together with the active flags from before soloing it.
+    /// If this is None, no patch is soloed.
+    solo: Option<(usize, Vec<bool>)>,
     /// GUI helper that remembers for which bezier model a certain operation is refering to.
     /// This is needed since for popups to work, they have to be continuously be called, even
     /// long after the information about what button associated with what model has caused this.
@@ -72,35 +157,62 @@ pub struct BezierEditorScene {
 }
 
 impl BezierEditorScene {
-    pub fn new(model: RcCell<BezierModelParameters>, w: u32, h: u32) -> BezierEditorScene {
+    pub fn new(model: &BezierModelParameters, edit: RcCell<BezierModelEdit>, w: u32, h: u32, normal_length: f32) -> BezierEditorScene {
         let mat = Box::new(SimpleMaterial::new());
-        let sphere_geom = SphereGeometry::new(0.01, 40, 40, Vec3::new(1.0, 1.0, 1.0));
+        let sphere_geom = SphereGeometry::new(CONTROL_POINT_RADIUS, CONTROL_POINT_RESOLUTION, CONTROL_POINT_RESOLUTION, Vec3::new(1.0, 1.0, 1.0));
 
-        let mut mesh = Mesh::new_indexed(PrimitiveType::TriangleStrip, mat, &sphere_geom);
-        mesh.draw_wireframe = false;
+        let mesh = Mesh::new_indexed(PrimitiveType::TriangleStrip, mat, &sphere_geom);
 
-        let working_copy = model.borrow().clone();
+        let selected_mat = Box::new(SimpleMaterial::new());
+        let selected_sphere_geom = SphereGeometry::new(CONTROL_POINT_RADIUS, CONTROL_POINT_RESOLUTION, CONTROL_POINT_RESOLUTION, Vec3::new(1.0, 0.5, 0.0));
+        let selected_mesh = Mesh::new_indexed(PrimitiveType::TriangleStrip, selected_mat, &selected_sphere_geom);
+
+        let working_copy = model.clone();
         let active = vec![true; working_copy.patches.len()];
+        let lights = LightingContext::new_default();
+        let light_gizmo = LightGizmo::new(0.25, 0.02, &lights.directional_light);
         let mut scene = BezierEditorScene {
             working_copy: working_copy,
-            model: model,
+            edit: edit,
             camera: Camera::new(w, h, ProjectionType::Perspective(75.0)),
             meshes: Vec::new(),
             control_point_models: Vec::new(),
             control_curve_meshes: Vec::new(),
             normal_vector_vis: Vec::new(),
             draw_control_curves: true,
+            draw_control_cage: false,
             width: w,
             height: h,
             sphere_mesh: Rc::new(mesh),
+            selected_sphere_mesh: Rc::new(selected_mesh),
+            scale_factor: 2.0,
+            clipboard: None,
             in_drag: false,
             drag_depth: None,
             drag_begin: None,
             dragged_point: None,
-            lights: LightingContext::new_default(),
+            mirrored_point: None,
+            mirror_mode: None,
+            snap_step: None,
+            selected_points: Vec::new(),
+            lights: lights,
             draw_normal_vectors: false,
-            axis_gizmo: OriginGizmo::new(0.3, 3.5),
+            normal_length: normal_length,
+            axis_gizmo: Self::create_axis_gizmo(true),
+            show_axis_ticks: true,
+            gizmos_on_top: false,
+            show_cursor_position: false,
+            transform_gizmo: TransformGizmo::new(0.2, 0.015),
+            gizmo_patch: None,
+            gizmo_drag: None,
+            light_gizmo: light_gizmo,
+            show_light_gizmo: false,
+            light_drag: None,
+            gui_visible: true,
+            current_time: 0.0,
+            flash: None,
             active: active,
+            solo: None,
             gui_cached_id: None
         };
 
@@ -111,6 +223,57 @@ impl BezierEditorScene {
 }
 
 impl BezierEditorScene {
+    /// Show only the patch with given index, hiding all others. The previous set of active
+    /// patches is remembered, so that it can be restored by `unsolo`.
+    fn solo(&mut self, index: usize) {
+        let previous = match self.solo.take() {
+            // Switching between soloed patches keeps the original set
+            Some((_, previous)) => previous,
+            None => self.active.clone()
+        };
+
+        for (i, active) in self.active.iter_mut().enumerate() {
+            *active = i == index;
+        }
+
+        self.solo = Some((index, previous));
+    }
+
+    /// Restore the set of active patches from before a patch was soloed. Patches that were added
+    /// in the meantime are shown.
+    fn unsolo(&mut self) {
+        if let Some((_, mut previous)) = self.solo.take() {
+            previous.resize(self.active.len(), true);
+            self.active = previous;
+        }
+    }
+
+    /// Swap the patches with given indices. All state referring to patches by index is updated, so that it
+    /// keeps referring to the same patches.
+    fn swap_patches(&mut self, a: usize, b: usize) {
+        let remap = |index: usize| if index == a { b } else if index == b { a } else { index };
+        let remap_point = |(patch, curve, point): (usize, usize, usize)| (remap(patch), curve, point);
+
+        self.working_copy.patches.swap(a, b);
+        self.active.swap(a, b);
+
+        if let Some((soloed, previous)) = &mut self.solo {
+            *soloed = remap(*soloed);
+            previous.swap(a, b);
+        }
+
+        for point in self.selected_points.iter_mut() {
+            *point = remap_point(*point);
+        }
+
+        self.dragged_point = self.dragged_point.map(remap_point);
+        self.mirrored_point = self.mirrored_point.map(remap_point);
+        self.gizmo_patch = self.gizmo_patch.map(remap);
+        self.gui_cached_id = self.gui_cached_id.map(remap);
+
+        self.refresh_meshes();
+    }
+
     /// Just refresh the mesh for the patch with given index
     fn refresh_mesh_for(&mut self, index: usize) {
         let patch = &self.working_copy.patches[index];
@@ -118,7 +281,7 @@ impl BezierEditorScene {
         let mesh = self.create_mesh(patch);
         self.meshes[index] = mesh;
 
-        let control_point_model = self.create_control_point_model(patch);
+        let control_point_model = self.create_control_point_model(index, patch);
         self.control_point_models[index] = control_point_model;
 
         let control_curve_mesh = self.create_control_curve_mesh(patch);
@@ -128,10 +291,76 @@ impl BezierEditorScene {
             let normal_mesh = self.create_normal_mesh(patch);
             self.normal_vector_vis[index] = normal_mesh;
         }
+
+        self.refresh_gizmo_anchor();
+        self.refresh_light_gizmo_anchor();
+    }
+
+    /// Move the transform gizmo to the centroid of the patch it is attached to.
+    fn refresh_gizmo_anchor(&mut self) {
+        if let Some(i) = self.gizmo_patch {
+            self.transform_gizmo.anchor = self.working_copy.patches[i].centroid();
+        }
+    }
+
+    /// Translate the patch the transform gizmo is attached to by given offset. Only the control meshes
+    /// are refreshed; the patch mesh is rebuilt once the drag ends.
+    fn translate_gizmo_patch(&mut self, offset: &Vec3) {
+        if let Some(i) = self.gizmo_patch {
+            self.working_copy.patches[i].translate(offset);
+
+            let patch = &self.working_copy.patches[i];
+            self.control_curve_meshes[i] = self.create_control_curve_mesh(patch);
+            self.control_point_models[i] = self.create_control_point_model(i, patch);
+
+            self.refresh_gizmo_anchor();
+        }
+    }
+
+    /// Start dragging one of the transform gizmo handles, if the user clicked on one.
+    /// Returns whether a handle was hit.
+    fn begin_gizmo_drag(&mut self, x: u32, y: u32) -> bool {
+        match self.gizmo_patch {
+            Some(i) if self.active[i] => {},
+            _ => return false
+        }
+
+        let (depth, position) = self.clicked_position(x, y);
+
+        match self.transform_gizmo.find_clicked_handle(&position) {
+            Some(axis) => {
+                self.gizmo_drag = Some((axis, depth, position));
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Briefly show given confirmation message below the editor buttons.
+    fn show_flash(&mut self, message: &str) {
+        self.flash = Some((message.to_string(), self.current_time + FLASH_DURATION));
+    }
+
+    /// Store the working copy as the saved model, which is committed once the editor is closed.
+    fn save(&mut self) {
+        self.edit.borrow_mut().saved = Some(self.working_copy.clone());
+    }
+
+    /// Set the active flag of all patches. This also ends soloing.
+    fn set_all_active(&mut self, active: bool) {
+        self.solo = None;
+
+        for flag in &mut self.active {
+            *flag = active;
+        }
     }
 
     /// Refresh all patch meshes
     fn refresh_meshes(&mut self) {
+        // The active flags are maintained in parallel to the patches, and have to be updated whenever patches
+        // are added or removed.
+        debug_assert_eq!(self.active.len(), self.working_copy.patches.len(), "Active flags out of sync with patches");
+
         self.meshes = Vec::new();
         self.normal_vector_vis = Vec::new();
 
@@ -144,55 +373,231 @@ impl BezierEditorScene {
         }
 
         self.refresh_control_meshes();
+        self.refresh_light_gizmo_anchor();
+    }
+
+    /// Create the gizmo visualizing the cardinal axises, optionally with tick marks every tenth of a unit.
+    fn create_axis_gizmo(with_ticks: bool) -> OriginGizmo {
+        OriginGizmo::new(0.3, 3.5, if with_ticks { Some(0.1) } else { None })
+    }
+
+    /// Draw the coordinate values of the axis gizmo tick marks as text next to their projected screen positions.
+    fn draw_axis_labels(&self, ui: &Ui) {
+        let labels: Vec<(Vec3, String)> = self.axis_gizmo.ticks().iter()
+            .map(|(position, value)| (*position, format!("{:.1}", value)))
+            .collect();
+
+        world_space_labels(ui, im_str!("Axis Labels"), &self.camera, self.width, self.height, &labels);
+    }
+
+    /// Move the light gizmo to the maximum corner of the bounding box around all control points, where
+    /// it is least likely to be obstructed by the model.
+    fn refresh_light_gizmo_anchor(&mut self) {
+        let mut corner: Option<Vec3> = None;
+
+        for patch in &self.working_copy.patches {
+            for curve in &patch.curves {
+                for point in &curve.control_points {
+                    corner = Some(match corner {
+                        Some(c) => c.zip_map(point, |a, b| a.max(b)),
+                        None => point.clone()
+                    });
+                }
+            }
+        }
+
+        self.light_gizmo.anchor = corner.unwrap_or(Vec3::zeros());
+    }
+
+    /// Start dragging the light gizmo handle, if it is shown and the user clicked on it.
+    /// Returns whether the handle was hit.
+    fn begin_light_drag(&mut self, x: u32, y: u32) -> bool {
+        if !self.show_light_gizmo {
+            return false;
+        }
+
+        let (depth, position) = self.clicked_position(x, y);
+
+        if self.light_gizmo.is_handle_clicked(&position) {
+            self.light_drag = Some(depth);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Apply the gizmos on top setting to all gizmos.
+    fn refresh_gizmos_on_top(&mut self) {
+        self.axis_gizmo.always_on_top = self.gizmos_on_top;
+        self.transform_gizmo.always_on_top = self.gizmos_on_top;
+        self.light_gizmo.always_on_top = self.gizmos_on_top;
     }
 
     fn create_normal_mesh(&self, patch: &BezierPatchParameters) -> Mesh {
-        let geometry = BezierGeometry::new(patch, 30, 30);
+        let geometry = BezierGeometry::new(patch, self.working_copy.resolution, self.working_copy.resolution, self.working_copy.smoothing_angle);
 
-        let mat = Box::new(NormalTestMaterial::new(0.05, &Vec3::new(1.0, 1.0, 0.0)));
+        let mat = Box::new(NormalTestMaterial::new(self.normal_length, &Vec3::new(1.0, 1.0, 0.0)));
 
-        let mut mesh = Mesh::new_indexed(PrimitiveType::TriangleStrip, mat, &geometry);
-        mesh
+        Mesh::new_indexed(geometry.primitive_type(), mat, &geometry)
     }
     
-    /// Refresh the control point meshes only for the currently dragged point
+    /// Determine the indices of all patches containing the currently dragged or selected points.
+    fn moved_patches(&self) -> Vec<usize> {
+        let mut patches: Vec<usize> = self.selected_points.iter().map(|(i, _, _)| *i).collect();
+
+        if let Some((i, _, _)) = self.dragged_point {
+            patches.push(i);
+        }
+
+        // The mirrored partner might reside in a different patch
+        if let Some((i, _, _)) = self.mirrored_point {
+            patches.push(i);
+        }
+
+        patches.sort();
+        patches.dedup();
+
+        patches
+    }
+
+    /// Refresh the control point meshes only for the patches containing the currently dragged
+    /// or selected points
     fn refresh_control_meshes_for_dragged(&mut self) {
-        if let Some((i, j, k)) = self.dragged_point {
+        for i in self.moved_patches() {
             let patch = &self.working_copy.patches[i];
 
             self.control_curve_meshes[i] = self.create_control_curve_mesh(patch);
-            self.control_point_models[i] = self.create_control_point_model(patch);
+            self.control_point_models[i] = self.create_control_point_model(i, patch);
         }
+
+        self.refresh_gizmo_anchor();
+    }
+
+    /// Rebuild all meshes of the patches containing the currently dragged or selected points. This
+    /// is done once moving the points is finished.
+    fn refresh_meshes_for_moved(&mut self) {
+        for i in self.moved_patches() {
+            self.refresh_mesh_for(i);
+        }
+    }
+
+    /// Move the currently dragged control point to given position. All other selected control points
+    /// are translated by the same world space delta, and the mirrored partner point, if any, is kept
+    /// in sync with the dragged point.
+    fn move_selection(&mut self, new_point: Vec3) {
+        let (i, j, k) = match self.dragged_point {
+            Some(index) => index,
+            None => return
+        };
+
+        let old_point = self.working_copy.patches[i].curves[j].control_points[k];
+        let delta = new_point - old_point;
+
+        for &(si, sj, sk) in &self.selected_points {
+            // The dragged point and its partner are handled separately
+            if Some((si, sj, sk)) == self.dragged_point || Some((si, sj, sk)) == self.mirrored_point {
+                continue;
+            }
+
+            self.working_copy.patches[si].curves[sj].control_points[sk] += delta;
+        }
+
+        self.working_copy.patches[i].curves[j].control_points[k] = new_point;
+
+        // Keep the mirrored partner point in sync, if there is one
+        if let (Some(plane), Some((mi, mj, mk))) = (self.mirror_mode, self.mirrored_point) {
+            self.working_copy.patches[mi].curves[mj].control_points[mk] = new_point.component_mul(&plane.factors());
+        }
+    }
+
+    /// Translate the currently dragged control point, and with it the whole selection, by given
+    /// world space delta. Only the control meshes of the affected patches are refreshed; the patch
+    /// meshes themselves are rebuilt once the key is released.
+    fn nudge_selection(&mut self, delta: Vec3) {
+        let (i, j, k) = match self.dragged_point {
+            Some(index) => index,
+            None => return
+        };
+
+        let new_point = self.working_copy.patches[i].curves[j].control_points[k] + delta;
+
+        self.move_selection(new_point);
+        self.refresh_control_meshes_for_dragged();
+    }
+
+    /// Search for the control point that is the mirror image of the control point with given indices,
+    /// using the currently selected mirror plane. The partner may be located in the same or any other
+    /// active patch. Points lying directly on the mirror plane do not have a partner.
+    fn find_mirrored_control_point(&self, index: (usize, usize, usize)) -> Option<(usize, usize, usize)> {
+        let plane = self.mirror_mode?;
+
+        let (i, j, k) = index;
+        let target = self.working_copy.patches[i].curves[j].control_points[k].component_mul(&plane.factors());
+
+        // Maximum distance at which a control point is still considered to be the mirror image
+        let tolerance = 0.001;
+
+        for (pi, patch) in self.working_copy.patches.iter().enumerate() {
+            if !self.active[pi] {
+                continue;
+            }
+
+            for (ci, curve) in patch.curves.iter().enumerate() {
+                for pti in 0..4 {
+                    if (pi, ci, pti) == index {
+                        continue;
+                    }
+
+                    if (curve.control_points[pti] - target).norm() <= tolerance {
+                        return Some((pi, ci, pti));
+                    }
+                }
+            }
+        }
+
+        None
     }
 
     fn refresh_control_meshes(&mut self) {
         self.control_point_models = Vec::new();
         self.control_curve_meshes = Vec::new();
 
-        for patch in &self.working_copy.patches {
-            self.control_point_models.push(self.create_control_point_model(patch));
+        for (i, patch) in self.working_copy.patches.iter().enumerate() {
+            self.control_point_models.push(self.create_control_point_model(i, patch));
             self.control_curve_meshes.push(self.create_control_curve_mesh(patch));
         }
+
+        self.refresh_gizmo_anchor();
     }
 
     fn create_mesh(&self, patch: &BezierPatchParameters) -> Mesh {
-        let geometry = BezierGeometry::new(patch, 30, 30);
+        let geometry = BezierGeometry::new(patch, self.working_copy.resolution, self.working_copy.resolution, self.working_copy.smoothing_angle);
 
-        let mat = Box::new(ShadedMaterial::new());
+        let mat: Box<dyn Material> = match self.working_copy.material {
+            BezierMaterial::Flat => Box::new(SimpleMaterial::new()),
+            BezierMaterial::Shaded => Box::new(ShadedMaterial::new()),
+            BezierMaterial::NormalColor => Box::new(NormalColorTestMaterial::new())
+        };
 
-        let mut mesh = Mesh::new_indexed(PrimitiveType::TriangleStrip, mat, &geometry);
-        mesh
+        Mesh::new_indexed(geometry.primitive_type(), mat, &geometry)
     }
 
-    fn create_control_point_model(& self, patch: &BezierPatchParameters) -> MultiModel { 
+    fn create_control_point_model(&self, index: usize, patch: &BezierPatchParameters) -> MultiModel { 
         let mut spheres = Vec::new();
         
-        for curve in &patch.curves {
+        for (j, curve) in patch.curves.iter().enumerate() {
             for i in 0..4 {
                 let point = &curve.control_points[i];
 
+                // Selected control points use a differently colored sphere
+                let mesh = if self.selected_points.contains(&(index, j, i)) {
+                    self.selected_sphere_mesh.clone()
+                } else {
+                    self.sphere_mesh.clone()
+                };
+
                 spheres.push(
-                    Model::from_mesh_transformed_rc(self.sphere_mesh.clone(), Mat4::new_translation(&point))  
+                    Model::from_mesh_transformed_rc(mesh, Mat4::new_translation(&point))  
                 );
             }
         }
@@ -200,8 +605,11 @@ impl BezierEditorScene {
         MultiModel::from_models(spheres)
     }
 
+    /// Create the mesh visualizing the control curves of given patch. If the control cage is enabled,
+    /// the control points are also connected across the curves, which are drawn in a different color.
     fn create_control_curve_mesh(&self, patch: &BezierPatchParameters) -> Mesh {
         let mut points = Vec::new();
+        let mut colors = Vec::new();
 
         for curve in &patch.curves {
             for i in 1..4 {
@@ -210,57 +618,99 @@ impl BezierEditorScene {
             }
         }
 
+        colors.resize(points.len(), Vec3::new(1.0, 1.0, 0.0));
+
+        if self.draw_control_cage {
+            for i in 0..4 {
+                for j in 1..4 {
+                    points.push(patch.curves[j-1].control_points[i].clone());
+                    points.push(patch.curves[j].control_points[i].clone());
+                }
+            }
+
+            colors.resize(points.len(), Vec3::new(1.0, 0.5, 0.0));
+        }
+
         let mut geom = BasicGeometry::new();
-        geom.colors.local_buffer = vec![Vec3::new(1.0, 1.0, 0.0); points.len()];
+        geom.colors.local_buffer = colors;
         geom.normals.local_buffer = vec![Vec3::new(0.0, 0.0, 0.0); points.len()];
         geom.positions.local_buffer = points;
 
         let mat = Box::new(SimpleMaterial::new());
 
-        let mut mesh = Mesh::new(PrimitiveType::Lines, mat, &geom);
+        Mesh::new(PrimitiveType::Lines, mat, &geom)
+    }
 
-        mesh
+    /// Focus the camera on the currently selected control points. If nothing is selected, all control
+    /// points of the active patches are used instead.
+    fn focus_camera(&mut self) {
+        let mut points = Vec::new();
+
+        if self.selected_points.is_empty() {
+            for (i, patch) in self.working_copy.patches.iter().enumerate() {
+                if !self.active[i] {
+                    continue;
+                }
+
+                for curve in &patch.curves {
+                    points.extend_from_slice(&curve.control_points);
+                }
+            }
+        } else {
+            for &(i, j, k) in &self.selected_points {
+                points.push(self.working_copy.patches[i].curves[j].control_points[k]);
+            }
+        }
+
+        // There is nothing to focus on
+        if points.is_empty() {
+            return;
+        }
+
+        let bb = BoundingBox::new(&Vec3::repeat(1.0), &points);
+
+        self.camera.recenter(&bb.aabb.center().coords);
+
+        // A single selected point would result in a radius of zero, which would place the camera
+        // right inside the point.
+        self.camera.set_radius(bb.radius().max(0.1));
+    }
+
+    /// Snap given point to the control point grid, if snapping is enabled.
+    fn snap(&self, point: Vec3) -> Vec3 {
+        match self.snap_step {
+            Some(step) if step > 0.0 => point.map(|c| (c / step).round() * step),
+            _ => point
+        }
     }
 
     /// Unproject a given window position to a point in world space
     fn unproject(&self, x: u32, y: u32, depth: f32) -> Vec3 {
-        unproject(
-            &Vec3::new(x as _, (self.height - y) as _, depth),
-            &self.camera.view,
-            &self.camera.projection,
-            Vec4::new(0.0, 0.0, self.width as _, self.height as _)
-        )
+        self.camera.unproject(x, y, depth)
+    }
+
+    /// Read the depth buffer at given window position and unproject it. Returns the depth value
+    /// and the resulting world space position.
+    fn clicked_position(&self, x: u32, y: u32) -> (f32, Vec3) {
+        self.camera.read_position(x, y)
     }
 
     /// Returns clicked control point and its depth
     fn find_clicked_control_point(&mut self, x: u32, y: u32) -> Option<(f32, usize, usize, usize)> {
-        // Retrieve depth value
-        let mut depth: f32 = 0.0;
-        unsafe {
-            gl::ReadPixels(
-                x as _,
-                (self.height - y) as _,
-                1 as _,
-                1 as _,
-                gl::DEPTH_COMPONENT,
-                gl::FLOAT,
-                &mut depth as *mut f32 as _
-            );
-        }
-
-        let position = self.unproject(x, y, depth);
+        let (depth, position) = self.clicked_position(x, y);
 
         // We now have the rough position. We now have to intersect a ball around it with the balls around all
         // the other control points to find the closest point to it.
 
-        // The sphere used for all points
-        let sphere = shape::Ball::<f32>::new(0.01);
+        // The sphere used for all points. It has the same size as the rendered control point spheres.
+        let sphere = shape::Ball::<f32>::new(CONTROL_POINT_RADIUS);
         
         // Create translation for ball around point
         let position_isometry = Isometry::new(position.clone(), nalgebra::zero());    
 
-        // Collect intersection results
-        let mut control_point: Option<&mut Vec3> = None;
+        // Collect all intersecting control points. If they overlap on screen, multiple of them might
+        // be hit at once.
+        let mut candidates: Vec<((usize, usize, usize), Vec3)> = Vec::new();
 
         // We check if a sphere around the clicked point intersects which spheres around any of the
         // control points, in order to retrieve which of the control points is the clicked one.
@@ -277,15 +727,13 @@ impl BezierEditorScene {
 
                     let translation = Isometry::new(point.clone(), nalgebra::zero());
 
-                    // TODO: it could be multiple intersections. Calculate actual center distance for each, 
-                    // and return point with lowest value.
                     let result = proximity(
                         &position_isometry, &sphere,
                         &translation, &sphere, 0.01);
 
                     match result {
                         Proximity::Intersecting => {
-                            return Some((depth, i, j, k));
+                            candidates.push(((i, j, k), point.clone()));
                         },
                         _ => {}
                     };
@@ -293,10 +741,25 @@ impl BezierEditorScene {
             }
         }
 
-        return None;
+        let camera_position = self.camera.state.position;
+
+        nearest_control_point(&candidates, &position, &camera_position)
+            .map(|(i, j, k)| (depth, i, j, k))
     }
 }
 
+/// Select the candidate control point whose center is closest to the given clicked position. If
+/// multiple candidates are equally close, the one closest to the camera is chosen.
+fn nearest_control_point(candidates: &[((usize, usize, usize), Vec3)], position: &Vec3, camera_position: &Vec3) -> Option<(usize, usize, usize)> {
+    let key = |point: &Vec3| ((point - position).norm(), (point - camera_position).norm());
+
+    candidates.iter()
+        .min_by(|(_, a), (_, b)| {
+            key(a).partial_cmp(&key(b)).unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .map(|(index, _)| *index)
+}
+
 impl Scene for BezierEditorScene {
     /// Render scene to screen. This also includes any GUI components.
     fn render(&self) {
@@ -305,6 +768,16 @@ impl Scene for BezierEditorScene {
 
         self.axis_gizmo.render(&mut rp);
 
+        if self.show_light_gizmo {
+            self.light_gizmo.render(&mut rp);
+        }
+
+        if let Some(i) = self.gizmo_patch {
+            if self.active[i] {
+                self.transform_gizmo.render(&mut rp);
+            }
+        }
+
         // The use of a range based for loop here might seem odd, but we
         // need to be able to check for each patch if its currently activated.
         for i in 0..self.working_copy.patches.len() {
@@ -316,7 +789,7 @@ impl Scene for BezierEditorScene {
             self.meshes[i].render(&mut rp);
             self.control_point_models[i].render(&mut rp);
 
-            if self.draw_control_curves {
+            if self.draw_control_curves || self.draw_control_cage {
                 self.control_curve_meshes[i].render(&mut rp);
             }
             
@@ -330,10 +803,27 @@ impl Scene for BezierEditorScene {
 
     }
 
+    fn set_time(&mut self, time: f64) {
+        self.current_time = time;
+    }
+
+    /// Show or hide the editor window.
+    fn set_gui_visible(&mut self, visible: bool) {
+        self.gui_visible = visible;
+    }
+
     /// Show imgui GUI if needed.
     fn do_gui(&mut self, ui: &Ui) -> SceneAction {
         let mut action = SceneAction::Nothing;
 
+        if !self.gui_visible {
+            if self.camera.locked() {
+                camera_lock_indicator(ui, self.width);
+            }
+
+            return action;
+        }
+
         imgui::Window::new(im_str!("Bezier Model Editor"))
             .size([250.0, 500.0], Condition::FirstUseEver)
             .position([0.0, 60.0], Condition::FirstUseEver)
@@ -353,6 +843,12 @@ impl Scene for BezierEditorScene {
                     // actually draw this popup outside the loops since we cant reconstruct that id!
                     let mut show_delete_popup: Option<usize> = None;
                     let mut show_clone_menu: Option<usize> = None;
+                    let mut solo_action: Option<Option<usize>> = None;
+                    let mut to_swap: Option<(usize, usize)> = None;
+                    let patch_count = self.working_copy.patches.len();
+
+                    let visible = self.active.iter().filter(|active| **active).count();
+                    ui.text(format!("{}/{} visible", visible, self.active.len()));
 
                     for (i, patch) in self.working_copy.patches.iter_mut().enumerate() {
                         let patch_id = ui.push_id(i as i32);
@@ -369,6 +865,21 @@ impl Scene for BezierEditorScene {
                             ui.same_line(0.0);
                             help_marker(ui, im_str!("Inactive models and their control points and curves are not rendered in the editor viewport."));
 
+                            ui.same_line(0.0);
+
+                            match self.solo {
+                                Some((soloed, _)) if soloed == i => {
+                                    if ui.button(im_str!("Unsolo"), [0.0, 0.0]) {
+                                        solo_action = Some(None);
+                                    }
+                                },
+                                _ => {
+                                    if ui.button(im_str!("Solo"), [0.0, 0.0]) {
+                                        solo_action = Some(Some(i));
+                                    }
+                                }
+                            }
+
 
                             ui.same_line(345.0);
                     
@@ -390,6 +901,47 @@ impl Scene for BezierEditorScene {
                     
                             colors.pop(ui);
 
+                            if ui.button(im_str!("Copy"), [0.0, 0.0]) {
+                                self.clipboard = Some(patch.clone());
+                            }
+
+                            if let Some(clipboard) = &self.clipboard {
+                                ui.same_line(0.0);
+
+                                if ui.button(im_str!("Paste Into"), [0.0, 0.0]) {
+                                    patch.curves = clipboard.curves.clone();
+                                    modified = Some(i);
+                                }
+                            }
+                            ui.same_line(0.0);
+                            help_marker(ui, im_str!("\"Copy\" stores this model in the clipboard. \"Paste Into\" replaces the control points of\nthis model with those of the copied one, keeping its color."));
+
+                            if ui.button(im_str!("Move Up"), [0.0, 0.0]) && i > 0 {
+                                to_swap = Some((i - 1, i));
+                            }
+
+                            ui.same_line(0.0);
+
+                            if ui.button(im_str!("Move Down"), [0.0, 0.0]) && i + 1 < patch_count {
+                                to_swap = Some((i, i + 1));
+                            }
+                            ui.same_line(0.0);
+                            help_marker(ui, im_str!("Changes the position of this model in the list. The order determines which of\noverlapping transparent surfaces is drawn first."));
+
+                            let mut gizmo_enabled = self.gizmo_patch == Some(i);
+                            if ui.checkbox(im_str!("Transform Gizmo"), &mut gizmo_enabled) {
+                                self.gizmo_patch = if gizmo_enabled { Some(i) } else { None };
+                                self.transform_gizmo.anchor = patch.centroid();
+                            }
+                            ui.same_line(0.0);
+                            help_marker(ui, im_str!("Shows a gizmo at the center of this model. Dragging one of its axis handles moves\nall control points of this model along that axis."));
+
+                            if ui.checkbox(im_str!("Flip Normals"), &mut patch.flip_normals) {
+                                modified = Some(i);
+                            }
+                            ui.same_line(0.0);
+                            help_marker(ui, im_str!("Inverts the normal vectors of this model. Use this if the model appears dark because its surface faces away from the light."));
+
                             let mut color: [f32; 3] = [patch.color.x, patch.color.y, patch.color.z];
 
                             if ColorEdit::new(im_str!("Model Color"), &mut color).build(ui) {
@@ -461,8 +1013,75 @@ impl Scene for BezierEditorScene {
                 
                     colors.pop(ui);
 
+                    if let Some(clipboard) = &self.clipboard {
+                        ui.same_line(0.0);
+
+                        if ui.button(im_str!("Paste as New"), [0.0, 0.0]) {
+                            self.working_copy.patches.push(clipboard.clone());
+                            self.active.push(true);
+                            refresh_all = true;
+                        }
+                    }
+
+                    ui.same_line(0.0);
+
+                    if ui.button(im_str!("Activate All"), [0.0, 0.0]) {
+                        self.set_all_active(true);
+                    }
+
+                    ui.same_line(0.0);
+
+                    if ui.button(im_str!("Deactivate All"), [0.0, 0.0]) {
+                        self.set_all_active(false);
+                    }
+
+                    ui.same_line(0.0);
+
+                    if ui.button(im_str!("Recompute Normals"), [0.0, 0.0]) {
+                        refresh_all = true;
+                    }
+                    ui.same_line(0.0);
+                    help_marker(ui, im_str!("Regenerates the meshes and normal vectors of all models."));
+
+                    ui.spacing();
+
+                    let token = ui.push_item_width(80.0);
+                    ui.drag_float(im_str!("##scale_factor"), &mut self.scale_factor)
+                        .min(0.01)
+                        .max(100.0)
+                        .display_format(im_str!("%.2lf"))
+                        .speed(0.01)
+                        .build();
+                    token.pop(ui);
+
+                    ui.same_line(0.0);
+
+                    if ui.button(im_str!("Scale"), [0.0, 0.0]) {
+                        self.working_copy.scale(self.scale_factor);
+                        refresh_all = true;
+                    }
+
+                    ui.same_line(0.0);
+
+                    if ui.button(im_str!("Center to origin"), [0.0, 0.0]) {
+                        self.working_copy.center();
+                        refresh_all = true;
+                    }
+                    ui.same_line(0.0);
+                    help_marker(ui, im_str!("\"Scale\" uniformly scales all control points by the given factor.\n\"Center to origin\" moves all models so that the centroid of their control points lies at the origin."));
+
                     ui.unindent();
 
+                    match solo_action {
+                        Some(Some(i)) => self.solo(i),
+                        Some(None) => self.unsolo(),
+                        None => {}
+                    }
+
+                    if let Some((a, b)) = to_swap {
+                        self.swap_patches(a, b);
+                    }
+
                     if let Some(i) = show_delete_popup {
                         self.gui_cached_id = Some(i);
                         ui.open_popup(im_str!("Delete model?"));
@@ -484,6 +1103,15 @@ impl Scene for BezierEditorScene {
                                 self.active.remove(index);
                                 self.working_copy.patches.remove(index);
 
+                                // The remembered active flags no longer match the patch indices
+                                self.solo = None;
+
+                                // Indices of selected points might now be invalid
+                                self.selected_points.clear();
+                                self.dragged_point = None;
+                                self.mirrored_point = None;
+                                self.gizmo_patch = None;
+
                                 refresh_all = true;
                             },
                             _ => {}
@@ -534,6 +1162,39 @@ impl Scene for BezierEditorScene {
                     .build() {
                     ui.indent();          
 
+                    let mut current_item: i32 = self.working_copy.material as _;
+                    let items = vec![im_str!("Flat"), im_str!("Shaded"), im_str!("Normal Colors")];
+
+                    if ui.combo(im_str!("Material"), &mut current_item, &items, 3) {
+                        self.working_copy.material = match current_item {
+                            0 => BezierMaterial::Flat,
+                            1 => BezierMaterial::Shaded,
+                            _ => BezierMaterial::NormalColor
+                        };
+
+                        self.refresh_meshes();
+                    }
+                    ui.same_line(0.0);
+                    help_marker(ui, im_str!("The material the patches of this model are rendered with. It is saved with the model and also used in the L-System view.\n\
+                                             \tFlat: Only uses the patch colors, ignoring all lights.\n\
+                                             \tShaded: Lit by the scene lights.\n\
+                                             \tNormal Colors: Displays surface normals as colors, which helps to spot flipped patches."));
+
+                    if Slider::<u32>::new(im_str!("Resolution"), 2..=100).build(ui, &mut self.working_copy.resolution) {
+                        self.refresh_meshes();
+                    }
+                    ui.same_line(0.0);
+                    help_marker(ui, im_str!("Number of rows and columns each patch is divided into. It is saved with the model and also used in the L-System view."));
+
+                    if Slider::<f32>::new(im_str!("Smoothing angle"), 0.0..=180.0)
+                        .display_format(im_str!("%.0f deg"))
+                        .build(ui, &mut self.working_copy.smoothing_angle) {
+                        self.refresh_meshes();
+                    }
+                    ui.same_line(0.0);
+                    help_marker(ui, im_str!("Faces meeting at a sharper angle than this do not share their vertex normals, which keeps creases\n\
+                                             and corners of the surface sharp. At 180 degrees, all normals are smoothed."));
+
                     {
                         let mut data = [self.lights.ambient_intensity.x, self.lights.ambient_intensity.y, self.lights.ambient_intensity.z];
 
@@ -557,9 +1218,14 @@ impl Scene for BezierEditorScene {
                             .speed(0.0006)
                             .build() {
                                 self.lights.directional_light = Vec3::new(data[0], data[1], data[2]);
+                                self.light_gizmo.set_direction(&self.lights.directional_light);
                         }
                     }   
 
+                    ui.checkbox(im_str!("Show light gizmo"), &mut self.show_light_gizmo);
+                    ui.same_line(0.0);
+                    help_marker(ui, im_str!("Shows a handle at a corner of the model that points towards the directional light.\nDragging it changes the light direction."));
+
                     {
                         let mut data = [self.lights.directional_intensity.x, self.lights.directional_intensity.y, self.lights.directional_intensity.z];
 
@@ -573,6 +1239,10 @@ impl Scene for BezierEditorScene {
                         }
                     }  
 
+                    Slider::<f32>::new(im_str!("Normal Darkening"), 0.0..=1.0).build(ui, &mut self.lights.normal_darkening);
+                    ui.same_line(0.0);
+                    help_marker(ui, im_str!("Darkens surfaces facing downwards, which approximates the shadows in crevices."));
+
                     ui.unindent();
                 }
 
@@ -583,15 +1253,118 @@ impl Scene for BezierEditorScene {
 
                     ui.checkbox(im_str!("Draw control curves"), &mut self.draw_control_curves);
 
+                    if ui.checkbox(im_str!("Draw control cage"), &mut self.draw_control_cage) {
+                        self.refresh_control_meshes();
+                    }
+                    ui.same_line(0.0);
+                    help_marker(ui, im_str!("Also connects the control points across the curves, showing the full 4x4 control net of each patch."));
+
+                    if ui.checkbox(im_str!("Show axis ticks"), &mut self.show_axis_ticks) {
+                        self.axis_gizmo = Self::create_axis_gizmo(self.show_axis_ticks);
+                        self.refresh_gizmos_on_top();
+                    }
+
+                    if ui.checkbox(im_str!("Gizmos on top"), &mut self.gizmos_on_top) {
+                        self.refresh_gizmos_on_top();
+                    }
+                    ui.same_line(0.0);
+                    help_marker(ui, im_str!("Draws the axis, transform and light gizmos on top of the patches, so they stay visible\n\
+                                             when surrounded by geometry. Gizmo handles hidden behind patches can not be clicked."));
+
+                    ui.checkbox(im_str!("Show cursor coordinates"), &mut self.show_cursor_position);
+
+                    let mut mirror_enabled = self.mirror_mode.is_some();
+                    if ui.checkbox(im_str!("Mirror editing"), &mut mirror_enabled) {
+                        self.mirror_mode = if mirror_enabled { Some(MirrorPlane::YZ) } else { None };
+                    }
+                    ui.same_line(0.0);
+                    help_marker(ui, im_str!("When dragging a control point, its mirror image on the selected plane is moved along with it.\nThe partner point may be part of the same or any other active model."));
+
+                    if let Some(plane) = self.mirror_mode {
+                        ui.indent();
+
+                        let mut current_item: i32 = match plane {
+                            MirrorPlane::XY => 0,
+                            MirrorPlane::XZ => 1,
+                            _ => 2
+                        };
+
+                        let items = vec![im_str!("XY plane"), im_str!("XZ plane"), im_str!("YZ plane")];
+
+                        if ui.combo(im_str!("Mirror plane"), &mut current_item, &items, 3) {
+                            self.mirror_mode = Some(match current_item {
+                                0 => MirrorPlane::XY,
+                                1 => MirrorPlane::XZ,
+                                _ => MirrorPlane::YZ
+                            });
+                        }
+
+                        ui.unindent();
+                    }
+
+                    let mut snap_enabled = self.snap_step.is_some();
+                    if ui.checkbox(im_str!("Snap to grid"), &mut snap_enabled) {
+                        self.snap_step = if snap_enabled { Some(0.05) } else { None };
+                    }
+                    ui.same_line(0.0);
+                    help_marker(ui, im_str!("Rounds the coordinates of dragged control points to the nearest multiple of the grid step."));
+
+                    if let Some(step) = &mut self.snap_step {
+                        ui.indent();
+
+                        ui.drag_float(im_str!("Grid step"), step)
+                            .min(0.001)
+                            .max(10.0)
+                            .display_format(im_str!("%.3lf"))
+                            .speed(0.001)
+                            .build();
+
+                        ui.unindent();
+                    }
+
                     if ui.checkbox(im_str!("Draw normal vectors"), &mut self.draw_normal_vectors) {
                         self.refresh_meshes();
                     }
 
+                    if self.draw_normal_vectors {
+                        ui.indent();
+
+                        if ui.drag_float(im_str!("Normal length"), &mut self.normal_length)
+                            .min(0.001)
+                            .max(100.0)
+                            .display_format(im_str!("%.3lf"))
+                            .speed(0.001)
+                            .build() {
+                            for mesh in &mut self.normal_vector_vis {
+                                mesh.retrieve_material_mut_ref::<NormalTestMaterial>().normal_length = self.normal_length;
+                            }
+                        }
+
+                        ui.unindent();
+                    }
+
                     ui.unindent();
                 }
 
                 ui.spacing();
 
+                if ui.button(im_str!("Focus"), [0.0, 0.0]) {
+                    self.focus_camera();
+                }
+                ui.same_line(0.0);
+                help_marker(ui, im_str!("Centers the camera on the selected control points, or on all active models if nothing is selected.\nThis can also be triggered using the F key."));
+
+                axis_view_buttons(ui, &mut self.camera);
+
+                let mut locked = self.camera.locked();
+                if ui.checkbox(im_str!("Lock camera"), &mut locked) {
+                    self.camera.set_locked(locked);
+                }
+                ui.same_line(0.0);
+                help_marker(ui, im_str!("Ignores all mouse input to the camera. Control points can still be dragged.\nThis can also be toggled using the L key."));
+
+                ui.spacing();
+
                 if ui.button(im_str!("Cancel"), [0.0, 0.0]) {
                     action = SceneAction::PopScene;
                 }
@@ -599,17 +1372,43 @@ impl Scene for BezierEditorScene {
                 ui.same_line(0.0);
 
                 if ui.button(im_str!("Save"), [0.0, 0.0]) {
-                    *self.model.borrow_mut() = self.working_copy.clone();
+                    self.save();
+                    self.show_flash("Model saved");
                 }
 
                 ui.same_line(0.0);
 
                 if ui.button(im_str!("Save and Exit"), [0.0, 0.0]) {
-                    *self.model.borrow_mut() = self.working_copy.clone();
+                    self.save();
                     action = SceneAction::PopScene;
                 }
+
+                if ui.button(im_str!("Apply to L-system preview"), [0.0, 0.0]) {
+                    self.edit.borrow_mut().preview = Some(self.working_copy.clone());
+                    self.show_flash("Applied to L-system preview");
+                }
+                ui.same_line(0.0);
+                help_marker(ui, im_str!("Refreshes all instances of this model in the L-system without leaving the editor.\nThe preview is reverted on exit unless the model was saved."));
+
+                if let Some((message, until)) = &self.flash {
+                    if *until > self.current_time {
+                        ui.text_colored([0.4, 0.9, 0.4, 1.0], message);
+                    }
+                }
         });
 
+        if self.show_axis_ticks {
+            self.draw_axis_labels(ui);
+        }
+
+        if self.show_cursor_position {
+            cursor_position_tooltip(ui, &self.camera, 1.0);
+        }
+
+        if self.camera.locked() {
+            camera_lock_indicator(ui, self.width);
+        }
+
         action
     }
 
@@ -617,24 +1416,141 @@ impl Scene for BezierEditorScene {
     fn handle_event(&mut self, window: &glfw::Window, event: &glfw::WindowEvent) {
         // MouseButton(MouseButton, Action, Modifiers)
         match event {
-            glfw::WindowEvent::MouseButton(glfw::MouseButton::Button1, glfw::Action::Press, _) => {
+            glfw::WindowEvent::MouseButton(glfw::MouseButton::Button1, glfw::Action::Press, modifiers) => {
                 let (x, y) = window.get_cursor_pos();
-                // If the user has clicked on one of the control points of the bezier patch, start
-                // drag process.
-                if let Some((d, i, j, k)) = self.find_clicked_control_point(x as _, y as _) {
+                let additive = modifiers.contains(glfw::Modifiers::Shift);
+
+                // Handles of the light and transform gizmos take precedence over control points. If the user has clicked
+                // on one of the control points of the bezier patch, start drag process.
+                if self.begin_light_drag(x as _, y as _) {
+                    // The light direction is updated on cursor movement
+                } else if self.begin_gizmo_drag(x as _, y as _) {
+                    // Nothing else to do here, the gizmo drag is handled on cursor movement
+                } else if let Some((d, i, j, k)) = self.find_clicked_control_point(x as _, y as _) {
+                    // Shift-clicking toggles membership in the selection. A normal click on a point that
+                    // is not already selected replaces the selection, while clicking an already selected
+                    // point keeps the selection intact so that the whole group can be dragged.
+                    if additive {
+                        if let Some(pos) = self.selected_points.iter().position(|p| *p == (i, j, k)) {
+                            self.selected_points.remove(pos);
+                        } else {
+                            self.selected_points.push((i, j, k));
+                        }
+                    } else if !self.selected_points.contains(&(i, j, k)) {
+                        self.selected_points = vec![(i, j, k)];
+                    }
+
+                    self.refresh_control_meshes();
+
                     self.drag_begin = Some((x as _, y as _));
                     self.drag_depth = Some(d);
                     self.in_drag = true;
                     self.dragged_point = Some((i, j, k));
+                    self.mirrored_point = self.find_mirrored_control_point((i, j, k));
+
+                    // Snap the anchor point right away, so that the dragged point is aligned to the grid
+                    // even if the mouse is not moved at all.
+                    if self.snap_step.is_some() {
+                        let anchor = self.snap(self.working_copy.patches[i].curves[j].control_points[k]);
+                        self.move_selection(anchor);
+                        self.refresh_control_meshes_for_dragged();
+                    }
+                } else if !additive && !self.selected_points.is_empty() {
+                    // Clicking into empty space clears the selection
+                    self.selected_points.clear();
+                    self.dragged_point = None;
+                    self.mirrored_point = None;
+                    self.refresh_control_meshes();
+                }
+            },
+            glfw::WindowEvent::Key(key, _, action, modifiers) if *action != glfw::Action::Release && self.dragged_point.is_some() => {
+                // Arrow keys and page up/down nudge the last clicked control point along the world axes.
+                // Holding shift uses a larger step.
+                let step = if modifiers.contains(glfw::Modifiers::Shift) { 0.05 } else { 0.005 };
+
+                let direction = match key {
+                    glfw::Key::Left => Some(Vec3::new(-1.0, 0.0, 0.0)),
+                    glfw::Key::Right => Some(Vec3::new(1.0, 0.0, 0.0)),
+                    glfw::Key::Up => Some(Vec3::new(0.0, 1.0, 0.0)),
+                    glfw::Key::Down => Some(Vec3::new(0.0, -1.0, 0.0)),
+                    glfw::Key::PageUp => Some(Vec3::new(0.0, 0.0, 1.0)),
+                    glfw::Key::PageDown => Some(Vec3::new(0.0, 0.0, -1.0)),
+                    _ => None
+                };
+
+                if let Some(direction) = direction {
+                    self.nudge_selection(direction * step);
+                } else if *key == glfw::Key::F {
+                    self.focus_camera();
+                } else if *key == glfw::Key::L && *action == glfw::Action::Press {
+                    self.camera.set_locked(!self.camera.locked());
+                } else if let Some(view) = AxisView::from_key(*key, *modifiers) {
+                    self.camera.snap_to_axis(view);
+                }
+            },
+            glfw::WindowEvent::Key(glfw::Key::Left, _, glfw::Action::Release, _)
+            | glfw::WindowEvent::Key(glfw::Key::Right, _, glfw::Action::Release, _)
+            | glfw::WindowEvent::Key(glfw::Key::Up, _, glfw::Action::Release, _)
+            | glfw::WindowEvent::Key(glfw::Key::Down, _, glfw::Action::Release, _)
+            | glfw::WindowEvent::Key(glfw::Key::PageUp, _, glfw::Action::Release, _)
+            | glfw::WindowEvent::Key(glfw::Key::PageDown, _, glfw::Action::Release, _) => {
+                // The patch meshes are only rebuilt once nudging is done
+                if self.dragged_point.is_some() {
+                    self.refresh_meshes_for_moved();
+                }
+            },
+            glfw::WindowEvent::Key(glfw::Key::F, _, glfw::Action::Press, _) => {
+                self.focus_camera();
+            },
+            glfw::WindowEvent::Key(glfw::Key::L, _, glfw::Action::Press, _) => {
+                self.camera.set_locked(!self.camera.locked());
+            },
+            glfw::WindowEvent::Key(key, _, glfw::Action::Press, modifiers) => {
+                if let Some(view) = AxisView::from_key(*key, *modifiers) {
+                    self.camera.snap_to_axis(view);
                 }
             },
             glfw::WindowEvent::MouseButton(glfw::MouseButton::Button1, glfw::Action::Release, _) => {
+                self.light_drag = None;
+
+                if self.gizmo_drag.is_some() {
+                    self.gizmo_drag = None;
+
+                    if let Some(i) = self.gizmo_patch {
+                        self.refresh_mesh_for(i);
+                    }
+                }
+
                 if self.in_drag {
                     self.in_drag = false;
-                    self.refresh_meshes();
+                    self.refresh_meshes_for_moved();
                 }
             },
             glfw::WindowEvent::CursorPos(x, y) => {
+                if let Some(depth) = self.light_drag {
+                    if *x >= 0.0 && *x <= (self.width as f64) && *y >= 0.0 && *y <= (self.height as f64) {
+                        // The light points from the gizmo anchor towards the dragged handle
+                        let direction = self.unproject(*x as _, *y as _, depth) - self.light_gizmo.anchor;
+
+                        if direction.norm() > 0.0 {
+                            self.lights.directional_light = direction.normalize();
+                            self.light_gizmo.set_direction(&direction);
+                        }
+                    }
+                }
+
+                if let Some((axis, depth, last)) = self.gizmo_drag {
+                    if *x >= 0.0 && *x <= (self.width as f64) && *y >= 0.0 && *y <= (self.height as f64) {
+                        // Only the movement along the dragged axis is applied to the patch
+                        let current = self.unproject(*x as _, *y as _, depth);
+                        let direction = TransformGizmo::axis_direction(axis);
+                        let offset = direction * (current - last).dot(&direction);
+
+                        self.translate_gizmo_patch(&offset);
+                        self.gizmo_drag = Some((axis, depth, current));
+                    }
+                }
+
                 if self.in_drag {
                     // If the user drags the cursor outside of the window, stop dragging process.
                     if *x >= 0.0 && *x <= (self.width as f64) && *y >= 0.0 && *y <= (self.height as f64) {
@@ -645,11 +1561,9 @@ impl Scene for BezierEditorScene {
                         let curY = *y as u32;
 
                         let (oldX, oldY) = self.drag_begin.unwrap();
-                        let new_point = self.unproject(curX, curY, self.drag_depth.unwrap());
+                        let new_point = self.snap(self.unproject(curX, curY, self.drag_depth.unwrap()));
                         
-                        let (i, j, k) = self.dragged_point.unwrap();
-                        let p = &mut self.working_copy.patches[i].curves[j].control_points[k];
-                        *p = new_point.clone();
+                        self.move_selection(new_point);
             
                         self.drag_begin = Some((curX, curY));     
                         
@@ -657,15 +1571,15 @@ impl Scene for BezierEditorScene {
                         self.refresh_control_meshes_for_dragged();
                     } else {
                         self.in_drag = false;
-                        self.refresh_mesh_for(self.dragged_point.unwrap().0);
+                        self.refresh_meshes_for_moved();
                     }
                 }
             },
             _ => {}
         };
 
-        // We do not want to move the camera when the user is currently dragging a control point.
-        if !self.in_drag {
+        // We do not want to move the camera when the user is currently dragging a control point or gizmo handle.
+        if !self.in_drag && self.gizmo_drag.is_none() && self.light_drag.is_none() {
             self.camera.handle_event(window, event);
         }
     }
@@ -677,4 +1591,38 @@ impl Scene for BezierEditorScene {
         self.width = w;
         self.height = h;
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearest_candidate_is_chosen() {
+        let candidates = vec![
+            ((0, 0, 0), Vec3::new(1.0, 0.0, 0.0)),
+            ((0, 1, 2), Vec3::new(0.1, 0.1, 0.0)),
+            ((1, 3, 3), Vec3::new(0.0, 2.0, 0.0))
+        ];
+
+        let nearest = nearest_control_point(&candidates, &Vec3::zeros(), &Vec3::new(0.0, 0.0, 5.0));
+        assert_eq!(nearest, Some((0, 1, 2)));
+    }
+
+    #[test]
+    fn equal_distance_prefers_candidate_closer_to_camera() {
+        // Both candidates are 0.5 away from the clicked position, but the second one faces the camera
+        let candidates = vec![
+            ((0, 0, 0), Vec3::new(0.0, 0.0, -0.5)),
+            ((0, 0, 1), Vec3::new(0.0, 0.0, 0.5))
+        ];
+
+        let nearest = nearest_control_point(&candidates, &Vec3::zeros(), &Vec3::new(0.0, 0.0, 5.0));
+        assert_eq!(nearest, Some((0, 0, 1)));
+    }
+
+    #[test]
+    fn no_candidates_select_nothing() {
+        assert_eq!(nearest_control_point(&[], &Vec3::zeros(), &Vec3::new(0.0, 0.0, 5.0)), None);
+    }
+}
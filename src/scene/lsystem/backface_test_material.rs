@@ -0,0 +1,99 @@
+use nalgebra_glm::Vec3;
+use std::any::*;
+use crate::rendering::*;
+use crate::rendering::materials::*;
+use crate::rendering::shaders::*;
+use crate::rendering::uniforms::*;
+
+/// A material that draws front faces in their vertex color and back faces in a fixed, contrasting color.
+/// This makes polygons with inconsistent winding order easy to spot.
+pub struct BackfaceTestMaterial {
+    /// The shader program used by this material
+    program: Program,
+    /// The color back faces are drawn in
+    pub back_color: Vec3
+}
+
+impl BackfaceTestMaterial {
+    pub fn new(back_color: &Vec3) -> BackfaceTestMaterial {
+        let mut shaders = vec![
+            Shader::new_vertex(Self::VERTEX_SHADER_SOURCE).unwrap(),
+            Shader::new_fragment(Self::FRAGMENT_SHADER_SOURCE).unwrap()
+        ];
+
+        BackfaceTestMaterial {
+            program: Program::from_shaders(
+                &mut shaders
+            ).unwrap(),
+            back_color: back_color.clone()
+        }
+    }
+}
+
+impl Material for BackfaceTestMaterial {
+    fn enable_material(&self, params: &mut RenderParameters) -> Result<(), RenderError> {
+        self.program.use_program();
+
+        self.program.report_uniform_errors(&[
+            self.program.set_uniform_mat4("projection", &params.projection),
+            self.program.set_uniform_mat4("view", &params.view),
+            self.program.set_uniform_mat4("model", &params.model),
+            self.program.set_uniform_vec3("BackColor", &self.back_color)
+        ]);
+
+        Ok(())
+    }
+
+    /// Retrieve this instance as a reference to Any. This is used for downcasting.
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    /// Retrieve this instance as a mutable reference to Any. This is used for downcasting.
+    fn as_mut_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// The shader source code
+impl BackfaceTestMaterial {
+    /// The vertex shader source for this material
+    const VERTEX_SHADER_SOURCE: &'static str = r#"
+        #version 330 core
+
+        layout (location = 0) in vec3 Position;
+        layout (location = 1) in vec3 Color;
+
+        uniform mat4 projection;
+        uniform mat4 view;
+        uniform mat4 model;
+
+        out VS_OUTPUT {
+            vec3 Color;
+        } OUT;
+
+        void main()
+        {
+            gl_Position = projection * view * model * vec4(Position, 1.0);
+            OUT.Color = Color;
+        }
+    "#;
+
+    /// The fragment shader source for this material
+    const FRAGMENT_SHADER_SOURCE: &'static str = r#"
+        #version 330 core
+
+        in VS_OUTPUT {
+            vec3 Color;
+        } IN;
+
+        uniform vec3 BackColor;
+
+        out vec4 Color;
+
+        void main()
+        {
+            Color = vec4(gl_FrontFacing ? IN.Color : BackColor, 1.0);
+        }
+    "#;
+}
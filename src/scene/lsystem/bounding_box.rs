@@ -75,11 +75,69 @@ impl BoundingBoxGeometry {
     }
 }
 
+/// Number of line segments each circle of the bounding sphere wireframe is made of
+const SPHERE_CIRCLE_SEGMENTS: u32 = 48;
+
+/// Number of meridians and parallels of the bounding sphere wireframe
+const SPHERE_CIRCLE_COUNT: u32 = 6;
+
+/// Geometry for the wireframe of the bounding sphere, made of meridians and parallels.
+struct BoundingSphereGeometry {
+    /// Position information
+    position: AttributeArray<Vec3>
+}
+
+impl Geometry for BoundingSphereGeometry {
+    fn retrieve_attributes(&self) -> Vec<&dyn AttributeArrayBase> {
+        vec![&self.position]
+    }
+}
+
+impl BoundingSphereGeometry {
+    /// Create new sphere wireframe geometry with given center and radius
+    pub fn new(center: &Vec3, radius: f32) -> BoundingSphereGeometry {
+        let mut geom = BoundingSphereGeometry {
+            position: AttributeArray::new(0, "position")
+        };
+
+        let buffer = &mut geom.position.local_buffer;
+        let step = 2.0 * std::f32::consts::PI / SPHERE_CIRCLE_SEGMENTS as f32;
+
+        for i in 0..SPHERE_CIRCLE_COUNT {
+            // Meridians are evenly spaced around the y axis
+            let longitude = std::f32::consts::PI * i as f32 / SPHERE_CIRCLE_COUNT as f32;
+            let meridian = |angle: f32| center + Vec3::new(
+                angle.sin() * longitude.cos(), angle.cos(), angle.sin() * longitude.sin()
+            ) * radius;
+
+            // Parallels are evenly spaced between the poles, excluding the poles themselves
+            let latitude = std::f32::consts::PI * (i + 1) as f32 / (SPHERE_CIRCLE_COUNT + 1) as f32;
+            let parallel = |angle: f32| center + Vec3::new(
+                angle.cos() * latitude.sin(), latitude.cos(), angle.sin() * latitude.sin()
+            ) * radius;
+
+            for j in 0..SPHERE_CIRCLE_SEGMENTS {
+                let (begin, end) = (j as f32 * step, (j + 1) as f32 * step);
+
+                buffer.push(meridian(begin)); buffer.push(meridian(end));
+                buffer.push(parallel(begin)); buffer.push(parallel(end));
+            }
+        }
+
+        geom
+    }
+}
+
 pub struct BoundingBox {
     /// The mesh containing the bounding box lines
     mesh: Mesh,
+    /// The wireframe of the bounding sphere, which is what the camera is fitted to when auto adjusting
+    /// its radius
+    sphere_mesh: Mesh,
     /// The AABB instance used to crate the outline and calculate the center
-    pub aabb: AABB<f32>
+    pub aabb: AABB<f32>,
+    /// Whether the bounding box is drawn on top of all other geometry, instead of being occluded by it
+    pub always_on_top: bool
 }
 
 impl BoundingBox {
@@ -106,15 +164,36 @@ impl BoundingBox {
             &BoundingBoxGeometry::new(&bx)
         );
 
+        let sphere = bx.bounding_sphere();
+        let sphere_mesh = Mesh::new(
+            PrimitiveType::Lines,
+            Box::new(BoundingBoxMaterial::new(&color)),
+            &BoundingSphereGeometry::new(&sphere.center().coords, sphere.radius())
+        );
+
         BoundingBox {
             aabb: bx,
-            mesh: mesh
+            mesh: mesh,
+            sphere_mesh: sphere_mesh,
+            always_on_top: false
         }
     }
 
     pub fn set_color(&mut self, clr: &Vec3) {
         self.mesh.retrieve_material_mut_ref::<BoundingBoxMaterial>()
             .color = clr.clone();
+        self.sphere_mesh.retrieve_material_mut_ref::<BoundingBoxMaterial>()
+            .color = clr.clone();
+    }
+
+    /// Render the wireframe of the bounding sphere, whose radius is returned by `radius`.
+    pub fn render_sphere(&self, params: &mut RenderParameters) {
+        draw_on_top(self.always_on_top, || self.sphere_mesh.render(params));
+    }
+
+    /// Retrieve the smallest and biggest corner of this bounding box, in that order.
+    pub fn extents(&self) -> (Vec3, Vec3) {
+        (self.aabb.mins().coords, self.aabb.maxs().coords)
     }
 
     /// Retrieve a radius of a sphere completely containing this AABB
@@ -127,7 +206,7 @@ impl BoundingBox {
 
 impl Render for BoundingBox {
     fn render(&self, params: &mut RenderParameters) {
-        self.mesh.render(params);
+        draw_on_top(self.always_on_top, || self.mesh.render(params));
     }
 }
 
@@ -150,13 +229,17 @@ impl BoundingBoxMaterial {
 }
 
 impl Material for BoundingBoxMaterial {
-    fn enable_material(&self, params: &mut RenderParameters) {
+    fn enable_material(&self, params: &mut RenderParameters) -> Result<(), RenderError> {
         self.shader.use_program();
 
-        self.shader.set_uniform_mat4("projection", &params.projection);
-        self.shader.set_uniform_mat4("view", &params.view);
-        self.shader.set_uniform_mat4("model", &params.model);
-        self.shader.set_uniform_vec3("color", &self.color);
+        self.shader.report_uniform_errors(&[
+            self.shader.set_uniform_mat4("projection", &params.projection),
+            self.shader.set_uniform_mat4("view", &params.view),
+            self.shader.set_uniform_mat4("model", &params.model),
+            self.shader.set_uniform_vec3("color", &self.color)
+        ]);
+
+        Ok(())
     }
 
     /// Retrieve this instance as a reference to Any. This is used for downcasting.
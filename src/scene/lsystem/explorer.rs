@@ -0,0 +1,161 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::data::LSystemParameters;
+
+/// Maximum number of variants kept in the explorer history. The oldest ones are dropped first.
+const HISTORY_LENGTH: usize = 64;
+
+/// A range a single parameter is randomized within, if enabled.
+#[derive(Clone, Copy)]
+pub struct ParameterRange<T> {
+    /// Whether the parameter is randomized at all
+    pub enabled: bool,
+    /// The smallest value that is generated
+    pub min: T,
+    /// The biggest value that is generated
+    pub max: T
+}
+
+impl<T> ParameterRange<T> {
+    /// Create a new parameter range with given bounds
+    pub fn new(enabled: bool, min: T, max: T) -> ParameterRange<T> {
+        ParameterRange {
+            enabled: enabled,
+            min: min,
+            max: max
+        }
+    }
+}
+
+/// Randomizes a set of lsystem parameters within user defined ranges, and keeps a history of the generated
+/// variants that can be navigated like an undo stack.
+pub struct RandomExplorer {
+    /// The range of the angle delta, in degrees
+    pub angle_delta: ParameterRange<f32>,
+    /// The range of the step
+    pub step: ParameterRange<f32>,
+    /// The range of the iteration depth
+    pub iteration_depth: ParameterRange<u32>,
+    /// Whether a new random seed is picked for each variant
+    pub randomize_seed: bool,
+    /// Snapshots of all variants, oldest first. The first entry is the parameters the exploration started from.
+    history: Vec<LSystemParameters>,
+    /// Index of the currently shown variant in the history
+    position: usize,
+    /// State of the random number generator
+    state: u64
+}
+
+impl RandomExplorer {
+    /// Create a new explorer with default ranges and an empty history.
+    pub fn new() -> RandomExplorer {
+        // The generator state must never be zero
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+
+        RandomExplorer {
+            angle_delta: ParameterRange::new(true, 15.0, 90.0),
+            step: ParameterRange::new(false, 0.05, 0.2),
+            iteration_depth: ParameterRange::new(false, 2, 5),
+            randomize_seed: true,
+            history: Vec::new(),
+            position: 0,
+            state: seed | 1
+        }
+    }
+
+    /// Generate a new variant of given parameters and make it the current entry of the history. Any variants
+    /// after the current one are discarded, just like redo entries after a new edit.
+    pub fn generate(&mut self, current: &LSystemParameters) -> LSystemParameters {
+        if self.history.is_empty() {
+            self.history.push(current.clone());
+        } else {
+            self.history.truncate(self.position + 1);
+        }
+
+        let mut variant = current.clone();
+
+        if self.angle_delta.enabled {
+            let degrees = self.next_float(self.angle_delta.min, self.angle_delta.max);
+            variant.drawing_parameters.angle_delta = (degrees as f64).to_radians();
+        }
+
+        if self.step.enabled {
+            variant.drawing_parameters.step = self.next_float(self.step.min, self.step.max) as _;
+        }
+
+        if self.iteration_depth.enabled {
+            let span = self.iteration_depth.max.saturating_sub(self.iteration_depth.min) as u64 + 1;
+            variant.iteration_depth = self.iteration_depth.min + (self.next() % span) as u32;
+        }
+
+        if self.randomize_seed {
+            variant.seed = self.next();
+        }
+
+        self.history.push(variant.clone());
+
+        if self.history.len() > HISTORY_LENGTH {
+            self.history.remove(0);
+        }
+
+        self.position = self.history.len() - 1;
+
+        variant
+    }
+
+    /// Step back to the previous variant in the history, if there is one.
+    pub fn previous(&mut self) -> Option<LSystemParameters> {
+        if !self.has_previous() {
+            return None;
+        }
+
+        self.position -= 1;
+        Some(self.history[self.position].clone())
+    }
+
+    /// Step forward to the next variant in the history, if there is one.
+    pub fn next_variant(&mut self) -> Option<LSystemParameters> {
+        if !self.has_next() {
+            return None;
+        }
+
+        self.position += 1;
+        Some(self.history[self.position].clone())
+    }
+
+    /// Check whether there is a variant before the current one.
+    pub fn has_previous(&self) -> bool {
+        self.position > 0
+    }
+
+    /// Check whether there is a variant after the current one.
+    pub fn has_next(&self) -> bool {
+        self.position + 1 < self.history.len()
+    }
+
+    /// Retrieve the index of the current variant and the number of variants in the history.
+    pub fn position(&self) -> (usize, usize) {
+        (self.position, self.history.len())
+    }
+
+    /// Forget all generated variants. This has to be done when a different lsystem is loaded.
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+        self.position = 0;
+    }
+
+    /// Generate the next pseudo random number, using xorshift64*.
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545F4914F6CDD1D)
+    }
+
+    /// Generate a pseudo random number in given range.
+    fn next_float(&mut self, min: f32, max: f32) -> f32 {
+        let unit = (self.next() >> 40) as f32 / (1u64 << 24) as f32;
+        min + (max - min) * unit
+    }
+}
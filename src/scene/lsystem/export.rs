@@ -0,0 +1,231 @@
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{Write, BufWriter};
+use std::path::Path;
+use nalgebra_glm::Vec3;
+use image::RgbaImage;
+use gif::SetParameter;
+
+/// The file formats a turntable animation can be exported as.
+#[derive(Clone, Copy, PartialEq)]
+#[repr(u32)]
+pub enum TurntableFormat {
+    /// A single, endlessly looping animated GIF
+    Gif = 0,
+    /// One numbered PNG file per frame
+    PngSequence = 1
+}
+
+/// Settings of the turntable export dialog. These are kept between invocations of the dialog.
+#[derive(Clone, Copy)]
+pub struct TurntableSettings {
+    /// How many frames a full revolution of the camera consists of
+    pub frames: u32,
+    /// Width of the exported frames, in pixels
+    pub width: u32,
+    /// Height of the exported frames, in pixels
+    pub height: u32,
+    /// The output format
+    pub format: TurntableFormat
+}
+
+impl TurntableSettings {
+    /// Create turntable settings with default values
+    pub fn new() -> TurntableSettings {
+        TurntableSettings {
+            frames: 36,
+            width: 512,
+            height: 512,
+            format: TurntableFormat::Gif
+        }
+    }
+}
+
+/// Write given frames as an endlessly looping animated GIF. The delay between frames is given
+/// in hundredths of a second. All frames have to be of the same size.
+pub fn write_gif(path: &str, frames: Vec<RgbaImage>, delay: u16) -> Result<(), String> {
+    let (width, height) = match frames.first() {
+        Some(frame) => frame.dimensions(),
+        None => return Err("There are no frames to export".to_string())
+    };
+
+    let file = File::create(path).map_err(|e| format!("Could not create file \"{}\": {}", path, e))?;
+
+    let mut encoder = gif::Encoder::new(file, width as _, height as _, &[])
+        .map_err(|e| format!("Could not write GIF header: {}", e))?;
+
+    encoder.set(gif::Repeat::Infinite)
+        .map_err(|e| format!("Could not write GIF header: {}", e))?;
+
+    for frame in frames {
+        let mut pixels = frame.into_raw();
+
+        let mut gif_frame = gif::Frame::from_rgba_speed(width as _, height as _, &mut pixels, 10);
+        gif_frame.delay = delay;
+
+        encoder.write_frame(&gif_frame)
+            .map_err(|e| format!("Could not write GIF frame: {}", e))?;
+    }
+
+    Ok(())
+}
+
+/// Write given frames as a sequence of PNG files. The frame number is appended to the file stem
+/// of given path, e.g. "turntable.png" results in "turntable_0000.png", "turntable_0001.png" and so on.
+pub fn write_png_sequence(path: &str, frames: Vec<RgbaImage>) -> Result<(), String> {
+    let path = Path::new(path);
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("frame");
+
+    for (i, frame) in frames.iter().enumerate() {
+        let frame_path = directory.join(format!("{}_{:04}.png", stem, i));
+
+        frame.save(&frame_path)
+            .map_err(|e| format!("Could not write file \"{}\": {}", frame_path.display(), e))?;
+    }
+
+    Ok(())
+}
+
+/// Variance of the Z coordinates above which flattening an lsystem to 2D is considered lossy.
+pub const FLATTEN_VARIANCE_THRESHOLD: f32 = 1.0e-6;
+
+/// The line segment end points of an lsystem, projected onto the XY plane.
+pub struct FlattenedPoints {
+    /// Begin and end point of each line segment, in that order
+    pub points: Vec<(f32, f32)>,
+    /// Variance of the discarded Z coordinates
+    pub z_variance: f32
+}
+
+impl FlattenedPoints {
+    /// Check whether flattening discarded a significant amount of depth information, meaning
+    /// the lsystem was not planar.
+    pub fn is_lossy(&self) -> bool {
+        self.z_variance > FLATTEN_VARIANCE_THRESHOLD
+    }
+}
+
+/// Write given flattened points as CSV file. Each row contains the index of the line segment
+/// the point belongs to, followed by its X and Y coordinates.
+pub fn write_points_csv(path: &str, points: &FlattenedPoints) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| format!("Could not create file \"{}\": {}", path, e))?;
+    let mut writer = BufWriter::new(file);
+
+    let mut write_all = || -> std::io::Result<()> {
+        writeln!(writer, "segment,x,y")?;
+
+        for (i, (x, y)) in points.points.iter().enumerate() {
+            writeln!(writer, "{},{},{}", i / 2, x, y)?;
+        }
+
+        writer.flush()
+    };
+
+    write_all().map_err(|e| format!("Could not write file \"{}\": {}", path, e))
+}
+
+/// The geometry of an lsystem as exported to Wavefront OBJ files. Every element carries the index of the
+/// color palette entry it was drawn with.
+pub struct ObjGeometry {
+    /// Begin and end point of each line segment, together with its color index
+    pub lines: Vec<(Vec3, Vec3, usize)>,
+    /// Vertices of each polygon, together with its color index
+    pub polygons: Vec<(Vec<Vec3>, usize)>
+}
+
+impl ObjGeometry {
+    /// Retrieve all color indices used by at least one line segment or polygon, in ascending order.
+    pub fn color_indices(&self) -> BTreeSet<usize> {
+        self.lines.iter().map(|line| line.2)
+            .chain(self.polygons.iter().map(|polygon| polygon.1))
+            .collect()
+    }
+}
+
+/// Write given geometry as Wavefront OBJ. Line segments become line elements and polygons become
+/// faces. If `split_by_color` is set, one file is written per color index used, with the color index
+/// appended to the file stem of given path, e.g. "tree.obj" results in "tree_color0.obj",
+/// "tree_color1.obj" and so on. This allows a material to be assigned per color in other tools.
+pub fn write_obj(path: &str, geometry: &ObjGeometry, split_by_color: bool) -> Result<(), String> {
+    if !split_by_color {
+        return write_obj_file(Path::new(path), geometry, None);
+    }
+
+    let path = Path::new(path);
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("lsystem");
+
+    for color in geometry.color_indices() {
+        write_obj_file(&directory.join(format!("{}_color{}.obj", stem, color)), geometry, Some(color))?;
+    }
+
+    Ok(())
+}
+
+/// Write the elements of given geometry with given color index, or all elements if it is None, to
+/// a single OBJ file.
+fn write_obj_file(path: &Path, geometry: &ObjGeometry, color: Option<usize>) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| format!("Could not create file \"{}\": {}", path.display(), e))?;
+    let mut writer = BufWriter::new(file);
+
+    let included = |index: usize| color.map_or(true, |color| color == index);
+
+    let mut write_all = || -> std::io::Result<()> {
+        writeln!(writer, "# Exported from lsystems-gui")?;
+
+        match color {
+            Some(color) => writeln!(writer, "o color{}", color)?,
+            None => writeln!(writer, "o lsystem")?
+        }
+
+        // OBJ indices are one-based and refer to all vertices written so far
+        let mut vertex_count = 0;
+
+        for (begin, end, _) in geometry.lines.iter().filter(|line| included(line.2)) {
+            writeln!(writer, "v {} {} {}", begin.x, begin.y, begin.z)?;
+            writeln!(writer, "v {} {} {}", end.x, end.y, end.z)?;
+            writeln!(writer, "l {} {}", vertex_count + 1, vertex_count + 2)?;
+            vertex_count += 2;
+        }
+
+        for (vertices, _) in geometry.polygons.iter().filter(|polygon| included(polygon.1)) {
+            // Degenerate polygons can't be represented as faces
+            if vertices.len() < 3 {
+                continue;
+            }
+
+            for vertex in vertices {
+                writeln!(writer, "v {} {} {}", vertex.x, vertex.y, vertex.z)?;
+            }
+
+            let indices: Vec<String> = (1..=vertices.len()).map(|i| (vertex_count + i).to_string()).collect();
+            writeln!(writer, "f {}", indices.join(" "))?;
+            vertex_count += vertices.len();
+        }
+
+        writer.flush()
+    };
+
+    write_all().map_err(|e| format!("Could not write file \"{}\": {}", path.display(), e))
+}
+
+/// Wrap given lsystem parameters, serialized as JSON, in a Rust constant, like the built-in presets
+/// are embedded. The resulting code also shows how to create a scene from the constant.
+pub fn rust_snippet(json: &str) -> String {
+    // The raw string literal needs more hashes than any quote followed by hashes inside the JSON
+    let mut hashes = String::from("#");
+    while json.contains(&format!("\"{}", hashes)) {
+        hashes.push('#');
+    }
+
+    format!(
+        "/// L-System parameters exported from lsystems-gui\n\
+         pub const LSYSTEM: &'static str = r{hashes}\"{json}\"{hashes};\n\
+         \n\
+         // The scene showing the lsystem is then created using:\n\
+         // LSystemScene::new(&LSystemParameters::from_string(LSYSTEM), &settings, width, height)\n",
+        hashes = hashes,
+        json = json
+    )
+}
@@ -0,0 +1,48 @@
+use std::collections::VecDeque;
+
+/// Number of frames the frame time history covers
+const HISTORY_LENGTH: usize = 240;
+
+/// A rolling history of the durations of the most recent frames, in milliseconds.
+pub struct FrameTimes {
+    /// The frame durations, oldest first
+    history: VecDeque<f32>
+}
+
+impl FrameTimes {
+    /// Create a new, empty frame time history.
+    pub fn new() -> FrameTimes {
+        FrameTimes {
+            history: VecDeque::with_capacity(HISTORY_LENGTH)
+        }
+    }
+
+    /// Record the duration of a frame, given in seconds. The oldest frame is dropped if the
+    /// history is full.
+    pub fn record(&mut self, delta_time: f32) {
+        if self.history.len() == HISTORY_LENGTH {
+            self.history.pop_front();
+        }
+
+        self.history.push_back(delta_time * 1000.0);
+    }
+
+    /// Retrieve the recorded frame durations in milliseconds, oldest first.
+    pub fn values(&self) -> Vec<f32> {
+        self.history.iter().cloned().collect()
+    }
+
+    /// Determine minimum, average and maximum of the recorded frame durations in milliseconds.
+    /// Returns None if no frames were recorded yet.
+    pub fn statistics(&self) -> Option<(f32, f32, f32)> {
+        if self.history.is_empty() {
+            return None;
+        }
+
+        let min = self.history.iter().cloned().fold(std::f32::MAX, f32::min);
+        let max = self.history.iter().cloned().fold(0.0, f32::max);
+        let avg = self.history.iter().sum::<f32>() / self.history.len() as f32;
+
+        Some((min, avg, max))
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use lsystems_core::LSystem;
+use lsystems_core::drawing::TurtleCommand;
+use crate::data::LSystemParameters;
+
+/// The generations of all line segments of an interpreted lsystem.
+pub struct SegmentGenerations {
+    /// The position of each segment on the generation gradient, in [0, 1]
+    pub fractions: Vec<f32>,
+    /// The generation of each segment. This is None if the generations could not be determined, in which
+    /// case the fractions follow the drawing order instead.
+    pub generations: Option<Vec<u32>>
+}
+
+impl SegmentGenerations {
+    /// Check whether the generations could be determined by replaying the expansion.
+    pub fn replayed(&self) -> bool {
+        self.generations.is_some()
+    }
+
+    /// Check whether the segment with given index lies within given inclusive range of generations. All segments
+    /// pass if the generations could not be determined.
+    pub fn in_range(&self, index: usize, range: (u32, u32)) -> bool {
+        match &self.generations {
+            Some(generations) => generations[index] >= range.0 && generations[index] <= range.1,
+            None => true
+        }
+    }
+}
+
+/// Determine the generation of every line segment of an interpreted lsystem, as a fraction in [0, 1].
+/// The generation of a segment is the iteration in which the symbol drawing it was produced, with the
+/// symbols of the axiom being generation zero.
+///
+/// lsystems-core does not keep track of the generations, so the expansion is replayed here using the
+/// rules in their simple `A -> ...` form. If that does not reproduce the expansion of the core, which
+/// happens for example with stochastic rules, the drawing order of the segments is used instead.
+pub fn segment_generations(lsystem: &LSystem, params: &LSystemParameters) -> SegmentGenerations {
+    let segment_count = lsystem.drawing_result.line_segments.len();
+
+    if let Some(generations) = replay_generations(lsystem, params) {
+        if generations.len() == segment_count {
+            let max = params.iteration_depth.max(1) as f32;
+
+            return SegmentGenerations {
+                fractions: generations.iter().map(|g| *g as f32 / max).collect(),
+                generations: Some(generations)
+            };
+        }
+    }
+
+    let max = (segment_count.max(2) - 1) as f32;
+
+    SegmentGenerations {
+        fractions: (0..segment_count).map(|i| i as f32 / max).collect(),
+        generations: None
+    }
+}
+
+/// Replay the expansion of the lsystem while tracking the generation of each module, and collect the
+/// generations of all modules that draw a line segment.
+fn replay_generations(lsystem: &LSystem, params: &LSystemParameters) -> Option<Vec<u32>> {
+    let mut rules = HashMap::new();
+    for rule in &params.rules {
+        let (predecessor, successor) = split_rule(rule)?;
+
+        // Multiple rules for the same symbol are either stochastic or conditional, which can not be replayed
+        if rules.insert(predecessor, successor).is_some() {
+            return None;
+        }
+    }
+
+    // The replayed expansion can never become longer than the real one without being wrong
+    let limit = lsystem.iteration_result.len();
+
+    let mut modules: Vec<(char, u32)> = params.axiom.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| (c, 0))
+        .collect();
+
+    for generation in 1..=params.iteration_depth {
+        let mut next = Vec::with_capacity(modules.len());
+
+        for (symbol, old_generation) in modules {
+            match rules.get(&symbol) {
+                Some(successor) => next.extend(successor.iter().map(|c| (*c, generation))),
+                None => next.push((symbol, old_generation))
+            }
+
+            if next.len() > limit {
+                return None;
+            }
+        }
+
+        modules = next;
+    }
+
+    if modules.len() != limit || !modules.iter().zip(lsystem.iteration_result.iter()).all(|(m, r)| m.0 == r.identifier) {
+        return None;
+    }
+
+    // Later interpretations for the same symbol replace earlier ones, just like in the interpretation engine
+    let mut commands = HashMap::new();
+    for interpretation in &params.interpretations {
+        if let Some(symbol) = interpretation.symbol {
+            commands.insert(symbol, interpretation.operation);
+        }
+    }
+
+    let generations = modules.iter()
+        .filter(|(symbol, _)| match commands.get(symbol) {
+            Some(TurtleCommand::Forward) | Some(TurtleCommand::ForwardContracting) => true,
+            _ => false
+        })
+        .map(|(_, generation)| *generation)
+        .collect();
+
+    Some(generations)
+}
+
+/// Split a rule of the form `A -> BC` into its predecessor symbol and the successor symbols. Returns
+/// None for rules with context or more than one predecessor symbol.
+fn split_rule(rule: &str) -> Option<(char, Vec<char>)> {
+    let pos = rule.find("->")?;
+    let predecessor = rule[..pos].trim();
+    let mut symbols = predecessor.chars();
+
+    match (symbols.next(), symbols.next()) {
+        (Some(symbol), None) => Some((symbol, rule[pos + 2..].chars().filter(|c| !c.is_whitespace()).collect())),
+        _ => None
+    }
+}
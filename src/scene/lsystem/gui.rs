@@ -1,27 +1,30 @@
-use imgui::{MenuItem, EditableColor, ColorEdit, ImStr, StyleColor, ImString, ImColor, Slider, Condition, Context as ImContext, Window as ImWindow, im_str, Ui};
+use imgui::{MenuItem, Selectable, EditableColor, ColorEdit, ColorButton, ImStr, StyleColor, ImString, ImColor, Slider, Condition, Context as ImContext, Window as ImWindow, im_str, Ui};
+use std::collections::HashSet;
 use nalgebra_glm::Vec3;
 use crate::scene::lsystem::*;
+use crate::scene::lsystem::export::*;
+use crate::scene::lsystem::explorer::*;
 use crate::scene::*;
 use crate::scene::bezier::*;
 use crate::data;
 use crate::data::bezier::*;
+use crate::data::palette::*;
 use crate::data::*;
 use crate::gui_utils::*;
 use lsystems_core::drawing::types::*;
 use lsystems_core::drawing::TurtleCommand;
-use nfd::*;
-use std::fs::*;
 
 
-fn do_color_palette_entry(ui: &Ui, value: &mut Vec3, idx: usize) -> bool {
+fn do_color_palette_entry(ui: &Ui, value: &mut Vec3, opacity: &mut f32, idx: usize) -> bool {
     let outer_id = ui.push_id(idx as i32);
     
-    let mut color: [f32; 3] = [value.x, value.y, value.z];
+    let mut color: [f32; 4] = [value.x, value.y, value.z, *opacity];
 
     let mut changed = false;
     if ColorEdit::new(im_str!(""), &mut color).build(ui) {
         let new_color = Vec3::new(color[0], color[1], color[2]);
         *value = new_color;
+        *opacity = color[3];
         changed = true;
     }
 
@@ -29,12 +32,34 @@ fn do_color_palette_entry(ui: &Ui, value: &mut Vec3, idx: usize) -> bool {
     return changed;
 }
 
+/// Retrieve the condition under which windows are placed at their default position and size. This is
+/// only done once, unless the user requested the window layout to be reset.
+fn layout_condition(lsystem: &LSystemScene) -> Condition {
+    if lsystem.reset_layout {
+        Condition::Always
+    } else {
+        Condition::FirstUseEver
+    }
+}
+
 pub fn do_lsystem_params_gui(ui: &Ui, lsystem: &mut LSystemScene) -> SceneAction {
     let mut action = SceneAction::Nothing;
 
-    ImWindow::new(&ImString::new(&lsystem.lsystem_params.name))
-            .size([450.0, 550.0], Condition::FirstUseEver)
-            .position([0.0, 60.0], Condition::FirstUseEver)
+    if !lsystem.show_params_window {
+        return action;
+    }
+
+    // The part after "###" is used as the window ID, which keeps the window state stable
+    // when the title changes.
+    let title = format!(
+        "{}{}###lsystem_params",
+        lsystem.lsystem_params.name,
+        if lsystem.dirty { "*" } else { "" }
+    );
+
+    ImWindow::new(&ImString::new(title))
+            .size([450.0, 550.0], layout_condition(lsystem))
+            .position([0.0, 60.0], layout_condition(lsystem))
             .build(&ui, || {
                 if ui.collapsing_header(im_str!("Drawing Parameters"))
                     .default_open(true)
@@ -76,6 +101,22 @@ pub fn do_lsystem_params_gui(ui: &Ui, lsystem: &mut LSystemScene) -> SceneAction
                     ui.unindent();
                 }
 
+                if ui.collapsing_header(im_str!("Random Explorer"))
+                    .default_open(false)
+                    .build() {
+                    ui.indent();
+                    do_explorer(ui, lsystem);
+                    ui.unindent();
+                }
+
+                if ui.collapsing_header(im_str!("Statistics"))
+                    .default_open(false)
+                    .build() {
+                    ui.indent();
+                    do_statistics(ui, lsystem);
+                    ui.unindent();
+                }
+
                 if ui.collapsing_header(im_str!("Application Settings"))
                     .default_open(true)
                     .build() {
@@ -121,11 +162,61 @@ fn draw_operations() -> Vec<&'static ImStr> {
     ]
 }
 
+/// Retrieve the color, icon and description of the turtle command with given index, as used by
+/// the hint shown next to each interpretation. The color encodes the kind of command.
+fn operation_hint(index: usize) -> ([f32; 4], &'static ImStr, &'static ImStr) {
+    const MOVE: [f32; 4] = [0.4, 0.8, 0.3, 1.0];
+    const ROTATE: [f32; 4] = [0.35, 0.6, 1.0, 1.0];
+    const STATE: [f32; 4] = [0.95, 0.8, 0.3, 1.0];
+    const POLYGON: [f32; 4] = [0.75, 0.45, 0.95, 1.0];
+    const STYLE: [f32; 4] = [1.0, 0.55, 0.25, 1.0];
+    const NONE: [f32; 4] = [0.5, 0.5, 0.5, 1.0];
+
+    match index {
+        0 => (MOVE, im_str!("|"), im_str!("Move forward by one step, drawing a line.")),
+        1 => (MOVE, im_str!(":"), im_str!("Move forward by one step without drawing (pen up).")),
+        2 => (ROTATE, im_str!(">"), im_str!("Turn right by the angle delta.")),
+        3 => (ROTATE, im_str!("<"), im_str!("Turn left by the angle delta.")),
+        4 => (STATE, im_str!("["), im_str!("Push the current turtle state (position, orientation, color, width) on the stack.")),
+        5 => (STATE, im_str!("]"), im_str!("Pop the most recently saved turtle state from the stack.")),
+        6 => (NONE, im_str!("-"), im_str!("Do nothing. Useful for symbols that only drive the rules.")),
+        7 => (MOVE, im_str!("!"), im_str!("Move forward by one step, drawing a line, and decrease the line width.")),
+        8 => (ROTATE, im_str!("v"), im_str!("Pitch down by the angle delta.")),
+        9 => (ROTATE, im_str!("^"), im_str!("Pitch up by the angle delta.")),
+        10 => (ROTATE, im_str!("\\"), im_str!("Roll left by the angle delta.")),
+        11 => (ROTATE, im_str!("/"), im_str!("Roll right by the angle delta.")),
+        12 => (ROTATE, im_str!("U"), im_str!("Turn around by 180 degrees.")),
+        13 => (POLYGON, im_str!("{"), im_str!("Begin recording a new polygon.")),
+        14 => (POLYGON, im_str!("}"), im_str!("Finish the current polygon.")),
+        15 => (POLYGON, im_str!("."), im_str!("Add the current turtle position as a vertex to the current polygon.")),
+        16 => (STYLE, im_str!("+"), im_str!("Switch to the next color palette entry.")),
+        17 => (STYLE, im_str!("-"), im_str!("Switch to the previous color palette entry.")),
+        18 => (STYLE, im_str!("+"), im_str!("Increase the line width by the line width delta.")),
+        19 => (STYLE, im_str!("-"), im_str!("Decrease the line width by the line width delta.")),
+        _ => (NONE, im_str!("?"), im_str!("Unknown operation"))
+    }
+}
+
+/// Draw a small colored icon for given turtle command, which shows a description when hovered over.
+fn operation_hint_marker(ui: &Ui, index: usize) {
+    let (color, icon, description) = operation_hint(index);
+
+    ui.text_colored(color, icon);
+    if ui.is_item_hovered() {
+        ui.tooltip_text(description);
+    }
+}
+
 fn do_bezier_models(ui: &Ui, system: &mut LSystemScene, action: &mut SceneAction) {
 
     //let mut to_rename: Option<(usize, char, char)> = None;
     let mut to_delete: Option<usize> = None;
     let mut to_edit: Option<usize> = None;
+    let mut to_clone: Option<(usize, MirrorPlane)> = None;
+    let mut to_swap: Option<(usize, usize)> = None;
+    // The models are borrowed while iterating, so the scene is only marked dirty afterwards
+    let mut renamed = false;
+    let model_count = system.lsystem_params.bezier_models.len();
 
     // We need to push an outer ID here since we are using buttons with the same identifiers as the ones
     // used to remove and add rules.
@@ -167,6 +258,8 @@ fn do_bezier_models(ui: &Ui, system: &mut LSystemScene, action: &mut SceneAction
                     system.bezier_manager.update_meshes(model);
                 }
             }
+
+            renamed = true;
         }
 
         token.pop(ui);
@@ -177,6 +270,48 @@ fn do_bezier_models(ui: &Ui, system: &mut LSystemScene, action: &mut SceneAction
             to_edit = Some(i);
         }
 
+        ui.same_line(0.0);
+
+        if ui.button(im_str!("clone"), [0.0, 0.0]) {
+            ui.open_popup(im_str!("Clone"));
+        }
+
+        ui.same_line(0.0);
+
+        if ui.button(im_str!("up"), [0.0, 0.0]) && i > 0 {
+            to_swap = Some((i - 1, i));
+        }
+
+        ui.same_line(0.0);
+
+        if ui.button(im_str!("down"), [0.0, 0.0]) && i + 1 < model_count {
+            to_swap = Some((i, i + 1));
+        }
+
+        // The popup is drawn with the same id stack it was opened with, so it refers to this model
+        ui.popup(im_str!("Clone"), || {
+            if Selectable::new(im_str!("Simple Clone")).build(ui) {
+                to_clone = Some((i, MirrorPlane::None));
+            }
+
+            ui.separator();
+            ui.text(im_str!("Mirrored Clone"));
+            ui.same_line(0.0);
+            help_marker(ui, im_str!("A mirroring clone uses the selected plane to mirror all control points."));
+
+            if Selectable::new(im_str!(".. on XY plane")).build(ui) {
+                to_clone = Some((i, MirrorPlane::XY));
+            }
+
+            if Selectable::new(im_str!(".. on XZ plane")).build(ui) {
+                to_clone = Some((i, MirrorPlane::XZ));
+            }
+
+            if Selectable::new(im_str!(".. on YZ plane")).build(ui) {
+                to_clone = Some((i, MirrorPlane::YZ));
+            }
+        });
+
         let colors = ui.push_style_colors(&[
             (StyleColor::Button, [0.6, 0.239, 0.239, 1.0]),
             (StyleColor::ButtonHovered, [0.7, 0.2117, 0.2117, 1.0]),
@@ -197,15 +332,33 @@ fn do_bezier_models(ui: &Ui, system: &mut LSystemScene, action: &mut SceneAction
 
     match to_edit {
         Some(i) => {
+            let edit = system.edit_bezier_model(i);
+
             *action = SceneAction::PushScene(
                 make_rc_cell(
-                    BezierEditorScene::new(system.edit_bezier_model(i), system.width, system.height)
+                    BezierEditorScene::new(&system.lsystem_params.bezier_models[i], edit, system.width, system.height, system.app_settings.normal_debug_length)
                 )
             );
         },
         _ => {}
     }
 
+    if renamed {
+        system.mark_dirty();
+    }
+
+    if let Some((a, b)) = to_swap {
+        system.swap_bezier_models(a, b);
+    }
+
+    // The clone starts out without a symbol, so no meshes have to be generated yet. This happens as soon
+    // as the user assigns one.
+    if let Some((i, plane)) = to_clone {
+        let clone = system.lsystem_params.bezier_models[i].clone_mirrored(plane);
+        system.lsystem_params.bezier_models.insert(i + 1, clone);
+        system.mark_dirty();
+    }
+
     match to_delete {
         Some(i) => {
             // Remove mesh from manager, if the symbol was not empty.
@@ -213,6 +366,7 @@ fn do_bezier_models(ui: &Ui, system: &mut LSystemScene, action: &mut SceneAction
 
             // Remove model parameters from lsystem parameters.
             system.lsystem_params.bezier_models.remove(i);
+            system.mark_dirty();
 
             if let Some(id) = id {
                 system.bezier_manager.remove_meshes(id);
@@ -249,9 +403,28 @@ fn do_bezier_models(ui: &Ui, system: &mut LSystemScene, action: &mut SceneAction
         system.lsystem_params.bezier_models.push(
             BezierModelParameters::default()
         );
+
+        system.mark_dirty();
     }
 
     colors.pop(ui);
+
+    ui.same_line(0.0);
+
+    if ui.button(im_str!("Save Library.."), [0.0, 0.0]) {
+        system.export_patch_library();
+    }
+
+    ui.same_line(0.0);
+
+    if ui.button(im_str!("Load Library.."), [0.0, 0.0]) {
+        system.import_patch_library();
+    }
+
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("Patch libraries store bezier models on their own, so they can be reused in other L-Systems.\n\
+                             Loading a library appends its models. Models whose symbol is already in use lose their symbol."));
+
     outer_id.pop(ui);
 }
 
@@ -281,247 +454,1282 @@ fn index_to_operation(index: usize) -> TurtleCommand {
     }
 }
 
-fn save_text_file(path: &str, contents: &str) {
-    write(path, contents).expect("Unable to write file");
-}
-
-fn load_text_file(path: &str) -> String {
-    read_to_string(path).expect("Unable to read file")
-}
-
 fn do_colors(ui: &Ui, lsystem: &mut LSystemScene) {
     let mut was_changed = false;
-    for (i, color) in &mut lsystem.lsystem_params.color_palette.iter_mut().enumerate() {
-        if do_color_palette_entry(ui, color, i) {
+
+    // Preview of the sequence of colors the turtle color commands cycle through
+    for (i, color) in lsystem.lsystem_params.color_palette.iter().enumerate() {
+        if i > 0 {
+            ui.same_line_with_spacing(0.0, 0.0);
+        }
+
+        ColorButton::new(im_str!("##preview"), [color.x, color.y, color.z, 1.0])
+            .size([18.0, 18.0])
+            .build(ui);
+    }
+
+    ui.spacing();
+
+    // Make sure there is an opacity value for every palette entry
+    let params = &mut lsystem.lsystem_params;
+    params.color_opacities.resize(params.color_palette.len(), 1.0);
+
+    for (i, (color, opacity)) in params.color_palette.iter_mut().zip(params.color_opacities.iter_mut()).enumerate() {
+        if do_color_palette_entry(ui, color, opacity, i) {
             was_changed = true;
         }
     }
 
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("Polygons using a color with an alpha value below 1 are drawn semi-transparent.\n\
+                             Transparent polygons are sorted per color, not per triangle, so overlapping\n\
+                             polygons of the same color might not blend correctly."));
+
     if was_changed {
         lsystem.refresh_color_palette();
     }
-}
 
-pub fn do_main_menu_bar(ui: &Ui, lsystem: &mut LSystemScene) {
-    if let Some(token) = ui.begin_main_menu_bar() {
-        do_file_menu(ui, lsystem);
-        do_presets(ui, lsystem);
-        token.end(ui);
+    if ui.button(im_str!("Generate Gradient.."), [0.0, 0.0]) {
+        ui.open_popup(im_str!("Generate Gradient"));
     }
-}
-
-fn do_presets(ui: &Ui, lsystem: &mut LSystemScene) {
-    if let Some(token) = ui.begin_menu(im_str!("Examples"), true) {
-        MenuItem::new(im_str!("2D"))
-            .enabled(false)
-            .build(ui);
 
-        if MenuItem::new(im_str!("Koch Snowflake")).build(ui) {
-            lsystem.load(data::presets::KOCH_SNOWFLAKE);
-        }
+    ui.same_line(0.0);
 
-        if MenuItem::new(im_str!("Penrose")).build(ui) {
-            lsystem.load(data::presets::PENROSE);
-        }
+    if ui.button(im_str!("Load Palette.."), [0.0, 0.0]) {
+        let result = nfd::open_file_dialog(Some("gpl,png,jpg,jpeg,bmp"), None);
 
+        if let Some(path) = lsystem.picked_path(result) {
+            let palette = if path.to_lowercase().ends_with(".gpl") {
+                load_palette_from_gpl(&path)
+            } else {
+                load_palette_from_image(&path, lsystem.palette_image_samples)
+            };
 
-        ui.separator();
+            match palette {
+                Ok(colors) => {
+                    lsystem.lsystem_params.color_palette = colors;
+                    lsystem.lsystem_params.color_opacities.clear();
+                    lsystem.refresh_color_palette();
+                },
+                Err(e) => lsystem.report_error(&e)
+            }
+        }
+    }
 
-        MenuItem::new(im_str!("3D"))
-            .enabled(false)
-            .build(ui);
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("Replaces the current palette with the colors stored in a GIMP palette (.gpl) file,\n\
+                             or with colors sampled from evenly spaced columns of an image."));
 
-        token.end(ui);
+    if ui.button(im_str!("Apply color-blind palette"), [0.0, 0.0]) {
+        lsystem.lsystem_params.color_palette = color_blind_safe();
+        lsystem.lsystem_params.color_opacities.clear();
+        lsystem.refresh_color_palette();
     }
-}
 
-fn do_file_menu(ui: &Ui, lsystem: &mut LSystemScene) {
-    if let Some(token) = ui.begin_menu(im_str!("File"), true) {
-        if MenuItem::new(im_str!("New"))
-            .shortcut(im_str!("      Ctrl+N"))
-            .build(ui) {
-                lsystem.load(data::presets::EMPTY);
-        }
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("Replaces the current palette with the eight colors of the Okabe-Ito palette,\n\
+                             which stay distinguishable for people with common forms of color blindness."));
 
-        if MenuItem::new(im_str!("Open"))
-            .shortcut(im_str!("      Ctrl+O"))
-            .build(ui) {
-                let result = nfd::open_file_dialog(Some("json"), None).unwrap_or_else(|e| {
-                    panic!(e);
-                });
-
-                match result {
-                    Response::Okay(path) => {
-                        let json = load_text_file(&path);
-                        lsystem.load(&json);
-                    },
-                    Response::OkayMultiple(paths) => {
-                        let json = load_text_file(&paths.iter().next().unwrap());
-                        lsystem.load(&json);
-                    },
-                    // User canceled
-                    _ => {}
-                }
-        }
+    Slider::<u32>::new(im_str!("Image samples"), 1..=64).build(ui, &mut lsystem.palette_image_samples);
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("How many colors are sampled when loading a palette from an image."));
 
-        if MenuItem::new(im_str!("Save"))
-            .shortcut(im_str!("      Ctrl+S"))
-            .build(ui) {
-                let result = nfd::open_save_dialog(Some("json"), None).unwrap_or_else(|e| {
-                    panic!(e);
-                });
-
-                match result {
-                    Response::Okay(path) => {
-                        let json = lsystem.save();
-                        save_text_file(&path, &json);
-                    },
-                    // User canceled, and multiple cant ever happen here
-                    _ => {}
-                }
-        }
+    do_gradient_popup(ui, lsystem);
+}
 
-        token.end(ui);
-    }
+/// Show the current toast message, if any, at the bottom center of the screen. The message is cleared
+/// once its time is up.
+pub fn do_toast(ui: &Ui, lsystem: &mut LSystemScene) {
+    let message = match &lsystem.toast {
+        Some((message, until)) if *until > lsystem.animation_clock => message.clone(),
+        Some(_) => {
+            lsystem.toast = None;
+            return;
+        },
+        None => return
+    };
+
+    ImWindow::new(im_str!("Toast"))
+            .title_bar(false)
+            .resizable(false)
+            .movable(false)
+            .always_auto_resize(true)
+            .position([(lsystem.width as f32) / 2.0, (lsystem.height as f32) - 60.0], Condition::Always)
+            .position_pivot([0.5, 0.0])
+            .build(&ui, || {
+                ui.text(message);
+            });
 }
 
-fn do_debug_options(ui: &Ui, lsystem: &mut LSystemScene) {
-    if ui.checkbox(im_str!("Show normal vectors"), &mut lsystem.app_settings.show_normals) {
-        lsystem.refresh_meshes();
-    }
+/// An action offered by the command palette, consisting of its label and the handler invoking it.
+type PaletteAction = (&'static str, Box<dyn Fn(&mut LSystemScene)>);
 
-    if ui.checkbox(im_str!("Draw polygons as wireframe"), &mut lsystem.app_settings.draw_wireframe) {
-        lsystem.refresh_wireframe_flag();
+/// Collect all actions offered by the command palette.
+fn command_palette_actions() -> Vec<PaletteAction> {
+    fn action<F: Fn(&mut LSystemScene) + 'static>(label: &'static str, handler: F) -> PaletteAction {
+        (label, Box::new(handler))
     }
-}
 
-fn do_interpretations(ui: &Ui, lsystem: &mut LSystemScene) {
-    let mut modified = false;
+    vec![
+        action("New", |l| l.perform_discarding_action(DiscardingAction::New)),
+        action("Open..", |l| l.perform_discarding_action(DiscardingAction::Open)),
+        action("Import text L-system..", |l| l.perform_discarding_action(DiscardingAction::ImportText)),
+        action("Save", |l| l.save_file()),
+        action("Save As..", |l| l.save_file_as()),
+        action("Reload from disk", |l| l.reload_file()),
+        action("New Tab", |l| l.new_document = Some(LSystemParameters::from_string(data::presets::EMPTY))),
+        action("Duplicate into New Tab", |l| l.new_document = Some(l.lsystem_params.clone())),
+        action("Close Tab", |l| l.perform_discarding_action(DiscardingAction::CloseDocument)),
+        action("Save Screenshot..", |l| l.save_screenshot()),
+        action("Export Turntable..", |l| l.show_turntable_dialog = true),
+        action("Export 2D Points (CSV)..", |l| l.export_csv()),
+        action("Export OBJ..", |l| l.show_obj_dialog = true),
+        action("Center camera", |l| l.center_camera()),
+        action("Toggle camera lock", |l| l.camera.set_locked(!l.camera.locked())),
+        action("Reload (rebuild everything)", |l| l.force_refresh_all()),
+        action("Toggle auto refresh", |l| l.toggle_auto_refresh()),
+        action("Toggle wireframe", |l| {
+            l.app_settings.wireframe_mode = match l.app_settings.wireframe_mode {
+                WireframeMode::Off => WireframeMode::Only,
+                _ => WireframeMode::Off
+            };
 
-    let params = &mut lsystem.lsystem_params;
+            l.refresh_wireframe_flag();
+        }),
+        action("Toggle bounding box", |l| l.app_settings.draw_bounding_box = !l.app_settings.draw_bounding_box),
+        action("Add rule", |l| {
+            l.lsystem_params.rules.push(String::new());
+            l.refresh_rules();
+        }),
+        action("Show parameters window", |l| l.show_params_window = true),
+        action("Reset window layout", |l| {
+            l.reset_layout = true;
+            l.show_params_window = true;
+        })
+    ]
+}
 
-    let mut to_delete: Option<usize> = None;
+/// Check whether given action label matches the command palette filter. Every word of the filter has to
+/// occur in the label, ignoring case.
+fn matches_filter(label: &str, filter: &str) -> bool {
+    let label = label.to_lowercase();
 
-    // We need to push an outer ID here since we are using buttons with the same identifiers as the ones
-    // used to remove and add rules.
-    let outer_id = ui.push_id(2);
+    filter.to_lowercase()
+        .split_whitespace()
+        .all(|word| label.contains(word))
+}
 
-    for (i, interp) in params.interpretations.iter_mut().enumerate() {
-        let id = ui.push_id(i as i32);
+/// Show the command palette, if it was requested via Ctrl+P or the view menu. It lists all actions matching
+/// the filter text. Pressing Enter invokes the first of them.
+pub fn do_command_palette(ui: &Ui, lsystem: &mut LSystemScene) {
+    if lsystem.open_command_palette {
+        lsystem.open_command_palette = false;
+        lsystem.command_filter.clear();
+        ui.open_popup(im_str!("Command Palette"));
+    }
 
-        let mut symbol_str = ImString::with_capacity(16);
+    let actions = command_palette_actions();
+    let mut chosen = None;
 
-        if let Some(symbol) = interp.symbol {
-            symbol_str.push_str(&symbol.to_string());
-        }
+    unsafe {
+        imgui::sys::igSetNextWindowPos(
+            imgui::sys::ImVec2 { x: (lsystem.width as f32) / 2.0, y: 80.0 },
+            Condition::Appearing as _,
+            imgui::sys::ImVec2 { x: 0.5, y: 0.0 }
+        );
+    }
 
-        let token = ui.push_item_width(20.0);
+    let filter = &mut lsystem.command_filter;
 
-        if ui.input_text(im_str!("##sym"), &mut symbol_str).build() {
-            let trimmed = symbol_str.to_str().trim();
-            if trimmed.is_empty() {
-                interp.symbol = None;
-            } else {
-                interp.symbol = Some(trimmed.chars().next().unwrap());
+    ui.popup(im_str!("Command Palette"), || {
+        // The filter has to have keyboard focus right away, so the user can start typing
+        unsafe {
+            if imgui::sys::igIsWindowAppearing() {
+                imgui::sys::igSetKeyboardFocusHere(0);
             }
-
-            modified = true;
         }
 
-        token.pop(ui);
+        let enter = ui.input_text(im_str!("##filter"), filter)
+            .enter_returns_true(true)
+            .build();
 
-        ui.same_line(0.0);
-        ui.text(im_str!("->"));
-        ui.same_line(0.0);
-        let mut current_item: i32 = interp.operation as _;
-        let items = draw_operations();
+        let matching: Vec<usize> = (0..actions.len())
+            .filter(|&i| matches_filter(actions[i].0, filter.to_str()))
+            .collect();
 
-        if ui.combo(im_str!("##op"), &mut current_item, &items, 8) {
-            let new_operation = index_to_operation(current_item as _);
-            interp.operation = new_operation;
-            modified = true;
+        if enter {
+            chosen = matching.first().cloned();
         }
 
-        let colors = ui.push_style_colors(&[
-            (StyleColor::Button, [0.6, 0.239, 0.239, 1.0]),
-            (StyleColor::ButtonHovered, [0.7, 0.2117, 0.2117, 1.0]),
-            (StyleColor::ButtonActive, [0.8, 0.1607, 0.1607, 1.0])
-        ]);
+        for &i in &matching {
+            if Selectable::new(&ImString::new(actions[i].0)).build(ui) {
+                chosen = Some(i);
+            }
+        }
 
-        ui.same_line(0.0);
+        if matching.is_empty() {
+            ui.text_disabled(im_str!("No matching actions"));
+        }
 
-        if ui.button(im_str!("-"), [0.0, 0.0]) {
-            println!("To delete: {}", i);
-            modified = true;
-            to_delete = Some(i);
+        if chosen.is_some() {
+            ui.close_current_popup();
         }
-        
-        colors.pop(ui);
+    });
 
-        id.pop(ui);     
-    }  
+    if let Some(i) = chosen {
+        (actions[i].1)(lsystem);
+    }
+}
 
-    match to_delete {
-        Some(i) => {
-            params.interpretations.remove(i);
-        }
-        _ => {}
-    };
+/// Show a small progress window while the lsystem is being iterated on a background thread, allowing
+/// the user to cancel the iteration.
+pub fn do_iteration_progress(ui: &Ui, lsystem: &mut LSystemScene) {
+    if !lsystem.is_iterating() {
+        return;
+    }
 
+    const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+    // Spinner steps per second
+    const SPINNER_SPEED: f64 = 7.5;
 
-    let colors = ui.push_style_colors(&[
-        (StyleColor::Button, [0.349, 0.6, 0.239, 1.0]),
-        (StyleColor::ButtonHovered, [0.3568, 0.7019, 0.2117, 1.0]),
-        (StyleColor::ButtonActive, [0.3529, 0.8, 0.1607, 1.0])
-    ]);
+    let spinner = SPINNER[(lsystem.animation_clock * SPINNER_SPEED) as usize % SPINNER.len()];
 
-    if ui.button(im_str!("+"), [0.0, 0.0]) {
-        params.interpretations.push(
-            Interpretation{
-                symbol: None,
-                operation: TurtleCommand::Forward
-            }
-        );
+    let mut cancel = false;
 
-        println!("Added new interp, size is now {}", params.interpretations.len());
-        modified = true;
-    }
+    ImWindow::new(im_str!("Iterating"))
+            .title_bar(false)
+            .resizable(false)
+            .always_auto_resize(true)
+            .position([(lsystem.width as f32) / 2.0 - 60.0, 60.0], Condition::Always)
+            .build(&ui, || {
+                ui.text(format!("{} Iterating..", spinner));
+                ui.same_line(0.0);
 
-    colors.pop(ui);
-    outer_id.pop(ui);
+                if ui.button(im_str!("Cancel"), [0.0, 0.0]) {
+                    cancel = true;
+                }
+            });
 
-    if modified {
-        lsystem.refresh_interpretations();
+    if cancel {
+        lsystem.cancel_iteration();
     }
 }
 
-fn do_rules(ui: &Ui, lsystem: &mut LSystemScene) {
-    let mut modified = false;
-    let params = &mut lsystem.lsystem_params;
+/// Draw a small, non-interactive color swatch.
+fn color_swatch(ui: &Ui, color: &Vec3) {
+    ColorButton::new(im_str!("##swatch"), [color.x, color.y, color.z, 1.0])
+        .size([12.0, 12.0])
+        .build(ui);
+}
 
-    let mut axiom = ImString::with_capacity(256);
-    axiom.push_str(&params.axiom);
-    if ui.input_text(im_str!("Axiom"), &mut axiom).build() {
-        params.axiom = axiom.to_str().to_string();
-        modified = true;
+/// Show the width, height and depth of the bounding box as labels next to its edges, if enabled.
+pub fn do_bounding_box_dimensions(ui: &Ui, lsystem: &mut LSystemScene) {
+    if !lsystem.app_settings.draw_bounding_box || !lsystem.app_settings.show_bounding_box_dimensions {
+        return;
     }
 
-    ui.text(im_str!("Production rules:"));
-    ui.indent();
+    // The labels are placed in window coordinates, which do not match a letterboxed render target
+    if lsystem.render_target.is_some() {
+        return;
+    }
 
-    // The rule to delete. It can only ever be one per frame, so this is enough.
-    let mut to_delete = None;
+    let (min, max) = match &lsystem.bounding_box {
+        Some(bb) => bb.extents(),
+        None => return
+    };
 
-    for (i, rule) in params.rules.iter_mut().enumerate() {
-        let mut rule_str = ImString::with_capacity(256);
-        rule_str.push_str(rule);
+    let size = max - min;
+    let center = (min + max) * 0.5;
 
-        let id = ui.push_id(i as i32);
+    // Each label sits at the middle of an edge running along its axis. The rendered lsystem may be
+    // scaled, but the shown dimensions are always in world units.
+    let scale = lsystem.render_scale;
+    let labels = vec![
+        (Vec3::new(center.x, min.y, min.z) * scale, format!("W: {:.3}", size.x)),
+        (Vec3::new(min.x, center.y, min.z) * scale, format!("H: {:.3}", size.y)),
+        (Vec3::new(max.x, min.y, center.z) * scale, format!("D: {:.3}", size.z))
+    ];
 
-        if ui.input_text(im_str!("##rule"), &mut rule_str).build() {
-            *rule = rule_str.to_str().to_string();
-            modified = true;
-        }
+    world_space_labels(ui, im_str!("Bounding Box Dimensions"), &lsystem.camera, lsystem.width, lsystem.height, &labels);
+}
+
+/// Show information about the polygon selected via Ctrl+Click, if any.
+pub fn do_polygon_inspector(ui: &Ui, lsystem: &mut LSystemScene) {
+    let index = match lsystem.selected_polygon {
+        Some(index) => index,
+        None => return
+    };
+
+    let vertex_count = lsystem.polygon_vertices(index).len();
+    let color_index = lsystem.polygon_color(index);
+    let color = lsystem.lsystem_params.color_palette.get(color_index).cloned();
+
+    let mut open = true;
+    let mut focus = false;
+
+    ImWindow::new(im_str!("Polygon Inspector"))
+            .opened(&mut open)
+            .always_auto_resize(true)
+            .position([(lsystem.width as f32) - 250.0, 200.0], Condition::FirstUseEver)
+            .build(&ui, || {
+                ui.text(format!("Polygon: {}", index));
+                ui.text(format!("Vertices: {}", vertex_count));
+                ui.text(format!("Color index: {}", color_index));
+
+                if let Some(color) = &color {
+                    ui.same_line(0.0);
+                    color_swatch(ui, color);
+                }
+
+                if ui.button(im_str!("Focus"), [0.0, 0.0]) {
+                    focus = true;
+                }
+            });
+
+    if focus {
+        lsystem.focus_selected_polygon();
+    }
+
+    if !open {
+        lsystem.select_polygon(None);
+    }
+}
+
+/// Show an overlay listing all symbols that are bound to either a turtle command or a bezier model,
+/// together with the color palette, if enabled in the debug options.
+pub fn do_symbol_legend(ui: &Ui, lsystem: &mut LSystemScene) {
+    if !lsystem.app_settings.show_symbol_legend {
+        return;
+    }
+
+    let params = &lsystem.lsystem_params;
+    let operations = draw_operations();
+
+    ImWindow::new(im_str!("Symbol Legend"))
+            .always_auto_resize(true)
+            .position([lsystem.width as f32 - 260.0, 60.0], layout_condition(lsystem))
+            .build(&ui, || {
+                for interp in &params.interpretations {
+                    if let Some(symbol) = interp.symbol {
+                        ui.text(format!("{}", symbol));
+                        ui.same_line(40.0);
+                        operation_hint_marker(ui, interp.operation as usize);
+                        ui.same_line(0.0);
+                        ui.text(operations[interp.operation as usize]);
+                    }
+                }
+
+                for (i, model) in params.bezier_models.iter().enumerate() {
+                    if let Some(symbol) = model.symbol {
+                        let id = ui.push_id(i as i32);
+
+                        ui.text(format!("{}", symbol));
+                        ui.same_line(40.0);
+                        ui.text(format!("Bezier model ({} patches)", model.patches.len()));
+
+                        for (j, patch) in model.patches.iter().enumerate() {
+                            let patch_id = ui.push_id(j as i32);
+                            ui.same_line(0.0);
+                            color_swatch(ui, &patch.color);
+                            patch_id.pop(ui);
+                        }
+
+                        id.pop(ui);
+                    }
+                }
+
+                if !params.color_palette.is_empty() {
+                    ui.separator();
+                    ui.text(im_str!("Palette"));
+
+                    for (i, color) in params.color_palette.iter().enumerate() {
+                        let id = ui.push_id(i as i32);
+                        ui.same_line(0.0);
+                        color_swatch(ui, color);
+                        if ui.is_item_hovered() {
+                            ui.tooltip_text(format!("Color {}", i));
+                        }
+                        id.pop(ui);
+                    }
+                }
+            });
+}
+
+/// Show the unsaved changes popup if the user requested an action that would discard them.
+pub fn do_unsaved_changes_popup(ui: &Ui, lsystem: &mut LSystemScene) {
+    if let Some(action) = lsystem.pending_discard.take() {
+        lsystem.confirming_discard = Some(action);
+        ui.open_popup(im_str!("Unsaved Changes"));
+    }
+
+    let result = show_popup(
+        ui,
+        im_str!("Unsaved Changes"),
+        im_str!("The current L-System has unsaved changes. Save them before continuing?"),
+        &[PopupButton::Yes, PopupButton::No, PopupButton::Cancel]
+    );
+
+    if let Some(button) = result {
+        if let Some(action) = lsystem.confirming_discard.take() {
+            match button {
+                PopupButton::Yes => {
+                    lsystem.save_file();
+
+                    // Saving might have been canceled by the user or might have failed
+                    if !lsystem.dirty {
+                        lsystem.execute_discarding_action(action);
+                    }
+                },
+                PopupButton::No => lsystem.execute_discarding_action(action),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Show the reload confirmation popup if the user requested reloading the file while there are unsaved changes.
+pub fn do_reload_popup(ui: &Ui, lsystem: &mut LSystemScene) {
+    if lsystem.pending_reload {
+        lsystem.pending_reload = false;
+        ui.open_popup(im_str!("Reload File"));
+    }
+
+    let result = show_popup(
+        ui,
+        im_str!("Reload File"),
+        im_str!("Reloading the L-System from disk discards all unsaved changes. Reload anyway?"),
+        &[PopupButton::Yes, PopupButton::No]
+    );
+
+    if let Some(PopupButton::Yes) = result {
+        lsystem.execute_reload();
+    }
+}
+
+/// Show the modal error popup if an error was reported by the scene.
+pub fn do_error_popup(ui: &Ui, lsystem: &mut LSystemScene) {
+    if let Some(message) = lsystem.pending_error.take() {
+        lsystem.error_message = ImString::new(message);
+        ui.open_popup(im_str!("Error"));
+    }
+
+    show_popup(ui, im_str!("Error"), &lsystem.error_message, &[PopupButton::Ok]);
+}
+
+fn do_gradient_popup(ui: &Ui, lsystem: &mut LSystemScene) {
+    ui.popup_modal(im_str!("Generate Gradient"))
+        .always_auto_resize(true)
+        .build(|| {
+            let generator = &mut lsystem.palette_generator;
+
+            let mut current_item: i32 = generator.mode as _;
+            let items = vec![im_str!("Linear"), im_str!("HSV Rainbow")];
+
+            if ui.combo(im_str!("Mode"), &mut current_item, &items, 2) {
+                generator.mode = match current_item {
+                    0 => GradientMode::Linear,
+                    _ => GradientMode::Rainbow
+                };
+            }
+
+            if let GradientMode::Linear = generator.mode {
+                let mut start: [f32; 3] = [generator.start_color.x, generator.start_color.y, generator.start_color.z];
+                if ColorEdit::new(im_str!("Start color"), &mut start).build(ui) {
+                    generator.start_color = Vec3::new(start[0], start[1], start[2]);
+                }
+
+                let mut end: [f32; 3] = [generator.end_color.x, generator.end_color.y, generator.end_color.z];
+                if ColorEdit::new(im_str!("End color"), &mut end).build(ui) {
+                    generator.end_color = Vec3::new(end[0], end[1], end[2]);
+                }
+            }
+
+            Slider::<u32>::new(im_str!("Count"), 1..=64).build(ui, &mut generator.count);
+
+            ui.checkbox(im_str!("Replace existing colors"), &mut generator.replace);
+            ui.same_line(0.0);
+            help_marker(ui, im_str!("If unchecked, the generated colors are appended to the current palette."));
+
+            ui.separator();
+
+            if ui.button(im_str!("Generate"), [80.0, 0.0]) {
+                let colors = generator.generate();
+
+                if generator.replace {
+                    lsystem.lsystem_params.color_palette = colors;
+                    lsystem.lsystem_params.color_opacities.clear();
+                } else {
+                    lsystem.lsystem_params.color_palette.extend(colors);
+                }
+
+                lsystem.refresh_color_palette();
+                ui.close_current_popup();
+            }
+
+            ui.same_line(0.0);
+
+            if ui.button(im_str!("Cancel"), [80.0, 0.0]) {
+                ui.close_current_popup();
+            }
+        });
+}
+
+pub fn do_main_menu_bar(ui: &Ui, lsystem: &mut LSystemScene) {
+    if let Some(token) = ui.begin_main_menu_bar() {
+        do_file_menu(ui, lsystem);
+        do_presets(ui, lsystem);
+        do_view_menu(ui, lsystem);
+        token.end(ui);
+    }
+}
+
+fn do_view_menu(ui: &Ui, lsystem: &mut LSystemScene) {
+    if let Some(token) = ui.begin_menu(im_str!("View"), true) {
+        MenuItem::new(im_str!("Parameters"))
+            .build_with_ref(ui, &mut lsystem.show_params_window);
+
+        MenuItem::new(im_str!("Cursor Coordinates"))
+            .build_with_ref(ui, &mut lsystem.app_settings.show_cursor_position);
+
+        if MenuItem::new(im_str!("Command Palette"))
+            .shortcut(im_str!("Ctrl+P"))
+            .build(ui) {
+                lsystem.open_command_palette = true;
+        }
+
+        if MenuItem::new(im_str!("Reset Window Layout")).build(ui) {
+            lsystem.reset_layout = true;
+            lsystem.show_params_window = true;
+        }
+
+        ui.separator();
+        ui.text_disabled(im_str!("Press M to hide all windows"));
+        ui.text_disabled(im_str!("Ctrl+Click a polygon to inspect it"));
+
+        token.end(ui);
+    }
+}
+
+fn do_presets(ui: &Ui, lsystem: &mut LSystemScene) {
+    if let Some(token) = ui.begin_menu(im_str!("Examples"), true) {
+        MenuItem::new(im_str!("2D"))
+            .enabled(false)
+            .build(ui);
+
+        if MenuItem::new(im_str!("Koch Snowflake")).build(ui) {
+            lsystem.perform_discarding_action(DiscardingAction::LoadPreset(data::presets::KOCH_SNOWFLAKE));
+        }
+
+        if MenuItem::new(im_str!("Penrose")).build(ui) {
+            lsystem.perform_discarding_action(DiscardingAction::LoadPreset(data::presets::PENROSE));
+        }
+
+
+        ui.separator();
+
+        MenuItem::new(im_str!("3D"))
+            .enabled(false)
+            .build(ui);
+
+        if MenuItem::new(im_str!("Hilbert Curve")).build(ui) {
+            lsystem.perform_discarding_action(DiscardingAction::LoadPreset(data::presets::HILBERT_3D));
+        }
+
+        if MenuItem::new(im_str!("Bush")).build(ui) {
+            lsystem.perform_discarding_action(DiscardingAction::LoadPreset(data::presets::BUSH_3D));
+        }
+
+        token.end(ui);
+    }
+}
+
+fn do_file_menu(ui: &Ui, lsystem: &mut LSystemScene) {
+    if let Some(token) = ui.begin_menu(im_str!("File"), true) {
+        if MenuItem::new(im_str!("New"))
+            .shortcut(im_str!("      Ctrl+N"))
+            .build(ui) {
+                lsystem.perform_discarding_action(DiscardingAction::New);
+        }
+
+        if MenuItem::new(im_str!("Open"))
+            .shortcut(im_str!("      Ctrl+O"))
+            .build(ui) {
+                lsystem.perform_discarding_action(DiscardingAction::Open);
+        }
+
+        if MenuItem::new(im_str!("Import text L-system..")).build(ui) {
+            lsystem.perform_discarding_action(DiscardingAction::ImportText);
+        }
+
+        if MenuItem::new(im_str!("Save"))
+            .shortcut(im_str!("      Ctrl+S"))
+            .build(ui) {
+                lsystem.save_file();
+        }
+
+        if MenuItem::new(im_str!("Save As.."))
+            .shortcut(im_str!("Ctrl+Shift+S"))
+            .build(ui) {
+                lsystem.save_file_as();
+        }
+
+        ui.separator();
+
+        if MenuItem::new(im_str!("New Tab")).build(ui) {
+            lsystem.new_document = Some(LSystemParameters::from_string(data::presets::EMPTY));
+        }
+
+        if MenuItem::new(im_str!("Duplicate into New Tab")).build(ui) {
+            lsystem.new_document = Some(lsystem.lsystem_params.clone());
+        }
+
+        if MenuItem::new(im_str!("Close Tab")).build(ui) {
+            lsystem.perform_discarding_action(DiscardingAction::CloseDocument);
+        }
+
+        ui.separator();
+
+        if MenuItem::new(im_str!("Reload from disk"))
+            .enabled(lsystem.file_path.is_some())
+            .build(ui) {
+                lsystem.reload_file();
+        }
+
+        ui.separator();
+
+        if MenuItem::new(im_str!("Save Screenshot..")).build(ui) {
+            lsystem.save_screenshot();
+        }
+
+        if MenuItem::new(im_str!("Export Turntable..")).build(ui) {
+            lsystem.show_turntable_dialog = true;
+        }
+
+        if MenuItem::new(im_str!("Export 2D Points (CSV)..")).build(ui) {
+            lsystem.export_csv();
+        }
+
+        if MenuItem::new(im_str!("Export OBJ..")).build(ui) {
+            lsystem.show_obj_dialog = true;
+        }
+
+        ui.separator();
+
+        if MenuItem::new(im_str!("Copy as Rust Code")).build(ui) {
+            ui.set_clipboard_text(&ImString::new(rust_snippet(&lsystem.save())));
+        }
+
+        token.end(ui);
+    }
+}
+
+/// Show the turntable export dialog, if it was requested via the file menu.
+pub fn do_turntable_dialog(ui: &Ui, lsystem: &mut LSystemScene) {
+    if !lsystem.show_turntable_dialog {
+        return;
+    }
+
+    let mut open = true;
+    let mut export = false;
+
+    ImWindow::new(im_str!("Export Turntable"))
+            .always_auto_resize(true)
+            .position([(lsystem.width as f32) / 2.0 - 150.0, (lsystem.height as f32) / 2.0 - 100.0], layout_condition(lsystem))
+            .opened(&mut open)
+            .build(&ui, || {
+                let settings = &mut lsystem.turntable_settings;
+
+                let mut frames = settings.frames as i32;
+                if ui.input_int(im_str!("Frames"), &mut frames).build() {
+                    settings.frames = frames.max(2).min(360) as _;
+                }
+                ui.same_line(0.0);
+                help_marker(ui, im_str!("The number of frames a full revolution of the camera is made of."));
+
+                let mut width = settings.width as i32;
+                if ui.input_int(im_str!("Width"), &mut width).build() {
+                    settings.width = width.max(16).min(4096) as _;
+                }
+
+                let mut height = settings.height as i32;
+                if ui.input_int(im_str!("Height"), &mut height).build() {
+                    settings.height = height.max(16).min(4096) as _;
+                }
+
+                let mut current_item: i32 = settings.format as _;
+                let items = vec![im_str!("Animated GIF"), im_str!("PNG sequence")];
+
+                if ui.combo(im_str!("Format"), &mut current_item, &items, 2) {
+                    settings.format = match current_item {
+                        0 => TurntableFormat::Gif,
+                        _ => TurntableFormat::PngSequence
+                    };
+                }
+                ui.same_line(0.0);
+                help_marker(ui, im_str!("A PNG sequence is written as one file per frame, with the frame number appended to the chosen file name."));
+
+                ui.spacing();
+
+                if ui.button(im_str!("Export"), [80.0, 0.0]) {
+                    export = true;
+                }
+            });
+
+    if export {
+        lsystem.export_turntable();
+    }
+
+    lsystem.show_turntable_dialog = open && !export;
+}
+
+/// Show the OBJ export dialog, if it was requested via the file menu.
+pub fn do_obj_dialog(ui: &Ui, lsystem: &mut LSystemScene) {
+    if !lsystem.show_obj_dialog {
+        return;
+    }
+
+    let mut open = true;
+    let mut export = false;
+
+    ImWindow::new(im_str!("Export OBJ"))
+            .always_auto_resize(true)
+            .position([(lsystem.width as f32) / 2.0 - 150.0, (lsystem.height as f32) / 2.0 - 50.0], layout_condition(lsystem))
+            .opened(&mut open)
+            .build(&ui, || {
+                ui.checkbox(im_str!("One file per color"), &mut lsystem.obj_split_by_color);
+                ui.same_line(0.0);
+                help_marker(ui, im_str!("Writes the geometry of each color palette entry to its own file, with the palette index appended\n\
+                                         to the chosen file name. This allows a material to be assigned per color in other tools."));
+
+                ui.spacing();
+
+                if ui.button(im_str!("Export"), [80.0, 0.0]) {
+                    export = true;
+                }
+            });
+
+    if export {
+        lsystem.export_obj();
+    }
+
+    lsystem.show_obj_dialog = open && !export;
+}
+
+fn do_explorer(ui: &Ui, lsystem: &mut LSystemScene) {
+    let max_depth = lsystem.app_settings.max_iteration_depth;
+    let explorer = &mut lsystem.explorer;
+
+    // Each range is edited as a pair of bounds. The bounds are kept ordered.
+    let range = |ui: &Ui, id: i32, label: &ImStr, range: &mut ParameterRange<f32>, min: f32, max: f32, speed: f32| {
+        let id = ui.push_id(id);
+        ui.checkbox(label, &mut range.enabled);
+        ui.same_line(150.0);
+
+        let mut bounds = [range.min, range.max];
+        if ui.drag_float2(im_str!("##range"), &mut bounds)
+            .min(min)
+            .max(max)
+            .display_format(im_str!("%.3f"))
+            .speed(speed)
+            .build() {
+            range.min = bounds[0].min(bounds[1]);
+            range.max = bounds[0].max(bounds[1]);
+        }
+        id.pop(ui);
+    };
+
+    range(ui, 0, im_str!("Angle delta"), &mut explorer.angle_delta, 0.0, 360.0, 0.5);
+    range(ui, 1, im_str!("Step"), &mut explorer.step, 0.0, 10.0, 0.005);
+
+    ui.checkbox(im_str!("Iterations"), &mut explorer.iteration_depth.enabled);
+    if explorer.iteration_depth.enabled {
+        ui.indent();
+        Slider::<u32>::new(im_str!("Min##iterations"), 0..=max_depth).build(ui, &mut explorer.iteration_depth.min);
+        Slider::<u32>::new(im_str!("Max##iterations"), 0..=max_depth).build(ui, &mut explorer.iteration_depth.max);
+        explorer.iteration_depth.max = explorer.iteration_depth.max.max(explorer.iteration_depth.min);
+        ui.unindent();
+    }
+
+    ui.checkbox(im_str!("Seed"), &mut explorer.randomize_seed);
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("Picks a new seed for each variant, which only has an effect on stochastic rules.\n\
+                             The angle delta range is given in degrees."));
+
+    ui.spacing();
+
+    let mut variant = None;
+
+    if ui.button(im_str!("Previous"), [0.0, 0.0]) {
+        variant = explorer.previous();
+    }
+
+    ui.same_line(0.0);
+
+    if ui.button(im_str!("Randomize"), [0.0, 0.0]) {
+        variant = Some(explorer.generate(&lsystem.lsystem_params));
+    }
+
+    ui.same_line(0.0);
+
+    if ui.button(im_str!("Next"), [0.0, 0.0]) {
+        variant = explorer.next_variant();
+    }
+
+    let (position, count) = explorer.position();
+    if count > 0 {
+        ui.same_line(0.0);
+        ui.text(format!("{}/{}", position + 1, count));
+    }
+
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("\"Randomize\" generates a variant of the current L-System with the enabled parameters picked randomly\n\
+                             within their ranges. \"Previous\" and \"Next\" step through all generated variants.\n\
+                             The first entry is the L-System the exploration started from."));
+
+    if let Some(variant) = variant {
+        lsystem.show_variant(variant);
+    }
+}
+
+fn do_statistics(ui: &Ui, lsystem: &mut LSystemScene) {
+    /// How many symbols of the expansion are shown in the preview
+    const PREVIEW_LENGTH: usize = 256;
+
+    let (segments, polygons, patches) = lsystem.drawing_statistics();
+
+    ui.text(format!("Symbols: {}", lsystem.expanded_length()));
+    ui.text(format!("Line segments: {}", segments));
+    ui.text(format!("Polygons: {}", polygons));
+    ui.text(format!("Bezier patches: {}", patches));
+
+    ui.spacing();
+
+    let (mut preview, truncated) = lsystem.expanded_string_preview(PREVIEW_LENGTH);
+
+    if truncated {
+        preview.push_str("..");
+    }
+
+    ui.text_wrapped(&ImString::new(preview));
+
+    if ui.button(im_str!("Copy expansion"), [0.0, 0.0]) {
+        ui.set_clipboard_text(&ImString::new(lsystem.expanded_string()));
+    }
+
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("Copies the full expanded L-System string to the clipboard. The preview above is\n\
+                             truncated for long expansions."));
+}
+
+fn do_debug_options(ui: &Ui, lsystem: &mut LSystemScene) {
+    if ui.checkbox(im_str!("Show normal vectors"), &mut lsystem.app_settings.show_normals) {
+        lsystem.refresh_meshes();
+    }
+
+    if lsystem.app_settings.show_normals {
+        ui.indent();
+
+        if ui.drag_float(im_str!("Normal length"), &mut lsystem.app_settings.normal_debug_length)
+            .min(0.001)
+            .max(100.0)
+            .display_format(im_str!("%.3lf"))
+            .speed(0.01)
+            .build() {
+            lsystem.refresh_normal_length();
+        }
+
+        ui.unindent();
+    }
+
+    if ui.checkbox(im_str!("Render nodes as points"), &mut lsystem.app_settings.show_node_points) {
+        lsystem.refresh_meshes();
+    }
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("Draws a point at the begin and end of every line segment, which helps to inspect the positions the turtle moved through."));
+
+    if ui.checkbox(im_str!("Show turtle frames"), &mut lsystem.app_settings.show_turtle_frames) {
+        lsystem.refresh_meshes();
+    }
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("Draws the coordinate frame of the turtle at every save and restore command, which helps to debug branching.\n\
+                             The heading is shown in red, the left direction in green and the up direction in blue.\n\
+                             The frames are computed by replaying the turtle commands, which only takes movement and rotation into account."));
+
+    ui.checkbox(im_str!("Show bounding sphere"), &mut lsystem.app_settings.show_bounding_sphere);
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("Draws the sphere enclosing the bounding box in the bounding box color. When centering the camera with\n\
+                             auto adjusting of the radius enabled, the camera distance is set to the radius of this sphere."));
+
+    if ui.checkbox(im_str!("Two-sided debug coloring"), &mut lsystem.app_settings.two_sided_debug) {
+        lsystem.refresh_meshes();
+    }
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("Draws the front faces of polygons in their palette color and the back faces in magenta, without lighting.\n\
+                             This makes polygons with inconsistent winding order or flipped normals easy to spot."));
+
+    if ui.checkbox(im_str!("Indexed line mesh"), &mut lsystem.app_settings.indexed_lines) {
+        lsystem.refresh_meshes();
+    }
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("Line segments sharing an end point of the same color use a single vertex. Only affects the basic line draw mode."));
+
+    ui.checkbox(im_str!("Show symbol legend"), &mut lsystem.app_settings.show_symbol_legend);
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("Shows an overlay listing all symbols that are bound to a turtle command or bezier model."));
+
+    ui.checkbox(im_str!("Show frame times"), &mut lsystem.app_settings.show_frame_times);
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("Shows a graph of the durations of the most recent frames, which helps to find operations causing hitches."));
+
+    if Slider::<f32>::new(im_str!("Surface depth offset"), 0.0..=10.0).build(ui, &mut lsystem.app_settings.polygon_offset) {
+        lsystem.refresh_polygon_offset();
+    }
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("Pushes filled polygons slightly away from the camera, so that lines lying on them, like the bounding box\n\
+                             or the wireframe overlay, do not flicker. A value of zero disables the offset."));
+
+    let mut current_item: i32 = lsystem.app_settings.wireframe_mode as _;
+    let items = vec![im_str!("Off"), im_str!("Wireframe only"), im_str!("Overlay")];
+
+    if ui.combo(im_str!("Wireframe"), &mut current_item, &items, 3) {
+        lsystem.app_settings.wireframe_mode = match current_item {
+            0 => WireframeMode::Off,
+            1 => WireframeMode::Only,
+            _ => WireframeMode::Overlay
+        };
+
+        lsystem.refresh_wireframe_flag();
+    }
+
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("Controls how polygons are drawn:\n\
+                             \tOff: Only the filled polygons are drawn.\n\
+                             \tWireframe only: Only the polygon edges are drawn.\n\
+                             \tOverlay: The polygon edges are drawn on top of the filled polygons."));
+}
+
+/// Show a text input field for given buffer. Returns whether the contents were changed this frame and
+/// whether the changes should be applied. If application is deferred, the latter is only the case once
+/// the user presses Enter or the field loses focus after being edited.
+fn deferred_input_text(ui: &Ui, label: &ImStr, buffer: &mut ImString, defer: bool) -> (bool, bool) {
+    let original = buffer.to_str().to_string();
+
+    let confirmed = ui.input_text(label, buffer)
+        .enter_returns_true(defer)
+        .build();
+
+    let changed = buffer.to_str() != original;
+
+    if defer {
+        let finished = unsafe { imgui::sys::igIsItemDeactivatedAfterEdit() };
+        (changed, confirmed || finished)
+    } else {
+        (changed, changed)
+    }
+}
+
+fn do_interpretations(ui: &Ui, lsystem: &mut LSystemScene) {
+    let mut modified = false;
+    let defer = lsystem.app_settings.apply_text_on_enter;
+
+    let params = &mut lsystem.lsystem_params;
+
+    let mut to_delete: Option<usize> = None;
+    let mut to_duplicate: Option<usize> = None;
+
+    // We need to push an outer ID here since we are using buttons with the same identifiers as the ones
+    // used to remove and add rules.
+    let outer_id = ui.push_id(2);
+
+    for (i, interp) in params.interpretations.iter_mut().enumerate() {
+        let id = ui.push_id(i as i32);
+
+        let mut symbol_str = ImString::with_capacity(16);
+
+        if let Some(symbol) = interp.symbol {
+            symbol_str.push_str(&symbol.to_string());
+        }
+
+        let token = ui.push_item_width(20.0);
+
+        let (changed, apply) = deferred_input_text(ui, im_str!("##sym"), &mut symbol_str, defer);
+
+        if changed {
+            let trimmed = symbol_str.to_str().trim();
+            if trimmed.is_empty() {
+                interp.symbol = None;
+            } else {
+                interp.symbol = Some(trimmed.chars().next().unwrap());
+            }
+        }
+
+        if apply {
+            modified = true;
+        }
+
+        token.pop(ui);
+
+        ui.same_line(0.0);
+        ui.text(im_str!("->"));
+        ui.same_line(0.0);
+        let mut current_item: i32 = interp.operation as _;
+        let items = draw_operations();
+
+        if ui.combo(im_str!("##op"), &mut current_item, &items, 8) {
+            let new_operation = index_to_operation(current_item as _);
+            interp.operation = new_operation;
+            modified = true;
+        }
+
+        ui.same_line(0.0);
+        operation_hint_marker(ui, current_item as _);
+
+        ui.same_line(0.0);
+
+        if ui.button(im_str!("Duplicate"), [0.0, 0.0]) {
+            to_duplicate = Some(i);
+        }
+
+        let colors = ui.push_style_colors(&[
+            (StyleColor::Button, [0.6, 0.239, 0.239, 1.0]),
+            (StyleColor::ButtonHovered, [0.7, 0.2117, 0.2117, 1.0]),
+            (StyleColor::ButtonActive, [0.8, 0.1607, 0.1607, 1.0])
+        ]);
+
+        ui.same_line(0.0);
+
+        if ui.button(im_str!("-"), [0.0, 0.0]) {
+            println!("To delete: {}", i);
+            modified = true;
+            to_delete = Some(i);
+        }
+        
+        colors.pop(ui);
+
+        id.pop(ui);     
+    }  
+
+    match to_delete {
+        Some(i) => {
+            params.interpretations.remove(i);
+        }
+        _ => {}
+    };
+
+    // The duplicate is inserted right below the original and starts out without a symbol, since
+    // the user will want to assign a different one anyway.
+    if let Some(i) = to_duplicate {
+        let mut copy = params.interpretations[i].clone();
+        copy.symbol = None;
+        params.interpretations.insert(i + 1, copy);
+        modified = true;
+    }
+
+
+    let colors = ui.push_style_colors(&[
+        (StyleColor::Button, [0.349, 0.6, 0.239, 1.0]),
+        (StyleColor::ButtonHovered, [0.3568, 0.7019, 0.2117, 1.0]),
+        (StyleColor::ButtonActive, [0.3529, 0.8, 0.1607, 1.0])
+    ]);
+
+    if ui.button(im_str!("+"), [0.0, 0.0]) {
+        params.interpretations.push(
+            Interpretation{
+                symbol: None,
+                operation: TurtleCommand::Forward
+            }
+        );
+
+        println!("Added new interp, size is now {}", params.interpretations.len());
+        modified = true;
+    }
+
+    colors.pop(ui);
+
+    ui.same_line(0.0);
+
+    if ui.button(im_str!("Remap Symbol.."), [0.0, 0.0]) {
+        ui.open_popup(im_str!("Remap Symbol"));
+    }
+
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("Renames a symbol everywhere it appears: in the axiom, the rules, the interpretations and the bezier models."));
+
+    if ui.button(im_str!("Add standard interpretations"), [0.0, 0.0]) {
+        if params.add_standard_interpretations() > 0 {
+            modified = true;
+        }
+    }
+
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("Interprets all symbols used in the axiom or rules that are not interpreted yet, but have a conventional meaning:\n\
+                             \tF, G: Forward\n\
+                             \tf: Forward without drawing\n\
+                             \t+, -: Turn left and right\n\
+                             \t|: Turn around\n\
+                             \t&, ^: Pitch down and up\n\
+                             \t\\, /: Roll left and right\n\
+                             \t[, ]: Save and restore the turtle state\n\
+                             \t{, }, .: Begin and end a polygon, submit a polygon vertex\n\
+                             \t!: Decrease the line width\n\
+                             \t': Increment the color index"));
+
+    do_remap_popup(ui, lsystem);
+
+    outer_id.pop(ui);
+
+    if modified {
+        lsystem.refresh_interpretations();
+    }
+}
+
+fn do_remap_popup(ui: &Ui, lsystem: &mut LSystemScene) {
+    ui.popup_modal(im_str!("Remap Symbol"))
+        .always_auto_resize(true)
+        .build(|| {
+            let token = ui.push_item_width(20.0);
+            ui.input_text(im_str!("From"), &mut lsystem.remap_from).build();
+            ui.same_line(0.0);
+            ui.input_text(im_str!("To"), &mut lsystem.remap_to).build();
+            token.pop(ui);
+
+            let from = lsystem.remap_from.to_str().trim().chars().next();
+            let to = lsystem.remap_to.to_str().trim().chars().next();
+
+            ui.spacing();
+
+            if ui.button(im_str!("Remap"), [80.0, 0.0]) {
+                if let (Some(from), Some(to)) = (from, to) {
+                    lsystem.rename_symbol(from, to);
+                }
+
+                ui.close_current_popup();
+            }
+
+            ui.same_line(0.0);
+
+            if ui.button(im_str!("Cancel"), [80.0, 0.0]) {
+                ui.close_current_popup();
+            }
+        });
+}
+
+/// Show a red marker after the last item if there are problems with the rule of given index, or the
+/// axiom if the index is None. Hovering the marker lists the problems.
+fn rule_problem_marker(ui: &Ui, problems: &[RuleProblem], rule: Option<usize>) {
+    let messages: Vec<&str> = problems.iter()
+        .filter(|p| p.rule == rule)
+        .map(|p| p.message.as_str())
+        .collect();
+
+    if messages.is_empty() {
+        return;
+    }
+
+    ui.same_line(0.0);
+    ui.text_colored([0.9, 0.2, 0.2, 1.0], im_str!("(!)"));
+
+    if ui.is_item_hovered() {
+        ui.tooltip_text(messages.join("\n"));
+    }
+}
+
+/// Color used to highlight undefined symbols in the axiom and production rules
+const UNDEFINED_SYMBOL_COLOR: [f32; 4] = [1.0, 0.6, 0.0, 1.0];
+
+/// Show given axiom or rule text with all symbols that are not contained in given set of known symbols
+/// highlighted. Nothing is shown if there are no such symbols. Returns whether any symbol was highlighted.
+fn highlight_undefined_symbols(ui: &Ui, text: &str, known: &HashSet<char>) -> bool {
+    // The predecessor and arrow of a rule are not checked, since the predecessor is always known
+    let (head, body) = match text.find("->") {
+        Some(pos) => (&text[..pos + 2], &text[pos + 2..]),
+        None => ("", text)
+    };
+
+    let is_undefined = |c: &char| !c.is_whitespace() && !known.contains(c);
+
+    if !body.chars().any(|c| is_undefined(&c)) {
+        return false;
+    }
+
+    // Split the text into runs of characters that are either all undefined or all known
+    let mut runs = vec![(false, head.to_string())];
+
+    for c in body.chars() {
+        let undefined = is_undefined(&c);
+
+        match runs.last_mut() {
+            Some((run_undefined, run)) if *run_undefined == undefined => run.push(c),
+            _ => runs.push((undefined, c.to_string()))
+        }
+    }
+
+    ui.indent();
+
+    for (i, (undefined, run)) in runs.iter().filter(|(_, run)| !run.is_empty()).enumerate() {
+        if i > 0 {
+            ui.same_line_with_spacing(0.0, 0.0);
+        }
+
+        if *undefined {
+            ui.text_colored(UNDEFINED_SYMBOL_COLOR, run);
+        } else {
+            ui.text_disabled(run);
+        }
+    }
+
+    ui.unindent();
+
+    true
+}
+
+fn do_rules(ui: &Ui, lsystem: &mut LSystemScene) {
+    let mut modified = false;
+    let defer = lsystem.app_settings.apply_text_on_enter;
+    let problems = lsystem.lsystem_params.validate_rules();
+    let known = lsystem.lsystem_params.known_symbols();
+    let params = &mut lsystem.lsystem_params;
+
+    // Whether any undefined symbol was highlighted, in which case the legend is shown
+    let mut any_undefined = false;
+
+    let mut axiom = ImString::with_capacity(256);
+    axiom.push_str(&params.axiom);
+
+    let (changed, apply) = deferred_input_text(ui, im_str!("Axiom"), &mut axiom, defer);
+
+    if changed {
+        params.axiom = axiom.to_str().to_string();
+    }
+
+    if apply {
+        modified = true;
+    }
+
+    rule_problem_marker(ui, &problems, None);
+    any_undefined |= highlight_undefined_symbols(ui, &params.axiom, &known);
+
+    ui.text(im_str!("Production rules:"));
+    ui.indent();
+
+    // The rule to delete. It can only ever be one per frame, so this is enough.
+    let mut to_delete = None;
+
+    for (i, rule) in params.rules.iter_mut().enumerate() {
+        let mut rule_str = ImString::with_capacity(256);
+        rule_str.push_str(rule);
+
+        let id = ui.push_id(i as i32);
+
+        let (changed, apply) = deferred_input_text(ui, im_str!("##rule"), &mut rule_str, defer);
+
+        if changed {
+            *rule = rule_str.to_str().to_string();
+        }
+
+        if apply {
+            modified = true;
+        }
 
         let colors = ui.push_style_colors(&[
             (StyleColor::Button, [0.6, 0.239, 0.239, 1.0]),
@@ -537,6 +1745,10 @@ fn do_rules(ui: &Ui, lsystem: &mut LSystemScene) {
         }
 
         colors.pop(ui);
+
+        rule_problem_marker(ui, &problems, Some(i));
+        any_undefined |= highlight_undefined_symbols(ui, rule, &known);
+
         id.pop(ui);
     }
 
@@ -553,10 +1765,17 @@ fn do_rules(ui: &Ui, lsystem: &mut LSystemScene) {
 
     colors.pop(ui);
 
-
-
     ui.unindent();
 
+    if any_undefined {
+        ui.text_colored(UNDEFINED_SYMBOL_COLOR, im_str!("X"));
+        ui.same_line(0.0);
+        ui.text_disabled(im_str!("Undefined symbol"));
+        ui.same_line(0.0);
+        help_marker(ui, im_str!("These symbols have no interpretation, are not bound to a bezier model and are not replaced\n\
+                                 by any production rule. They are treated as constants and do nothing, which often hints at a typo."));
+    }
+
     // Handle deletion request
     match to_delete {
         Some(i) => {
@@ -586,6 +1805,9 @@ fn do_drawing_parameters(ui: &Ui, lsystem: &mut LSystemScene) {
                 modified = true;
         }
 
+        ui.same_line(0.0);
+        help_marker(ui, im_str!("The position the turtle starts drawing at, in world units. The turtle always starts in the XY plane."));
+
         let mut start_angle: f32 = params.start_angle.to_degrees() as _;
         if ui.drag_float(im_str!("Starting angle"), &mut start_angle)
             .min(0.0)
@@ -597,6 +1819,10 @@ fn do_drawing_parameters(ui: &Ui, lsystem: &mut LSystemScene) {
                 modified = true;
         }
 
+        ui.same_line(0.0);
+        help_marker(ui, im_str!("The direction the turtle initially faces, in degrees, measured counterclockwise from the positive X axis.\n\
+                                 Shown in degrees, but stored in radians in saved files."));
+
         let mut delta_angle: f32 = params.angle_delta.to_degrees() as _;
         if ui.drag_float(im_str!("Angle delta"), &mut delta_angle)
             .min(0.0)
@@ -608,6 +1834,10 @@ fn do_drawing_parameters(ui: &Ui, lsystem: &mut LSystemScene) {
                 modified = true;
         }
 
+        ui.same_line(0.0);
+        help_marker(ui, im_str!("The angle every turn, pitch and roll command rotates the turtle by, in degrees. For example, 90 draws\n\
+                                 right angles and 60 hexagonal patterns. Shown in degrees, but stored in radians in saved files."));
+
         let mut step: f32 = params.step as _;
         if ui.drag_float(im_str!("Step"), &mut step)
             .min(0.0)
@@ -619,6 +1849,9 @@ fn do_drawing_parameters(ui: &Ui, lsystem: &mut LSystemScene) {
                 modified = true;
         }
 
+        ui.same_line(0.0);
+        help_marker(ui, im_str!("The distance the turtle moves with each forward command, in world units."));
+
         let mut line_width: f32 = params.initial_line_width as _;
         if ui.drag_float(im_str!("Line Width"), &mut line_width)
             .min(0.0)
@@ -630,6 +1863,9 @@ fn do_drawing_parameters(ui: &Ui, lsystem: &mut LSystemScene) {
                 modified = true;
         }
 
+        ui.same_line(0.0);
+        help_marker(ui, im_str!("The width of lines at the start of drawing. Not supported by legacy lines."));
+
         let mut line_delta: f32 = params.line_width_delta as _;
         if ui.drag_float(im_str!("Line Width Delta"), &mut line_delta)
             .min(0.0)
@@ -641,6 +1877,10 @@ fn do_drawing_parameters(ui: &Ui, lsystem: &mut LSystemScene) {
                 modified = true;
         }
 
+        ui.same_line(0.0);
+        help_marker(ui, im_str!("The amount the line width changes by with each line width command, and with each contracting forward\n\
+                                 command. The width never drops below zero."));
+
         if modified {
             lsystem.refresh_drawing_parameters();
         }
@@ -648,59 +1888,259 @@ fn do_drawing_parameters(ui: &Ui, lsystem: &mut LSystemScene) {
 
     // Technically, the iteration depth is not path of the drawing parameters, but it is displayed in the same section. 
     {
-        if Slider::<u32>::new(im_str!("Iterations"), 0..=13).build(ui, &mut lsystem.lsystem_params.iteration_depth) {
+        let max_depth = lsystem.app_settings.max_iteration_depth;
+        if Slider::<u32>::new(im_str!("Iterations"), 0..=max_depth).build(ui, &mut lsystem.lsystem_params.iteration_depth) {
             lsystem.refresh_iteration_depth();
         }
 
+        ui.same_line(0.0);
+        help_marker(ui, im_str!("How many times the rules are applied to the axiom. The expansion usually grows exponentially with this,\n\
+                                 so high values can take a long time."));
+
+        let estimate = lsystem.lsystem_params.estimate_expansion_length(lsystem.lsystem_params.iteration_depth);
+        let text = ImString::new(format!("Estimated expansion: ~{} symbols", estimate));
+
+        if estimate > lsystem.app_settings.max_expansion_length as u64 {
+            ui.text_colored([1.0, 0.4, 0.4, 1.0], &text);
+        } else {
+            ui.text_disabled(&text);
+        }
+
+        ui.same_line(0.0);
+        help_marker(ui, im_str!("A rough estimate of the expansion length at the selected iteration depth, based on symbol counts only.\n\
+                                 If it exceeds the expansion limit set in the application settings, the iteration depth is reduced."));
+
+        let mut window_enabled = lsystem.generation_window.is_some();
+        if ui.checkbox(im_str!("Generation window"), &mut window_enabled) {
+            lsystem.generation_window = if window_enabled {
+                Some((0, lsystem.lsystem_params.iteration_depth))
+            } else {
+                None
+            };
+
+            lsystem.refresh_meshes();
+        }
+
+        ui.same_line(0.0);
+        help_marker(ui, im_str!("Only shows the line segments drawn by symbols produced in the selected range of iterations,\n\
+                                 with the axiom being generation 0. lsystems-core does not track generations, so they are\n\
+                                 determined by replaying simple rules of the form A -> ..., which fails for example for\n\
+                                 stochastic rules. Polygons and bezier models are always shown."));
+
+        if let Some((min, max)) = lsystem.generation_window {
+            ui.indent();
+
+            if lsystem.segment_generations.as_ref().map_or(false, |g| !g.replayed()) {
+                ui.text_disabled(im_str!("Not available: the rules could not be replayed"));
+            } else {
+                let depth = lsystem.lsystem_params.iteration_depth;
+                let mut min = min.min(depth);
+                let mut max = max.min(depth);
+
+                let min_changed = Slider::<u32>::new(im_str!("Lowest generation"), 0..=depth).build(ui, &mut min);
+                let max_changed = Slider::<u32>::new(im_str!("Highest generation"), 0..=depth).build(ui, &mut max);
+
+                if min_changed || max_changed {
+                    // The other end of the range is pushed along, so it never becomes empty
+                    if min_changed {
+                        max = max.max(min);
+                    } else {
+                        min = min.min(max);
+                    }
+
+                    lsystem.generation_window = Some((min, max));
+                    lsystem.refresh_meshes();
+                }
+            }
+
+            ui.unindent();
+        }
+
         let mut current_item: i32 = lsystem.lsystem_params.line_draw_mode as _;
-        let items = vec![im_str!("Legacy Lines"), im_str!("2D Lines"), im_str!("3D Lines")];
+        let items = vec![im_str!("Legacy Lines"), im_str!("2D Lines"), im_str!("3D Lines"), im_str!("Billboard Lines")];
 
-        if ui.combo(im_str!("Line Mode"), &mut current_item, &items, 3) {
+        if ui.combo(im_str!("Line Mode"), &mut current_item, &items, 4) {
             let new_mode = match current_item {
                 0 => LineDrawMode::Basic,
                 1 => LineDrawMode::Advanced2D,
-                _ => LineDrawMode::Advanced3D
+                2 => LineDrawMode::Advanced3D,
+                _ => LineDrawMode::Billboard
             };
 
             lsystem.lsystem_params.line_draw_mode = new_mode;
+            lsystem.mark_dirty();
             lsystem.draw_lsystem();
         }
 
         ui.same_line(0.0);
-        help_marker(ui, im_str!("Three approaches to rendering lines are supported:\n\
+        help_marker(ui, im_str!("Four approaches to rendering lines are supported:\n\
                                  \tLegacy: Renders lines using built-in OpenGL functionality. Does not support custom widths.\n\
                                  \t2D: Uses a custom geometry shader to render lines as triangle strips. Supports arbitrary widths.\n\
-                                 \t3D: Renders lines as 3D tubes. Useful for more realistic looking models, like plants."));
+                                 \t3D: Renders lines as 3D tubes. Useful for more realistic looking models, like plants.\n\
+                                 \tBillboard: Renders lines as flat quads facing the camera. Their width is given in world units,\n\
+                                 \t           so it shrinks with distance. Useful for a sketched look."));
+
+        if let LineDrawMode::Advanced2D = lsystem.lsystem_params.line_draw_mode {
+            let mut current_item: i32 = lsystem.app_settings.line_join_style as _;
+            let items = vec![im_str!("None"), im_str!("Miter"), im_str!("Round")];
+
+            ui.indent();
+            if ui.combo(im_str!("Line joins"), &mut current_item, &items, 3) {
+                lsystem.app_settings.line_join_style = match current_item {
+                    0 => LineJoinStyle::None,
+                    1 => LineJoinStyle::Miter,
+                    _ => LineJoinStyle::Round
+                };
+
+                lsystem.refresh_meshes();
+            }
+
+            ui.same_line(0.0);
+            help_marker(ui, im_str!("How consecutive line segments are connected. Without joins, gaps can appear at sharp corners."));
+            ui.unindent();
+        }
+
+        if let LineDrawMode::Advanced3D = lsystem.lsystem_params.line_draw_mode {
+            ui.indent();
+            if ui.drag_float(im_str!("Tube radius scale"), &mut lsystem.app_settings.tube_radius_scale)
+                .min(0.0001)
+                .max(0.1)
+                .speed(0.0001)
+                .display_format(im_str!("%.4f"))
+                .build() {
+                lsystem.refresh_meshes();
+            }
+
+            ui.same_line(0.0);
+            help_marker(ui, im_str!("The radius of the 3D tubes is the line width multiplied by this factor."));
+            ui.unindent();
+        }
+
+        if let LineDrawMode::Billboard = lsystem.lsystem_params.line_draw_mode {
+            ui.indent();
+            if ui.drag_float(im_str!("Billboard width scale"), &mut lsystem.app_settings.billboard_width_scale)
+                .min(0.0001)
+                .max(0.1)
+                .speed(0.0001)
+                .display_format(im_str!("%.4f"))
+                .build() {
+                lsystem.refresh_meshes();
+            }
+
+            ui.same_line(0.0);
+            help_marker(ui, im_str!("The width of the billboard quads is the line width multiplied by this factor."));
+            ui.unindent();
+        }
+
+        let mut current_item: i32 = lsystem.lsystem_params.polygon_shading as _;
+        let items = vec![im_str!("Smooth"), im_str!("Flat")];
+
+        if ui.combo(im_str!("Polygon Shading"), &mut current_item, &items, 2) {
+            lsystem.lsystem_params.polygon_shading = match current_item {
+                0 => PolygonShading::Smooth,
+                _ => PolygonShading::Flat
+            };
+
+            lsystem.mark_dirty();
+            lsystem.refresh_meshes();
+        }
+
+        ui.same_line(0.0);
+        help_marker(ui, im_str!("Smooth: Normals are averaged between adjacent triangles, giving polygons a rounded look.\n\
+                                 Flat: Each triangle is shaded using its own normal. Suited for faceted, geometric shapes."));
     }
 }
 
-pub fn do_debug_gui(ui: &Ui) {
+/// Show the debug window, containing the current framerate and a graph of the most recent frame times.
+pub fn do_debug_gui(ui: &Ui, lsystem: &mut LSystemScene) {
+    let frame_times = lsystem.frame_times.values();
+    let statistics = lsystem.frame_times.statistics();
+
     ImWindow::new(im_str!("Debug"))
-            .size([85.0, 55.0], Condition::Always)
-            .position([0.0, 0.0], Condition::Always)
+            .size([280.0, 150.0], Condition::FirstUseEver)
+            .position([(lsystem.width as f32) - 290.0, 60.0], Condition::FirstUseEver)
             .build(&ui, || {
                 let fps = ui.io().framerate;
                 ui.text(format!(
                     "FPS: {:.1}",
                     fps
                 ));
-                
+
+                if let Some((min, avg, max)) = statistics {
+                    ui.text(format!("Min: {:.2} ms  Avg: {:.2} ms  Max: {:.2} ms", min, avg, max));
+                }
+
+                // The scale is fixed to at least 1/30 s, so that short frames do not look like hitches
+                ui.plot_lines(im_str!("##frame_times"), &frame_times)
+                    .graph_size([0.0, 60.0])
+                    .scale_min(0.0)
+                    .scale_max(statistics.map_or(0.0, |(_, _, max)| max).max(1000.0 / 30.0))
+                    .overlay_text(im_str!("Frame time (ms)"))
+                    .build();
             });
 }
 
 fn do_app_settings(ui: &Ui, lsystem: &mut LSystemScene) {
-    ui.checkbox(im_str!("Auto refresh"), &mut lsystem.app_settings.auto_refresh);
+    if ui.checkbox(im_str!("Auto refresh"), &mut lsystem.app_settings.auto_refresh) {
+        // Changes made while auto refresh was disabled should not be lost
+        if lsystem.app_settings.auto_refresh {
+            lsystem.apply_pending_changes();
+        }
+    }
 
     if !lsystem.app_settings.auto_refresh {
         ui.same_line_with_spacing(0.0, 30.0);
+        if ui.button(im_str!("Apply"), [0.0, 0.0]) {
+            lsystem.apply_pending_changes();
+        }
+
+        ui.same_line(0.0);
         if ui.button(im_str!("Reload"), [0.0, 0.0]) {
             lsystem.force_refresh_all();
         }
+
+        ui.same_line(0.0);
+        help_marker(ui, im_str!("\"Apply\" only redoes the work required by the parameters that were changed since the last refresh,\n\
+                                 while \"Reload\" rebuilds everything from scratch."));
+
+        if lsystem.has_pending_changes() {
+            ui.text_colored([1.0, 0.8, 0.0, 1.0], im_str!("There are unapplied changes."));
+        }
     } else {
         ui.same_line(0.0);
-        help_marker(ui, im_str!("Auto refresh can make editing large L-Systems rather slow. Consider disabling the option when dealing with big systems."))
+        help_marker(ui, im_str!("Auto refresh can make editing large L-Systems rather slow. Consider disabling the option when dealing with big systems.\n\
+                                 Press Ctrl+Shift+R to toggle it quickly."))
+    }
+
+    ui.checkbox(im_str!("Apply text edits on Enter"), &mut lsystem.app_settings.apply_text_on_enter);
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("Only applies changes to the axiom, the rules and the interpretation symbols once Enter is pressed\n\
+                             or the text field loses focus, instead of after every keystroke. This greatly improves typing\n\
+                             latency when editing big L-Systems."));
+
+    Slider::<u32>::new(im_str!("Maximum iterations"), 1..=30).build(ui, &mut lsystem.app_settings.max_iteration_depth);
+
+    if lsystem.app_settings.max_iteration_depth > 13 {
+        ui.indent();
+        ui.text_colored([1.0, 0.8, 0.0, 1.0], im_str!("High iteration depths can take a long time and a lot of memory."));
+        ui.unindent();
     }
 
+    Slider::<u32>::new(im_str!("Expansion limit"), 100_000..=500_000_000).build(ui, &mut lsystem.app_settings.max_expansion_length);
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("The maximum estimated number of symbols an expansion may have. If the selected iteration depth\n\
+                             would exceed it, the depth is reduced to avoid running out of memory."));
+
+    Slider::<u32>::new(im_str!("Line segment limit"), 100_000..=200_000_000).build(ui, &mut lsystem.app_settings.max_line_segments);
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("The maximum number of line segments a line mesh is built for. If an L-System has more segments,\n\
+                             the previous line mesh is kept and a warning is shown instead."));
+
+    ui.checkbox(im_str!("Reload file on external changes"), &mut lsystem.app_settings.auto_reload_file);
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("Checks the file the L-System was loaded from once per second, and reloads it if it was modified\n\
+                             by another program. If there are unsaved changes, you will be asked first."));
+
     ui.spacing();
 
     ui.checkbox(im_str!("Center camera on reload"), &mut lsystem.app_settings.auto_center_camera);
@@ -715,9 +2155,253 @@ fn do_app_settings(ui: &Ui, lsystem: &mut LSystemScene) {
         ui.indent();
         ui.checkbox(im_str!("Also adjust camera zoom"), &mut lsystem.app_settings.auto_adjust_radius);ui.same_line(0.0);
         help_marker(ui, im_str!("This will adjust the zoom level to always have the whole L-System in view."));    
+
+        if lsystem.app_settings.auto_adjust_radius {
+            ui.indent();
+            if ui.checkbox(im_str!("Also adjust clip planes"), &mut lsystem.app_settings.auto_clip_planes) {
+                lsystem.refresh_clip_planes();
+            }
+            ui.same_line(0.0);
+            help_marker(ui, im_str!("Derives the near and far clip planes from the size of the L-System's bounding box."));
+            ui.unindent();
+        }
+
+        ui.unindent();
+    }
+
+    {
+        let mut current_item: i32 = lsystem.app_settings.vsync_mode as _;
+        let items = vec![im_str!("On"), im_str!("Off"), im_str!("Adaptive")];
+
+        if ui.combo(im_str!("VSync"), &mut current_item, &items, 3) {
+            lsystem.app_settings.vsync_mode = match current_item {
+                0 => VSyncMode::On,
+                1 => VSyncMode::Off,
+                _ => VSyncMode::Adaptive
+            };
+
+            lsystem.vsync_changed = true;
+        }
+
+        ui.same_line(0.0);
+        help_marker(ui, im_str!("On: Caps the framerate at the refresh rate of the display.\n\
+                                 Off: Renders as fast as possible. Useful for performance profiling.\n\
+                                 Adaptive: Like on, but does not wait if a frame took too long. Not supported by all drivers."));
+
+        ui.same_line_with_spacing(0.0, 30.0);
+        ui.text(format!("FPS: {:.1}", ui.io().framerate));
+    }
+
+    ui.checkbox(im_str!("Depth fog"), &mut lsystem.app_settings.fog_enabled);
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("Fades surfaces into the fog color with increasing distance to the camera, which gives a sense of depth\n\
+                             for dense 3D structures. Applies to polygons, bezier models and legacy lines."));
+
+    if lsystem.app_settings.fog_enabled {
+        ui.indent();
+
+        let fog_color = &lsystem.app_settings.fog_color;
+        let mut color: [f32; 3] = [fog_color.x, fog_color.y, fog_color.z];
+
+        if ColorEdit::new(im_str!("Fog color"), &mut color).build(ui) {
+            lsystem.app_settings.fog_color = Vec3::new(color[0], color[1], color[2]);
+        }
+
+        ui.drag_float(im_str!("Fog start"), &mut lsystem.app_settings.fog_start)
+            .min(0.0)
+            .max(lsystem.app_settings.fog_end)
+            .display_format(im_str!("%.3lf"))
+            .speed(0.01)
+            .build();
+
+        ui.drag_float(im_str!("Fog end"), &mut lsystem.app_settings.fog_end)
+            .min(lsystem.app_settings.fog_start)
+            .max(10000.0)
+            .display_format(im_str!("%.3lf"))
+            .speed(0.01)
+            .build();
+
+        ui.unindent();
+    }
+
+    ui.checkbox(im_str!("Normal darkening"), &mut lsystem.app_settings.normal_darkening_enabled);
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("Darkens shaded surfaces facing downwards, which approximates the shadows in crevices of plant models\n\
+                             at almost no cost. Applies to shaded polygons and bezier models."));
+
+    if lsystem.app_settings.normal_darkening_enabled {
+        ui.indent();
+        Slider::<f32>::new(im_str!("Darkening intensity"), 0.0..=1.0).build(ui, &mut lsystem.app_settings.normal_darkening);
+        ui.unindent();
+    }
+
+    if ui.checkbox(im_str!("Generation gradient"), &mut lsystem.app_settings.generation_gradient) {
+        lsystem.refresh_meshes();
+    }
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("Colors line segments by the iteration they were produced in instead of using the color palette,\n\
+                             which shows how the L-System grows. Segments drawn by symbols of the axiom get the start color.\n\
+                             If the rules can not be replayed, for example stochastic ones, the drawing order is used instead."));
+
+    if lsystem.app_settings.generation_gradient {
+        ui.indent();
+
+        if lsystem.segment_generations.as_ref().map_or(false, |g| !g.replayed()) {
+            ui.text_colored([1.0, 0.8, 0.0, 1.0], im_str!("The rules could not be replayed, using drawing order"));
+        }
+
+        let start = &lsystem.app_settings.generation_gradient_start;
+        let mut start_color: [f32; 3] = [start.x, start.y, start.z];
+
+        if ColorEdit::new(im_str!("First generation"), &mut start_color).build(ui) {
+            lsystem.app_settings.generation_gradient_start = Vec3::new(start_color[0], start_color[1], start_color[2]);
+            lsystem.refresh_meshes();
+        }
+
+        let end = &lsystem.app_settings.generation_gradient_end;
+        let mut end_color: [f32; 3] = [end.x, end.y, end.z];
+
+        if ColorEdit::new(im_str!("Last generation"), &mut end_color).build(ui) {
+            lsystem.app_settings.generation_gradient_end = Vec3::new(end_color[0], end_color[1], end_color[2]);
+            lsystem.refresh_meshes();
+        }
+
+        ui.unindent();
+    }
+
+    ui.checkbox(im_str!("Gamma-correct rendering"), &mut lsystem.app_settings.srgb);
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("Shades the L-System in linear color space and converts the result to sRGB for display.\n\
+                             Without this, lit surfaces tend to look washed out. Screenshots match what is shown on screen."));
+
+    ui.checkbox(im_str!("Fixed render resolution"), &mut lsystem.app_settings.fixed_render_target);
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("Renders the L-System offscreen at the given resolution and shows it letterboxed, independent of the\n\
+                             window size. Screenshots are taken at this resolution, so their framing does not change when the\n\
+                             window is resized. The cursor position tooltip and dimension labels are not available in this mode."));
+
+    if lsystem.app_settings.fixed_render_target {
+        ui.indent();
+
+        let mut width = lsystem.app_settings.render_target_width as i32;
+        if ui.input_int(im_str!("Render width"), &mut width).build() {
+            lsystem.app_settings.render_target_width = width.max(16).min(8192) as _;
+        }
+
+        let mut height = lsystem.app_settings.render_target_height as i32;
+        if ui.input_int(im_str!("Render height"), &mut height).build() {
+            lsystem.app_settings.render_target_height = height.max(16).min(8192) as _;
+        }
+
+        ui.unindent();
+    }
+
+    if ui.checkbox(im_str!("Normalize scale"), &mut lsystem.app_settings.normalize_scale) {
+        lsystem.normalize_scale();
+
+        if lsystem.app_settings.auto_center_camera {
+            lsystem.center_camera();
+        }
+    }
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("Scales the rendered L-System so that the longest side of its bounding box has a length of 1.0.\n\
+                             This makes switching between L-Systems of very different sizes smoother. The geometry itself,\n\
+                             and thus all exports, are not affected."));
+
+    let mut locked = lsystem.camera.locked();
+    if ui.checkbox(im_str!("Lock camera"), &mut locked) {
+        lsystem.camera.set_locked(locked);
+    }
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("Ignores all mouse input to the camera, which avoids accidentally changing the view during presentations.\n\
+                             This can also be toggled using the L key."));
+
+    axis_view_buttons(ui, &mut lsystem.camera);
+
+    let mut current_item: i32 = lsystem.app_settings.flat_view_mode as _;
+    let items = vec![im_str!("Automatic"), im_str!("On"), im_str!("Off")];
+
+    if ui.combo(im_str!("2D mode"), &mut current_item, &items, 3) {
+        lsystem.app_settings.flat_view_mode = match current_item {
+            0 => FlatViewMode::Automatic,
+            1 => FlatViewMode::On,
+            _ => FlatViewMode::Off
+        };
+
+        lsystem.refresh_flat_view();
+    }
+
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("In 2D mode, the camera looks straight at the XY plane using orthographic projection.\n\
+                             Dragging with either mouse button pans the view and scrolling zooms.\n\
+                             In automatic mode, it is used for L-Systems without depth."));
+
+    let mut current_item: i32 = lsystem.app_settings.projection_mode as _;
+    let items = vec![im_str!("Perspective"), im_str!("Orthographic")];
+
+    if ui.combo(im_str!("Projection"), &mut current_item, &items, 2) {
+        lsystem.app_settings.projection_mode = match current_item {
+            0 => ProjectionMode::Perspective,
+            _ => ProjectionMode::Orthographic
+        };
+
+        lsystem.refresh_projection();
+    }
+
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("Orthographic projection shows the L-System without perspective distortion,\n\
+                             which is useful for inspecting 2D systems."));
+
+    if let ProjectionMode::Perspective = lsystem.app_settings.projection_mode {
+        ui.indent();
+        if Slider::<f32>::new(im_str!("Field of view"), 20.0..=120.0).build(ui, &mut lsystem.app_settings.field_of_view) {
+            lsystem.refresh_projection();
+        }
         ui.unindent();
     }
 
+    if Slider::<f32>::new(im_str!("Rotation sensitivity"), 0.0005..=0.02)
+        .display_format(im_str!("%.4f"))
+        .build(ui, &mut lsystem.app_settings.rotate_sensitivity) {
+        lsystem.refresh_camera_sensitivity();
+    }
+
+    if Slider::<f32>::new(im_str!("Pan sensitivity"), 0.0002..=0.01)
+        .display_format(im_str!("%.4f"))
+        .build(ui, &mut lsystem.app_settings.pan_sensitivity) {
+        lsystem.refresh_camera_sensitivity();
+    }
+
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("How fast the camera rotates and pans when dragging the mouse."));
+
+    let far_plane = lsystem.app_settings.far_plane;
+
+    if ui.drag_float(im_str!("Near clip plane"), &mut lsystem.app_settings.near_plane)
+        .min(0.00001)
+        .max(far_plane)
+        .display_format(im_str!("%.5f"))
+        .speed(0.0001)
+        .build() {
+        lsystem.clear_derived_clip_planes();
+    }
+
+    let near_plane = lsystem.app_settings.near_plane;
+
+    if ui.drag_float(im_str!("Far clip plane"), &mut lsystem.app_settings.far_plane)
+        .min(near_plane)
+        .max(100000.0)
+        .display_format(im_str!("%.1f"))
+        .speed(1.0)
+        .build() {
+        lsystem.clear_derived_clip_planes();
+    }
+
+    ui.same_line(0.0);
+    help_marker(ui, im_str!("Geometry closer than the near plane or farther away than the far plane is clipped.\n\
+                             A near plane that is too close to the camera can cause flickering surfaces.\n\
+                             Changing these replaces clip planes derived from the L-System until it is centered again."));
+
     ui.spacing();
 
     ui.checkbox(im_str!("Draw bounding box"), &mut lsystem.app_settings.draw_bounding_box);
@@ -732,6 +2416,16 @@ fn do_app_settings(ui: &Ui, lsystem: &mut LSystemScene) {
             lsystem.app_settings.bounding_box_color = new_color;
             lsystem.refresh_bounding_box_color();
         }
+
+        ui.checkbox(im_str!("Show dimensions"), &mut lsystem.app_settings.show_bounding_box_dimensions);
+        ui.same_line(0.0);
+        help_marker(ui, im_str!("Shows the width, height and depth of the bounding box in world units."));
+
+        if ui.checkbox(im_str!("Always on top"), &mut lsystem.app_settings.bounding_box_on_top) {
+            lsystem.refresh_bounding_box_on_top();
+        }
+        ui.same_line(0.0);
+        help_marker(ui, im_str!("Draws the bounding box on top of the L-System, so it is never hidden by its geometry."));
         ui.unindent();
     }
 }
\ No newline at end of file
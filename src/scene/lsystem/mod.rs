@@ -1,33 +1,137 @@
 use std::rc::*;
 use std::cell::*;
+use std::collections::BTreeMap;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::fs::{read_to_string, write, metadata};
+use std::time::{SystemTime, Instant, Duration};
 
-use nalgebra_glm::{Vec3, Mat4};
+use nalgebra_glm::{Vec3, Mat4, two_pi, lerp};
+use image::RgbaImage;
 
 use lsystems_core::*;
 use lsystems_core::drawing::types::*;
 
 use serde_json::*;
 
+use imgui::ImString;
+use glfw::{Action, Key, Modifiers, Window, WindowEvent};
+use nfd::Response;
+
 use crate::data::*;
 use crate::data::bezier::*;
+use crate::data::palette::*;
+use crate::data::text_import::*;
 use crate::rendering::*;
 use crate::rendering::camera::*;
 use crate::rendering::meshes::*;
 use crate::rendering::materials::*;
 use crate::rendering::model::*;
 use crate::rendering::traits::*;
+use crate::rendering::framebuffer::*;
 use crate::scene::lsystem::patch_management::*;
+use crate::scene::lsystem::export::*;
+use crate::scene::lsystem::turtle_frames::*;
+use crate::scene::lsystem::generations::*;
+use crate::scene::lsystem::frame_times::*;
+use crate::scene::lsystem::explorer::*;
+use crate::scene::lsystem::picking::*;
 use crate::scene::*;
+use crate::scene::bezier::BezierModelEdit;
 use crate::scene::lsystem::bounding_box::*;
+use crate::gui_utils::*;
 use crate::scene::lsystem::normal_test_material::*;
 use crate::scene::lsystem::normal_color_test_material::*;
+use crate::scene::lsystem::backface_test_material::*;
 use crate::rendering::primitives::line::*;
+use crate::rendering::primitives::point::*;
 
-mod bounding_box;
+pub mod bounding_box;
 pub mod normal_test_material;
-mod normal_color_test_material;
+pub mod normal_color_test_material;
+pub mod backface_test_material;
 mod gui;
 mod patch_management;
+pub mod export;
+mod turtle_frames;
+mod generations;
+mod frame_times;
+mod picking;
+mod explorer;
+
+/// An action that discards the current lsystem, and thus requires confirmation by the user if there
+/// are unsaved changes.
+#[derive(Clone, Copy)]
+pub enum DiscardingAction {
+    /// Replace the current lsystem with an empty one
+    New,
+    /// Let the user pick a file to load
+    Open,
+    /// Let the user pick a plain text rule file to import
+    ImportText,
+    /// Load given preset JSON string
+    LoadPreset(&'static str),
+    /// Quit the application
+    Quit,
+    /// Close the document tab showing this lsystem
+    CloseDocument
+}
+
+/// A mesh containing all polygons drawn with a single, not fully opaque color palette entry.
+/// These meshes have to be drawn after all opaque geometry, sorted back-to-front. Since sorting
+/// happens per mesh and not per triangle, overlapping polygons of the same color may still be
+/// blended in the wrong order.
+struct TranslucentMesh {
+    /// The mesh containing the polygons
+    mesh: Mesh,
+    /// The center of all polygon vertices, used as the sort key
+    center: Vec3
+}
+
+/// Tracks which parts of the lsystem parameters were modified while auto refresh was disabled,
+/// so that applying them only has to redo the work that is actually required.
+#[derive(Clone, Copy, Default)]
+struct PendingChanges {
+    /// The axiom or the rules were modified
+    rules: bool,
+    /// The symbol interpretations were modified
+    interpretations: bool,
+    /// The iteration depth was modified
+    iteration_depth: bool,
+    /// The drawing parameters were modified
+    drawing_parameters: bool,
+    /// The color palette was modified
+    color_palette: bool
+}
+
+impl PendingChanges {
+    /// Check whether any changes are pending.
+    fn any(&self) -> bool {
+        self.rules || self.interpretations || self.iteration_depth || self.drawing_parameters || self.color_palette
+    }
+
+    /// Check whether any of the pending changes require the lsystem to be reiterated.
+    fn requires_iteration(&self) -> bool {
+        self.rules || self.interpretations || self.iteration_depth
+    }
+}
+
+/// An lsystem iteration running on a background thread.
+struct IterationJob {
+    /// Receives the fully iterated and interpreted lsystem. Nothing is sent if the iteration was cancelled.
+    receiver: Receiver<LSystem>,
+    /// Tells the worker that its result is no longer needed, so that it can stop early.
+    cancelled: Arc<AtomicBool>
+}
+
+impl IterationJob {
+    /// Check whether this iteration was cancelled.
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
 
 /// A struct managing the currently displayed LSystem and providing methods
 /// to update certain parts of it.
@@ -40,16 +144,33 @@ pub struct LSystemScene {
     lsystem: LSystem,
     /// The mesh containing all lines of the lsystem
     lines_mesh: Mesh,
+    /// The mesh visualizing the turtle positions at the ends of all line segments, if enabled
+    /// in the debug options.
+    node_points_mesh: Option<Mesh>,
+    /// The axis triads showing the turtle frames at all save and restore commands, if enabled
+    turtle_frames_mesh: Option<Mesh>,
     /// The triangle fan meshes generated by the LSystem
     polygon_meshes: Vec<Mesh>,
+    /// The triangle fan meshes of all polygons that are not fully opaque
+    translucent_meshes: Vec<TranslucentMesh>,
+    /// Index of the polygon selected for inspection, if any
+    pub selected_polygon: Option<usize>,
+    /// The outline of the selected polygon
+    selected_polygon_mesh: Option<Mesh>,
     /// The bounding box around the lsystem. It might not exist, for example if there arent enough points.
     bounding_box: Option<BoundingBox>,
+    /// Uniform scale factor applied to the whole lsystem at render time. Is 1.0 unless scale normalization
+    /// is enabled.
+    render_scale: f32,
     /// The camera looking into the scene
     camera: Camera,
-    /// This option contains a reference shared with a BezierEditorScene instance that is running on top
-    /// of this scene in the SceneManager. If at any point, this scene gets evaluated and this is not None,
-    /// it contains the result of the editor scene and has to be handled.
-    model_to_refresh: Option<(usize, RcCell<BezierModelParameters>)>,
+    /// The near and far clip planes derived from the size of the lsystem, if automatic clip planes are enabled.
+    /// They are kept separate from the application settings, since they only apply to this lsystem.
+    derived_clip_planes: Option<(f32, f32)>,
+    /// This option contains the index of the bezier model currently being edited, together with the edit state
+    /// shared with the BezierEditorScene instance that is running on top of this scene in the SceneManager. It is
+    /// checked every frame, and once the editor is gone, its result is applied and this is cleared.
+    model_to_refresh: Option<(usize, RcCell<BezierModelEdit>)>,
     /// This types manages the meshes of all the defined bezier models, organized by their identifiers. This is 
     /// important since it allows the sharing of mesh instances between all the bezier model instantiations.
     /// Beware that this is completely independent from the actual _model_ instances, which are created from the meshes
@@ -60,7 +181,93 @@ pub struct LSystemScene {
     /// Screen width
     pub width: u32,
     /// Screen height
-    pub height: u32
+    pub height: u32,
+    /// The settings of the palette generator popup. These are kept here so that they are remembered
+    /// between invocations of the popup.
+    pub palette_generator: PaletteGenerator,
+    /// How many colors are sampled when importing a color palette from an image file
+    pub palette_image_samples: u32,
+    /// An error message that still has to be shown to the user in a modal popup.
+    pub pending_error: Option<String>,
+    /// The error message currently displayed by the error popup.
+    pub error_message: ImString,
+    /// The iteration currently running on the background thread, if any.
+    iteration_job: Option<IterationJob>,
+    /// Parameters of an iteration that was requested while another one was still running. It is started
+    /// as soon as the running one has finished, so that at most one worker thread exists at any time.
+    queued_iteration: Option<LSystemParameters>,
+    /// The current time in seconds, as provided by the scene manager.
+    current_time: f64,
+    /// The time in seconds the animation clock was last advanced at.
+    last_time: f64,
+    /// Time in seconds that drives all animations of this scene, like the progress indicator shown
+    /// while an iteration is running. It only advances while this scene is active.
+    pub animation_clock: f64,
+    /// Durations of the most recent frames, shown in the frame time graph.
+    pub frame_times: FrameTimes,
+    /// Generates random variants of the lsystem and remembers them.
+    pub explorer: RandomExplorer,
+    /// A short message that is briefly shown at the bottom of the screen, together with the value of the
+    /// animation clock at which it disappears.
+    pub toast: Option<(String, f64)>,
+    /// Whether the command palette should be opened next frame.
+    pub open_command_palette: bool,
+    /// The text the actions listed in the command palette are filtered by.
+    pub command_filter: ImString,
+    /// The file the current lsystem was loaded from or last saved to, if any.
+    pub file_path: Option<String>,
+    /// Whether the lsystem parameters were modified since they were last loaded or saved.
+    pub dirty: bool,
+    /// Modifications that were not yet applied to the lsystem because auto refresh is disabled.
+    pending_changes: PendingChanges,
+    /// The modification time of the associated file when it was last loaded, saved or checked
+    /// for changes.
+    file_modified: Option<SystemTime>,
+    /// When the associated file was last checked for changes.
+    last_file_check: Instant,
+    /// Whether the popup asking the user to confirm reloading the file has to be opened.
+    pub pending_reload: bool,
+    /// The application settings as they were last written to the settings file.
+    saved_settings: ApplicationSettings,
+    /// When the application settings were last checked for changes.
+    last_settings_check: Instant,
+    /// An action waiting for the unsaved changes popup to be opened.
+    pub pending_discard: Option<DiscardingAction>,
+    /// The action the unsaved changes popup is currently asking the user to confirm.
+    pub confirming_discard: Option<DiscardingAction>,
+    /// Whether the scene should tell the scene manager to quit the application.
+    quit_requested: bool,
+    /// Whether the document showing this lsystem should be closed
+    close_document_requested: bool,
+    /// Parameters of an lsystem that should be opened in a new document tab
+    pub new_document: Option<LSystemParameters>,
+    /// Whether the vsync mode was changed and has to be applied by the main loop.
+    pub vsync_changed: bool,
+    /// The symbol entered into the "from" field of the symbol remapping popup.
+    pub remap_from: ImString,
+    /// The symbol entered into the "to" field of the symbol remapping popup.
+    pub remap_to: ImString,
+    /// Whether the turntable export dialog is currently shown.
+    pub show_turntable_dialog: bool,
+    /// The settings of the turntable export dialog.
+    pub turntable_settings: TurntableSettings,
+    /// Whether the OBJ export dialog is currently shown.
+    pub show_obj_dialog: bool,
+    /// Whether the OBJ export writes one file per color index.
+    pub obj_split_by_color: bool,
+    /// Whether the lsystem parameters window is shown.
+    pub show_params_window: bool,
+    /// The offscreen target the scene is rendered into if a fixed render resolution is enabled
+    render_target: Option<Framebuffer>,
+    /// The generations of the line segments, if the generation gradient or window is enabled
+    pub segment_generations: Option<SegmentGenerations>,
+    /// The inclusive range of generations whose line segments are shown, if only a part of the lsystem is shown.
+    /// This requires the generations of the segments, and has no effect if they can not be determined.
+    pub generation_window: Option<(u32, u32)>,
+    /// Whether any GUI windows besides the main menu bar and popups are shown
+    gui_visible: bool,
+    /// Whether the windows should be moved back to their default positions and sizes this frame.
+    pub reset_layout: bool
 }
 
 impl LSystemScene {
@@ -75,8 +282,15 @@ impl LSystemScene {
 
         let bezier_mesh_manager = BezierMeshManager::from_parameters(&params.bezier_models);
 
-        let poly_meshes = Self::retrieve_polygon_meshes(&lsystem, params, settings);
-        let mesh = Self::retrieve_line_mesh(&lsystem, params, (w, h));
+        let (poly_meshes, translucent_meshes) = Self::retrieve_polygon_meshes(&lsystem, params, settings);
+        // A line mesh that is too big is replaced by an empty one. The user is told about it once the scene exists.
+        let segment_generations = Self::retrieve_segment_generations(&lsystem, params, settings, None);
+        let (mesh, line_mesh_error) = match Self::retrieve_line_mesh(&lsystem, params, settings, segment_generations.as_ref(), None, (w, h)) {
+            Ok(mesh) => (mesh, None),
+            Err(e) => (Mesh::new(PrimitiveType::Lines, Box::new(SimpleMaterial::new()), &BasicGeometry::from_vertices(&[])), Some(e))
+        };
+        let node_points_mesh = Self::retrieve_node_points_mesh(&lsystem, settings);
+        let turtle_frames_mesh = Self::retrieve_turtle_frames_mesh(&lsystem, params, settings);
         let bb = Self::calculate_bounding_box(&settings.bounding_box_color, &lsystem);
         let bezier_models = Self::retrieve_bezier_models(&lsystem, &bezier_mesh_manager);
 
@@ -84,37 +298,139 @@ impl LSystemScene {
             lsystem_params: params.clone(),
             app_settings: settings.clone(),
             lines_mesh: mesh,
+            node_points_mesh: node_points_mesh,
+            turtle_frames_mesh: turtle_frames_mesh,
             polygon_meshes: poly_meshes,
+            selected_polygon: None,
+            selected_polygon_mesh: None,
+            translucent_meshes: translucent_meshes,
             lsystem,
             bounding_box: bb,
-            camera: Camera::new(w, h, ProjectionType::Perspective(75.0)),
+            render_scale: 1.0,
+            camera: Camera::new(w, h, settings.projection_type()),
+            derived_clip_planes: None,
             model_to_refresh: None,
             width: w,
             height: h,
             bezier_manager: bezier_mesh_manager,
-            bezier_models: bezier_models
+            bezier_models: bezier_models,
+            palette_generator: PaletteGenerator::new(),
+            palette_image_samples: 8,
+            pending_error: None,
+            error_message: ImString::new(""),
+            iteration_job: None,
+            queued_iteration: None,
+            current_time: 0.0,
+            last_time: 0.0,
+            animation_clock: 0.0,
+            frame_times: FrameTimes::new(),
+            explorer: RandomExplorer::new(),
+            toast: None,
+            open_command_palette: false,
+            command_filter: ImString::with_capacity(128),
+            file_path: None,
+            dirty: false,
+            pending_changes: PendingChanges::default(),
+            file_modified: None,
+            last_file_check: Instant::now(),
+            pending_reload: false,
+            saved_settings: settings.clone(),
+            last_settings_check: Instant::now(),
+            pending_discard: None,
+            confirming_discard: None,
+            quit_requested: false,
+            close_document_requested: false,
+            new_document: None,
+            vsync_changed: false,
+            remap_from: ImString::with_capacity(16),
+            remap_to: ImString::with_capacity(16),
+            show_turntable_dialog: false,
+            turntable_settings: TurntableSettings::new(),
+            show_obj_dialog: false,
+            obj_split_by_color: false,
+            show_params_window: true,
+            render_target: None,
+            segment_generations: segment_generations,
+            generation_window: None,
+            gui_visible: true,
+            reset_layout: false
         };
 
+        scene.refresh_clip_planes();
+        scene.refresh_camera_sensitivity();
+        scene.normalize_scale();
+        scene.refresh_flat_view();
+        scene.refresh_bounding_box_on_top();
+
         if settings.auto_center_camera {
             scene.center_camera();
         } 
 
+        if let Some(e) = line_mesh_error {
+            scene.report_error(&e);
+        }
+
         scene
     }
 
     /// Mark the bezier model with given index as being "currently in edit mode".
     /// This means that a EditBezierScene is going to be the active scene and modify
     /// its contents.
-    pub fn edit_bezier_model(&mut self, index: usize) -> RcCell<BezierModelParameters> {
-        let model = self.lsystem_params.bezier_models[index].clone();
-
-        let cell = make_rc_cell(model);
+    pub fn edit_bezier_model(&mut self, index: usize) -> RcCell<BezierModelEdit> {
+        let cell = make_rc_cell(BezierModelEdit::new());
 
         self.model_to_refresh = Some((index, cell.clone()));
 
         cell
     }
 
+    /// Handle the edit state shared with a running bezier editor. Models applied to the preview get their
+    /// meshes regenerated right away. Once the editor is gone, the saved model is committed, and any preview
+    /// of unsaved changes is reverted.
+    fn poll_bezier_edit(&mut self) {
+        let (index, finished, saved, preview) = match &self.model_to_refresh {
+            Some((i, edit)) => {
+                // The editor holds the only other reference to the edit state
+                let finished = Rc::strong_count(edit) == 1;
+                let mut edit = edit.borrow_mut();
+                let saved = if finished { edit.saved.take() } else { None };
+
+                (*i, finished, saved, edit.preview.take())
+            },
+            None => return
+        };
+
+        if let Some(parameters) = preview {
+            self.bezier_manager.update_meshes(&parameters);
+        }
+
+        if finished {
+            if let Some(parameters) = saved {
+                // Store it for later
+                self.lsystem_params.bezier_models[index] = parameters;
+                self.mark_dirty();
+            }
+
+            // Recreate mesh. This also reverts unsaved previews.
+            self.bezier_manager.update_meshes(&self.lsystem_params.bezier_models[index]);
+
+            // Clear it, so that we don't to the refreshing again next frame.
+            self.model_to_refresh = None;
+        }
+    }
+
+    /// Swap the bezier models with given indices. If both share the same symbol, the later one provides the
+    /// meshes, so they are regenerated in the new order.
+    pub fn swap_bezier_models(&mut self, a: usize, b: usize) {
+        self.lsystem_params.bezier_models.swap(a, b);
+
+        for i in &[a.min(b), a.max(b)] {
+            self.bezier_manager.update_meshes(&self.lsystem_params.bezier_models[*i]);
+        }
+
+        self.mark_dirty();
+    }
+
     /// Completely refresh all loaded bezier models based on the information stored in the LSystem
     /// draw results and the bezier mesh manager
     pub fn refresh_bezier_models(&mut self) {
@@ -169,6 +485,16 @@ impl LSystemScene {
         } 
     }
 
+    /// Report an error to the user. The message will be shown in a modal popup.
+    pub fn report_error(&mut self, message: &str) {
+        self.pending_error = Some(message.to_string());
+    }
+
+    /// Mark the lsystem parameters as modified since they were last loaded or saved.
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
     /// Shortcut to auto refresh settings value
     fn auto_refresh(&self) -> bool {
         self.app_settings.auto_refresh
@@ -178,24 +504,50 @@ impl LSystemScene {
     pub fn center_camera(&mut self) {
         // We can only center the camera if there exists a bounding box
         if let Some(bb) = &self.bounding_box {
-            // Determine the center
-            let center = bb.aabb.center().coords;
+            // Determine the center. The bounding box is in lsystem coordinates, so the render scale has to be applied.
+            let center = bb.aabb.center().coords * self.render_scale;
             self.camera.recenter(&center);
 
             // Adjust zoom level if requested
             if self.app_settings.auto_adjust_radius {
-                self.camera.set_radius(bb.radius());
+                let radius = bb.radius() * self.render_scale as f64;
+                self.camera.set_radius(radius);
+
+                // The whole system lies within twice the radius from the camera. The far plane leaves
+                // additional room for zooming out.
+                if self.app_settings.auto_clip_planes {
+                    self.derived_clip_planes = Some(((radius * 0.001).max(0.00001) as _, (radius * 20.0).max(1.0) as _));
+                    self.refresh_clip_planes();
+                }
             }
         }
     }
 
     pub fn refresh_color_palette(&mut self) {
+        self.mark_dirty();
         self.lsystem_params.drawing_parameters.color_palette_size = self.lsystem_params.color_palette.len() as _;
+
+        if !self.auto_refresh() {
+            self.pending_changes.color_palette = true;
+            return;
+        }
+
         self.draw_lsystem();
     }
 
 
     pub fn force_refresh_all(&mut self) {
+        self.pending_changes = PendingChanges::default();
+
+        // Loaded files might contain drawing parameters the GUI would never produce. The adjusted parameters
+        // differ from the ones on disk, so they count as a modification.
+        let adjustments = self.lsystem_params.sanitize_drawing_parameters();
+
+        if !adjustments.is_empty() {
+            self.mark_dirty();
+            self.report_error(&format!("Some drawing parameters were out of range and have been adjusted:\n{}", adjustments.join("\n")));
+        }
+
         self.bezier_manager = BezierMeshManager::from_parameters(&self.lsystem_params.bezier_models);
         self.lsystem.set_drawing_parameters(&self.lsystem_params.drawing_parameters);
         self.lsystem.set_iteration_depth(self.lsystem_params.iteration_depth);
@@ -203,12 +555,141 @@ impl LSystemScene {
         self.apply_rules();
 
         self.iterate_lsystem();
-        self.draw_lsystem();
+    }
+
+    /// Replace the lsystem parameters with given variant generated by the random explorer, and rebuild
+    /// everything.
+    pub fn show_variant(&mut self, variant: LSystemParameters) {
+        self.lsystem_params = variant;
+        self.mark_dirty();
+        self.force_refresh_all();
+    }
+
+    /// Toggle auto refresh. When it is enabled again, everything is rebuilt, so that the view reflects
+    /// all edits made in the meantime.
+    pub fn toggle_auto_refresh(&mut self) {
+        self.app_settings.auto_refresh = !self.app_settings.auto_refresh;
+
+        if self.app_settings.auto_refresh {
+            self.force_refresh_all();
+            self.show_toast("Auto refresh enabled");
+        } else {
+            self.show_toast("Auto refresh disabled");
+        }
+    }
+
+    /// Briefly show given message at the bottom of the screen.
+    pub fn show_toast(&mut self, message: &str) {
+        const TOAST_DURATION: f64 = 2.0;
+
+        self.toast = Some((message.to_string(), self.animation_clock + TOAST_DURATION));
+    }
+
+    /// Check whether there are modifications that were not yet applied because auto refresh is disabled.
+    pub fn has_pending_changes(&self) -> bool {
+        self.pending_changes.any()
+    }
+
+    /// Apply all modifications that were made while auto refresh was disabled. In contrast to
+    /// `force_refresh_all`, only the work required by the modified parameters is redone.
+    pub fn apply_pending_changes(&mut self) {
+        let pending = self.pending_changes;
+        self.pending_changes = PendingChanges::default();
+
+        if pending.drawing_parameters {
+            self.lsystem.set_drawing_parameters(&self.lsystem_params.drawing_parameters);
+        }
+
+        if pending.requires_iteration() {
+            if pending.iteration_depth {
+                self.lsystem.set_iteration_depth(self.lsystem_params.iteration_depth);
+            }
+
+            if pending.interpretations {
+                self.apply_interpretations();
+            }
+
+            if pending.rules {
+                self.apply_rules();
+            }
+
+            self.iterate_lsystem();
+        } else if pending.any() {
+            // Drawing parameters and color palette only require the lsystem to be redrawn
+            self.draw_lsystem();
+        }
     }
 
     /// Redraw the bounding box. Should be called when the lsystem was newly drawn.
     fn draw_bounding_box(&mut self) {
         self.bounding_box = Self::calculate_bounding_box(&self.app_settings.bounding_box_color, &self.lsystem);
+        self.refresh_bounding_box_on_top();
+        self.normalize_scale();
+        self.refresh_flat_view();
+    }
+
+    /// Switch the camera in or out of 2D mode, depending on the flat view setting. In automatic mode,
+    /// 2D mode is used if the bounding box of the lsystem has no noticeable depth.
+    pub fn refresh_flat_view(&mut self) {
+        let flat = match self.app_settings.flat_view_mode {
+            FlatViewMode::On => true,
+            FlatViewMode::Off => false,
+            FlatViewMode::Automatic => match &self.bounding_box {
+                Some(bb) => {
+                    let extents = bb.aabb.extents();
+                    extents.z <= extents.x.max(extents.y) * 1.0e-4
+                },
+                None => false
+            }
+        };
+
+        self.camera.set_flat(flat);
+    }
+
+    /// Determine the render scale. If scale normalization is enabled, the lsystem is scaled so that the
+    /// longest axis of its bounding box has a length of 1.0. The geometry itself is left untouched.
+    pub fn normalize_scale(&mut self) {
+        self.render_scale = 1.0;
+
+        if self.app_settings.normalize_scale {
+            if let Some(bb) = &self.bounding_box {
+                let extents = bb.aabb.extents();
+                let longest = extents.x.max(extents.y).max(extents.z);
+
+                // Degenerate systems, like a single point, can't be scaled up
+                if longest > 0.0 {
+                    self.render_scale = 1.0 / longest;
+                }
+            }
+        }
+    }
+
+    /// Notify scene that the camera projection settings have changed
+    pub fn refresh_projection(&mut self) {
+        self.camera.set_projection(self.app_settings.projection_type());
+    }
+
+    /// Notify scene that the camera sensitivity settings have changed
+    pub fn refresh_camera_sensitivity(&mut self) {
+        self.camera.set_rotate_sensitivity(self.app_settings.rotate_sensitivity as _);
+        self.camera.set_pan_sensitivity(self.app_settings.pan_sensitivity);
+    }
+
+    /// Notify scene that the clip plane settings have changed. The planes derived from the lsystem take precedence
+    /// over the ones from the settings while automatic clip planes are enabled.
+    pub fn refresh_clip_planes(&mut self) {
+        let (near, far) = match self.derived_clip_planes {
+            Some(planes) if self.app_settings.auto_clip_planes => planes,
+            _ => (self.app_settings.near_plane, self.app_settings.far_plane)
+        };
+
+        self.camera.set_clip_planes(near, far);
+    }
+
+    /// Forget the clip planes derived from the lsystem, so that the ones from the settings are used again.
+    pub fn clear_derived_clip_planes(&mut self) {
+        self.derived_clip_planes = None;
+        self.refresh_clip_planes();
     }
 
     pub fn refresh_bounding_box_color(&mut self) {
@@ -217,9 +698,19 @@ impl LSystemScene {
         }
     }
 
+    /// Notify scene that the bounding box on top setting has changed
+    pub fn refresh_bounding_box_on_top(&mut self) {
+        if let Some(bb) = &mut self.bounding_box {
+            bb.always_on_top = self.app_settings.bounding_box_on_top;
+        }
+    }
+
     /// Notify scene that the  drawing parameters have changed
     pub fn refresh_drawing_parameters(&mut self) {
+        self.mark_dirty();
+
         if !self.auto_refresh() {
+            self.pending_changes.drawing_parameters = true;
             return;
         }
 
@@ -228,33 +719,51 @@ impl LSystemScene {
     }
 
     pub fn refresh_iteration_depth(&mut self) {
+        self.mark_dirty();
+
         if !self.auto_refresh() {
+            self.pending_changes.iteration_depth = true;
             return;
         }
 
         self.lsystem.set_iteration_depth(self.lsystem_params.iteration_depth);
         self.iterate_lsystem();
-        self.draw_lsystem();
     }
 
     pub fn refresh_rules(&mut self) {
+        self.mark_dirty();
+
         if !self.auto_refresh() {
+            self.pending_changes.rules = true;
             return;
         }
 
         self.apply_rules();
         self.iterate_lsystem();
-        self.draw_lsystem();
+    }
+
+    /// Rename given symbol everywhere in the lsystem parameters and fully refresh the lsystem. If the new
+    /// symbol is already in use, nothing is renamed and the user is told why.
+    pub fn rename_symbol(&mut self, old: char, new: char) {
+        match self.lsystem_params.rename_symbol(old, new) {
+            Ok(()) => {
+                self.mark_dirty();
+                self.force_refresh_all();
+            },
+            Err(e) => self.report_error(&e)
+        }
     }
 
     pub fn refresh_interpretations(&mut self) {
+        self.mark_dirty();
+
         if !self.auto_refresh() {
+            self.pending_changes.interpretations = true;
             return;
         }
 
         self.apply_interpretations();
         self.iterate_lsystem();
-        self.draw_lsystem();
     }
 
     /// Apply interpretations in the lsystem parameters to the current lsystem instance
@@ -274,14 +783,162 @@ impl LSystemScene {
     }
 
     /// Fully reiterate the lsystem. This is necessary if the iteration depth, the axiom or one or more 
-    /// rules changed.
+    /// rules changed. Since this can take a long time, the work is done on a background thread using
+    /// a fresh lsystem instance. The current scene objects stay on screen until the result is picked up
+    /// in `do_logic`. If an iteration is still running, it is cancelled and the new one is queued until
+    /// the worker has stopped.
     fn iterate_lsystem(&mut self) {
-        self.lsystem.iterate();
+        self.limit_iteration_depth();
+
+        let params = self.lsystem_params.clone();
+
+        match &self.iteration_job {
+            Some(job) => {
+                job.cancelled.store(true, Ordering::Relaxed);
+                self.queued_iteration = Some(params);
+            },
+            None => self.start_iteration(params)
+        }
+    }
+
+    /// Start iterating an lsystem with given parameters on a new worker thread.
+    fn start_iteration(&mut self, params: LSystemParameters) {
+        let (sender, receiver) = channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let flag = cancelled.clone();
+
+        thread::spawn(move || {
+            let mut lsystem = LSystem::new();
+            Self::setup_lsystem(&mut lsystem, &params);
+
+            // lsystems-core can not be interrupted while iterating, so the flag is only checked between steps.
+            lsystem.iterate();
+            if flag.load(Ordering::Relaxed) {
+                return;
+            }
+
+            lsystem.interpret();
+            if flag.load(Ordering::Relaxed) {
+                return;
+            }
+
+            let _ = sender.send(lsystem);
+        });
+
+        self.iteration_job = Some(IterationJob { receiver, cancelled });
+    }
+
+    /// Reduce the iteration depth until the estimated expansion length is within the configured limit,
+    /// and tell the user about it if that was necessary.
+    fn limit_iteration_depth(&mut self) {
+        let requested = self.lsystem_params.iteration_depth;
+        let limit = self.app_settings.max_expansion_length as u64;
+
+        let mut depth = requested;
+        while depth > 0 && self.lsystem_params.estimate_expansion_length(depth) > limit {
+            depth -= 1;
+        }
+
+        if depth < requested {
+            self.lsystem_params.iteration_depth = depth;
+            self.lsystem.set_iteration_depth(depth);
+
+            self.report_error(&format!(
+                "The iteration depth was reduced from {} to {}, since the expansion would have about {} symbols, \
+                 which exceeds the limit of {}. The limit can be changed in the application settings.",
+                requested, depth, self.lsystem_params.estimate_expansion_length(requested), limit
+            ));
+        }
+    }
+
+    /// Retrieve the full symbol sequence produced by the last iteration of the lsystem.
+    pub fn expanded_string(&self) -> String {
+        self.lsystem.iteration_result.iter().map(|module| module.identifier).collect()
+    }
+
+    /// Retrieve at most the given number of leading symbols of the expanded lsystem string, and
+    /// whether the expansion had to be truncated.
+    pub fn expanded_string_preview(&self, max_length: usize) -> (String, bool) {
+        let preview = self.lsystem.iteration_result.iter().take(max_length).map(|module| module.identifier).collect();
+        (preview, self.expanded_length() > max_length)
+    }
+
+    /// Retrieve the number of symbols in the expanded lsystem string.
+    pub fn expanded_length(&self) -> usize {
+        self.lsystem.iteration_result.len()
+    }
+
+    /// Retrieve the number of line segments, polygons and bezier patches the lsystem was drawn with.
+    pub fn drawing_statistics(&self) -> (usize, usize, usize) {
+        let result = &self.lsystem.drawing_result;
+        (result.line_segments.len(), result.polygons.len(), result.patches.len())
+    }
+
+    /// Check whether an iteration is currently running on the background thread, or queued to run once
+    /// the worker is available. Cancelled iterations that did not stop yet are not counted.
+    pub fn is_iterating(&self) -> bool {
+        self.queued_iteration.is_some() || self.iteration_job.as_ref().map_or(false, |job| !job.is_cancelled())
+    }
+
+    /// Cancel the currently running iteration and any queued one. The previous scene objects are kept.
+    pub fn cancel_iteration(&mut self) {
+        if let Some(job) = &self.iteration_job {
+            job.cancelled.store(true, Ordering::Relaxed);
+        }
+
+        self.queued_iteration = None;
+    }
+
+    /// Check whether the background iteration has finished, and if so, replace the current lsystem
+    /// with the result and rebuild all scene objects. A queued iteration is started once the worker
+    /// has stopped.
+    fn poll_iteration(&mut self) {
+        let (result, cancelled) = match &self.iteration_job {
+            Some(job) => (job.receiver.try_recv(), job.is_cancelled()),
+            None => return
+        };
+
+        match result {
+            // The result of a cancelled iteration is discarded, even if the worker finished anyway
+            Ok(lsystem) => {
+                self.iteration_job = None;
+
+                if !cancelled {
+                    self.lsystem = lsystem;
+                    self.refresh_scene_objects();
+                }
+            },
+            // Cancelled workers stop without sending anything
+            Err(TryRecvError::Disconnected) => {
+                self.iteration_job = None;
+
+                if !cancelled {
+                    self.report_error("The lsystem iteration failed unexpectedly.");
+                }
+            },
+            Err(TryRecvError::Empty) => return
+        }
+
+        if let Some(params) = self.queued_iteration.take() {
+            self.start_iteration(params);
+        }
     }
 
-    /// Draw the lsystem, which means interpreting it and retrieving all scene objects from it
+    /// Draw the lsystem, which means interpreting it and retrieving all scene objects from it.
+    /// If an iteration is currently running, it is restarted instead, since its result would
+    /// otherwise overwrite the changes.
     fn draw_lsystem(&mut self) {
+        if self.is_iterating() {
+            self.iterate_lsystem();
+            return;
+        }
+
         self.lsystem.interpret();
+        self.refresh_scene_objects();
+    }
+
+    /// Recreate all scene objects from the already interpreted lsystem.
+    fn refresh_scene_objects(&mut self) {
         self.refresh_meshes();
         self.refresh_bezier_models();
         self.draw_bounding_box();
@@ -295,14 +952,126 @@ impl LSystemScene {
     /// Does not redraw lsystem, just recreates the meshes. Needed if mesh data changes, such as debug settings
     /// or the color palette entries.
     pub fn refresh_meshes(&mut self) {
-        self.lines_mesh = Self::retrieve_line_mesh(&self.lsystem, &self.lsystem_params, (self.width, self.height));
-        self.polygon_meshes = Self::retrieve_polygon_meshes(&self.lsystem, &self.lsystem_params, &self.app_settings);
+        self.segment_generations = Self::retrieve_segment_generations(&self.lsystem, &self.lsystem_params, &self.app_settings, self.generation_window);
+
+        // If the line mesh would be too big, the previous one is kept
+        match Self::retrieve_line_mesh(&self.lsystem, &self.lsystem_params, &self.app_settings, self.segment_generations.as_ref(), self.generation_window, self.view_size()) {
+            Ok(mesh) => self.lines_mesh = mesh,
+            Err(e) => self.report_error(&e)
+        }
+
+        self.node_points_mesh = Self::retrieve_node_points_mesh(&self.lsystem, &self.app_settings);
+        self.turtle_frames_mesh = Self::retrieve_turtle_frames_mesh(&self.lsystem, &self.lsystem_params, &self.app_settings);
+        let (polygon_meshes, translucent_meshes) = Self::retrieve_polygon_meshes(&self.lsystem, &self.lsystem_params, &self.app_settings);
+        self.polygon_meshes = polygon_meshes;
+        self.translucent_meshes = translucent_meshes;
+
+        // The polygons might have changed, so the selection is no longer meaningful
+        self.select_polygon(None);
+    }
+
+    /// Retrieve the vertices of the polygon with given index.
+    pub fn polygon_vertices(&self, index: usize) -> Vec<Vec3> {
+        self.lsystem.drawing_result.polygons[index].vertices.iter()
+            .map(|v| Vec3::new(v.x as _, v.y as _, v.z as _))
+            .collect()
+    }
+
+    /// Retrieve the color palette index of the polygon with given index.
+    pub fn polygon_color(&self, index: usize) -> usize {
+        self.lsystem.drawing_result.polygons[index].color as usize
+    }
+
+    /// Select the polygon under given window position for inspection, or clear the selection if there is none.
+    /// Instead of reading the depth buffer, the ray through the cursor is intersected with all polygons, which
+    /// also allows translucent polygons to be selected.
+    pub fn pick_polygon(&mut self, x: u32, y: u32) {
+        let (x, y) = match self.to_view_position(x, y) {
+            Some(position) => position,
+            None => {
+                self.select_polygon(None);
+                return;
+            }
+        };
+
+        // The lsystem is scaled at render time, but the polygons are stored unscaled
+        let camera = self.view_camera();
+        let near = camera.unproject(x, y, 0.0) / self.render_scale;
+        let far = camera.unproject(x, y, 1.0) / self.render_scale;
+        let direction = far - near;
+
+        let mut closest: Option<(usize, f32)> = None;
+
+        for i in 0..self.lsystem.drawing_result.polygons.len() {
+            if let Some(distance) = ray_polygon_intersection(&near, &direction, &self.polygon_vertices(i)) {
+                if closest.map_or(true, |(_, d)| distance < d) {
+                    closest = Some((i, distance));
+                }
+            }
+        }
+
+        self.select_polygon(closest.map(|(i, _)| i));
+    }
+
+    /// Select the polygon with given index for inspection, and highlight its outline.
+    pub fn select_polygon(&mut self, index: Option<usize>) {
+        self.selected_polygon = index;
+
+        self.selected_polygon_mesh = index.map(|i| {
+            let color = Vec3::new(1.0, 1.0, 0.0);
+            let vertices: Vec<Vertex> = self.polygon_vertices(i).iter()
+                .map(|p| Vertex::new(p.clone(), color))
+                .collect();
+
+            let mut mesh = Mesh::new(PrimitiveType::LineLoop, Box::new(SimpleMaterial::new()), &BasicGeometry::from_vertices(&vertices));
+            mesh.line_width = 3.0;
+            mesh
+        });
+    }
+
+    /// Move the camera to look at the selected polygon, at a distance at which it is fully visible.
+    pub fn focus_selected_polygon(&mut self) {
+        let index = match self.selected_polygon {
+            Some(index) => index,
+            None => return
+        };
+
+        let vertices = self.polygon_vertices(index);
+        let center = vertices.iter().fold(Vec3::zeros(), |acc, v| acc + v) / (vertices.len().max(1) as f32);
+        let extent = vertices.iter().map(|v| (v - center).norm()).fold(0.0, f32::max);
+
+        self.camera.recenter(&(center * self.render_scale));
+        self.camera.set_radius((extent * self.render_scale * 3.0).max(0.01) as f64);
+    }
+
+    /// Notify scene that the normal vector debug length has changed
+    pub fn refresh_normal_length(&mut self) {
+        for mesh in &mut self.polygon_meshes {
+            if let Some(material) = mesh.try_retrieve_material_mut_ref::<NormalTestMaterial>() {
+                material.normal_length = self.app_settings.normal_debug_length;
+            }
+        }
+    }
+
+    /// Notify scene that the polygon offset setting has changed
+    pub fn refresh_polygon_offset(&mut self) {
+        for mesh in &mut self.polygon_meshes {
+            mesh.polygon_offset = self.app_settings.polygon_offset();
+        }
+
+        for translucent in &mut self.translucent_meshes {
+            translucent.mesh.polygon_offset = self.app_settings.polygon_offset();
+        }
     }
 
-    /// Notify scene that the wireframe setting has changed
+    /// Notify scene that the wireframe mode setting has changed
     pub fn refresh_wireframe_flag(&mut self) {
         for mesh in &mut self.polygon_meshes {
-            mesh.draw_wireframe = self.app_settings.draw_wireframe;
+            mesh.wireframe_mode = self.app_settings.wireframe_mode;
+        }
+
+        for translucent in &mut self.translucent_meshes {
+            translucent.mesh.wireframe_mode = self.app_settings.wireframe_mode;
         }
     }
 
@@ -321,102 +1090,714 @@ impl LSystemScene {
         }
     }
 
-    /// Load lsystem parameters from JSON string.
-    pub fn load(&mut self, json_str: &str) {
-        let params = from_str::<LSystemParameters>(json_str);
+    /// Load lsystem parameters from JSON string. The loaded lsystem is not associated with any file.
+    /// Returns whether loading was successful.
+    pub fn load(&mut self, json_str: &str) -> bool {
+        let params = LSystemParameters::from_string_migrated(json_str);
 
         match params {
-            Ok(params) => {
+            Ok((params, version)) => {
                 self.lsystem_params = params;
+                self.file_path = None;
+                self.file_modified = None;
+                self.dirty = false;
+                self.explorer.clear_history();
                 self.force_refresh_all();
+
+                // Saving writes the current version, which older versions of the application might not be able to read
+                if version < LSYSTEM_PARAMETERS_VERSION {
+                    self.show_toast(&format!("Migrated file from version {} to version {}", version, LSYSTEM_PARAMETERS_VERSION));
+                }
+
+                true
             }
             Err(e) => {
-                println!("Could not load given JSON string as LSystem parameters: {}", e);
+                self.report_error(&format!("Could not load given JSON string as LSystem parameters: {}", e));
+                false
             }
-        };
+        }
     }
 
-    /// Save lsystem parameters to JSON string.
-    pub fn save(&mut self) -> String {
-        to_string_pretty(&self.lsystem_params).unwrap()
+    /// Perform given action, asking the user for confirmation first if there are unsaved changes.
+    pub fn perform_discarding_action(&mut self, action: DiscardingAction) {
+        if self.dirty {
+            self.pending_discard = Some(action);
+        } else {
+            self.execute_discarding_action(action);
+        }
     }
 
-    /// Create line mesh from interpreted lsystem
-    fn retrieve_line_mesh(lsystem: &LSystem, params: &LSystemParameters, screen_dims: (u32, u32)) -> Mesh {
-        let mat: Box<dyn Material> = match params.line_draw_mode {
-            LineDrawMode::Basic => Box::new(SimpleMaterial::new()),
-            LineDrawMode::Advanced2D => Box::new(Line2DMaterial::new(screen_dims)),
-            LineDrawMode::Advanced3D => Box::new(Line3DMaterial::new())
-        };
-
-        // Handle legacy lines
-        let mesh: Mesh;
+    /// Perform given action without asking for confirmation.
+    pub fn execute_discarding_action(&mut self, action: DiscardingAction) {
+        match action {
+            DiscardingAction::New => {
+                self.load(crate::data::presets::EMPTY);
+            },
+            DiscardingAction::Open => self.open_file(),
+            DiscardingAction::ImportText => self.import_text_file(),
+            DiscardingAction::LoadPreset(json) => {
+                self.load(json);
+            },
+            DiscardingAction::Quit => self.quit_requested = true,
+            DiscardingAction::CloseDocument => self.close_document_requested = true
+        }
+    }
 
-        if let LineDrawMode::Basic = params.line_draw_mode {
-            // Buffer for line vertices
-            let mut vertices = Vec::new();
+    /// Extract the path picked by the user from given file dialog result. If the dialog itself failed,
+    /// the error is reported to the user. Returns None if there is no path to use.
+    pub fn picked_path(&mut self, result: nfd::Result<Response>) -> Option<String> {
+        match result {
+            Ok(Response::Okay(path)) => Some(path),
+            Ok(Response::OkayMultiple(paths)) => paths.into_iter().next(),
+            // User canceled
+            Ok(Response::Cancel) => None,
+            Err(e) => {
+                self.report_error(&format!("Could not show file dialog: {}", e));
+                None
+            }
+        }
+    }
 
-            for segment in &lsystem.drawing_result.line_segments {
-                // Lookup color
-                let color_index = if segment.color >= lsystem.parameters.color_palette_size as _ { 
-                    lsystem.parameters.color_palette_size - 1
-                } else {
-                    segment.color as _
-                };
+    /// Ask the user for a JSON file and load it.
+    pub fn open_file(&mut self) {
+        let result = nfd::open_file_dialog(Some("json"), None);
 
-                let color = if params.color_palette.len() == 0 {
-                    Vec3::repeat(1.0)
-                } else {
-                    params.color_palette[color_index as usize]
-                };
+        if let Some(path) = self.picked_path(result) {
+            self.load_file(&path);
+        }
+    }
 
-                let begin = Vertex::new(segment.begin.clone(), color);
-                let end = Vertex::new(segment.end.clone(), color);
-        
-                vertices.push(begin);
-                vertices.push(end);
+    /// Let the user pick a plain text rule file and import the lsystem it describes. The result is not
+    /// associated with the file, since saving writes JSON.
+    pub fn import_text_file(&mut self) {
+        let result = nfd::open_file_dialog(Some("txt"), None);
+
+        if let Some(path) = self.picked_path(result) {
+            let name = std::path::Path::new(&path).file_stem()
+                .map_or("Imported L-System".to_string(), |stem| stem.to_string_lossy().into_owned());
+
+            let params = read_to_string(&path)
+                .map_err(|e| format!("Could not read file \"{}\": {}", path, e))
+                .and_then(|text| parse_text_lsystem(&text, &name)
+                    .map_err(|e| format!("Could not import \"{}\": {}", path, e)));
+
+            match params {
+                Ok(params) => {
+                    self.lsystem_params = params;
+                    self.file_path = None;
+                    self.file_modified = None;
+                    self.mark_dirty();
+                    self.explorer.clear_history();
+                    self.force_refresh_all();
+                },
+                Err(e) => self.report_error(&e)
             }
+        }
+    }
 
-            mesh = Mesh::new(PrimitiveType::Lines, mat, &BasicGeometry::from_vertices(&vertices))
-        } else {
-            // Line geometry
-            let mut geom = LineGeometry::new();
-
-            for segment in &lsystem.drawing_result.line_segments {
-                // Lookup color
-                let color_index = if segment.color >= lsystem.parameters.color_palette_size as _ { 
-                    lsystem.parameters.color_palette_size - 1
-                } else {
-                    segment.color as _
-                };
-
-                let color = if params.color_palette.len() == 0 {
-                    Vec3::repeat(1.0)
-                } else {
-                    params.color_palette[color_index as usize]
-                };
+    /// Load lsystem parameters from given JSON file, and remember its path for subsequent saves.
+    pub fn load_file(&mut self, path: &str) {
+        match read_to_string(path) {
+            Ok(json) => {
+                if self.load(&json) {
+                    self.file_path = Some(path.to_string());
+                    self.file_modified = Self::modification_time(path);
+                }
+            },
+            Err(e) => self.report_error(&format!("Could not read file \"{}\": {}", path, e))
+        }
+    }
 
-                let begin = &segment.begin;
-                let end = &segment.end;
-        
-                geom.add_segment(
-                    segment.begin.clone(), segment.end.clone(),
-                    color, segment.width
-                );
-            }
+    /// Reload the lsystem from the file it is associated with, asking the user for confirmation
+    /// first if there are unsaved changes. Does nothing if there is no such file.
+    pub fn reload_file(&mut self) {
+        if self.file_path.is_none() {
+            return;
+        }
 
-            mesh = Mesh::new(PrimitiveType::Lines, mat, &geom)
+        if self.dirty {
+            self.pending_reload = true;
+        } else {
+            self.execute_reload();
         }
+    }
 
-        mesh
+    /// Reload the lsystem from the file it is associated with without asking for confirmation.
+    pub fn execute_reload(&mut self) {
+        if let Some(path) = self.file_path.clone() {
+            self.load_file(&path);
+        }
     }
 
-    fn retrieve_polygon_meshes(lsystem: &LSystem, params: &LSystemParameters, settings: &ApplicationSettings) -> Vec<Mesh> {
-        let mut meshes = Vec::new();
+    /// Check whether the associated file was modified by another program, and reload it if that is the case.
+    /// The check is only done once per second, and only if automatic reloading is enabled.
+    fn poll_file_changes(&mut self) {
+        if !self.app_settings.auto_reload_file || self.last_file_check.elapsed() < Duration::from_secs(1) {
+            return;
+        }
 
-        let mut combined_geometry = BasicIndexedGeometry::new();
+        self.last_file_check = Instant::now();
 
-        for polygon in &lsystem.drawing_result.polygons {
+        if let Some(path) = &self.file_path {
+            let modified = Self::modification_time(path);
+
+            if modified.is_some() && modified != self.file_modified {
+                // Remember the new time right away, so that the user is only asked once per change
+                self.file_modified = modified;
+                self.reload_file();
+            }
+        }
+    }
+
+    /// Advance the animation clock by the time elapsed since the last frame. The step is limited, so animations
+    /// do not jump ahead after the application stalled or this scene was inactive for a while.
+    fn advance_animation_clock(&mut self) {
+        const MAX_STEP: f64 = 0.1;
+
+        let delta = (self.current_time - self.last_time).max(0.0).min(MAX_STEP);
+        self.last_time = self.current_time;
+        self.animation_clock += delta;
+    }
+
+    /// Write the application settings to the settings file if they were changed since they were last saved.
+    /// The check is only done once per second, to avoid writing the file repeatedly while a slider is dragged.
+    fn poll_settings_changes(&mut self) {
+        if self.last_settings_check.elapsed() < Duration::from_secs(1) {
+            return;
+        }
+
+        self.last_settings_check = Instant::now();
+        self.save_settings();
+    }
+
+    /// Write the application settings to the settings file, if they were changed since they were last saved.
+    fn save_settings(&mut self) {
+        if self.app_settings != self.saved_settings {
+            // Failing to persist the settings is not worth interrupting the user with a popup. The
+            // settings are not retried until they change again.
+            if let Err(e) = self.app_settings.save() {
+                eprintln!("{}", e);
+            }
+
+            self.saved_settings = self.app_settings.clone();
+        }
+    }
+
+    /// Retrieve the modification time of given file, if it can be determined.
+    fn modification_time(path: &str) -> Option<SystemTime> {
+        metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    /// Save lsystem parameters to the file they were loaded from or last saved to. If there is
+    /// no such file yet, the user is asked for one.
+    pub fn save_file(&mut self) {
+        match self.file_path.clone() {
+            Some(path) => self.write_file(&path),
+            None => self.save_file_as()
+        }
+    }
+
+    /// Ask the user for a file and save the lsystem parameters to it.
+    pub fn save_file_as(&mut self) {
+        let result = nfd::open_save_dialog(Some("json"), None);
+
+        if let Some(path) = self.picked_path(result) {
+            self.write_file(&path);
+        }
+    }
+
+    /// Create or drop the offscreen render target according to the application settings. The target is
+    /// recreated if its resolution or color encoding changed.
+    fn refresh_render_target(&mut self) {
+        if !self.app_settings.fixed_render_target {
+            if self.render_target.take().is_some() {
+                self.refresh_line_screen_dimensions();
+            }
+
+            return;
+        }
+
+        let (width, height) = (self.app_settings.render_target_width, self.app_settings.render_target_height);
+        let srgb = self.app_settings.srgb;
+
+        if self.render_target.as_ref().map_or(false, |t| t.matches(width, height, srgb)) {
+            return;
+        }
+
+        match Framebuffer::new(width, height, srgb) {
+            Ok(target) => self.render_target = Some(target),
+            Err(e) => {
+                self.render_target = None;
+                self.app_settings.fixed_render_target = false;
+                self.report_error(&format!("Could not create render target: {}", e));
+            }
+        }
+
+        self.refresh_line_screen_dimensions();
+    }
+
+    /// Retrieve the size of the image the scene is rendered to. This is the window size, unless a fixed render
+    /// target is used.
+    fn view_size(&self) -> (u32, u32) {
+        match &self.render_target {
+            Some(target) => target.size(),
+            None => (self.width, self.height)
+        }
+    }
+
+    /// Pass the size of the rendered image to the material of the lines mesh, which needs accurate viewport
+    /// dimensions to function correctly.
+    fn refresh_line_screen_dimensions(&mut self) {
+        let dimensions = self.view_size();
+
+        if let Some(line_mat) = self.lines_mesh.try_retrieve_material_mut_ref::<Line2DMaterial>() {
+            line_mat.screen_dimensions = dimensions;
+        }
+    }
+
+    /// Retrieve the camera the scene is shown with. With a fixed render target, this is the user camera adjusted
+    /// to the aspect ratio of the target.
+    pub fn view_camera(&self) -> Camera {
+        let mut camera = self.camera.clone();
+
+        if let Some(target) = &self.render_target {
+            let (width, height) = target.size();
+            camera.update(width, height);
+        }
+
+        camera
+    }
+
+    /// Convert given window position to a position in the image shown on screen, which differs from the window
+    /// position if the scene is rendered into a fixed render target. Returns None if the position lies on the
+    /// bars around the letterboxed image.
+    pub fn to_view_position(&self, x: u32, y: u32) -> Option<(u32, u32)> {
+        let target = match &self.render_target {
+            Some(target) => target,
+            None => return Some((x, y))
+        };
+
+        let (width, height) = target.size();
+        let (bx, by, bw, bh) = letterbox((width, height), (self.width, self.height));
+
+        if x < bx || y < by || x >= bx + bw || y >= by + bh {
+            return None;
+        }
+
+        let tx = (x - bx) as f64 * width as f64 / bw as f64;
+        let ty = (y - by) as f64 * height as f64 / bh as f64;
+
+        Some((tx as _, ty as _))
+    }
+
+    /// Render the scene as seen by given camera into an offscreen image of given size.
+    pub fn capture(&self, camera: &Camera, width: u32, height: u32) -> Result<RgbaImage, String> {
+        let framebuffer = Framebuffer::new(width, height, self.app_settings.srgb)?;
+
+        // The window viewport is given in framebuffer pixels, which can differ from the screen size
+        let (viewport_width, viewport_height) = Viewport::current_size();
+
+        framebuffer.enable();
+
+        unsafe {
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
+
+        self.render_with_camera(camera);
+
+        let mut image = framebuffer.read_pixels();
+
+        framebuffer.disable();
+
+        unsafe {
+            gl::Viewport(0, 0, viewport_width as _, viewport_height as _);
+        }
+
+        // Blending of translucent surfaces also modifies the alpha channel, which is not wanted in the
+        // exported image.
+        for pixel in image.pixels_mut() {
+            pixel.0[3] = 255;
+        }
+
+        Ok(image)
+    }
+
+    /// Ask the user for a file and save a screenshot of the current view to it, in PNG format.
+    pub fn save_screenshot(&mut self) {
+        let result = nfd::open_save_dialog(Some("png"), None);
+
+        if let Some(path) = self.picked_path(result) {
+            // With a fixed render target, screenshots have its resolution instead of the window size
+            let (width, height) = match &self.render_target {
+                Some(target) => target.size(),
+                None => (self.width, self.height)
+            };
+
+            let image = self.capture(&self.view_camera(), width, height);
+
+            match image {
+                Ok(image) => {
+                    if let Err(e) = image.save(&path) {
+                        self.report_error(&format!("Could not write file \"{}\": {}", path, e));
+                    }
+                },
+                Err(e) => self.report_error(&format!("Could not capture screenshot: {}", e))
+            }
+        }
+    }
+
+    /// Ask the user for a file and export a turntable animation to it, as described by the current
+    /// turntable settings. The camera is rotated around the point it currently looks at.
+    pub fn export_turntable(&mut self) {
+        let extension = match self.turntable_settings.format {
+            TurntableFormat::Gif => "gif",
+            TurntableFormat::PngSequence => "png"
+        };
+
+        let result = nfd::open_save_dialog(Some(extension), None);
+
+        if let Some(path) = self.picked_path(result) {
+            let settings = self.turntable_settings;
+
+            let mut camera = self.camera.clone();
+            camera.update(settings.width, settings.height);
+
+            let (phi, theta) = camera.orientation();
+            let mut frames = Vec::with_capacity(settings.frames as _);
+
+            for i in 0..settings.frames {
+                let angle = two_pi::<f64>() * (i as f64) / (settings.frames as f64);
+                camera.set_orientation(phi, theta + angle);
+
+                match self.capture(&camera, settings.width, settings.height) {
+                    Ok(frame) => frames.push(frame),
+                    Err(e) => {
+                        self.report_error(&format!("Could not capture turntable frame: {}", e));
+                        return;
+                    }
+                }
+            }
+
+            let written = match settings.format {
+                // A full revolution takes four seconds, regardless of the frame count
+                TurntableFormat::Gif => write_gif(&path, frames, (400 / settings.frames).max(2) as _),
+                TurntableFormat::PngSequence => write_png_sequence(&path, frames)
+            };
+
+            if let Err(e) = written {
+                self.report_error(&e);
+            }
+        }
+    }
+
+    /// Retrieve the begin and end points of all line segments, projected onto the XY plane.
+    pub fn export_points_2d(&self) -> FlattenedPoints {
+        let segments = &self.lsystem.drawing_result.line_segments;
+
+        let mut points = Vec::with_capacity(segments.len() * 2);
+        let mut depths = Vec::with_capacity(segments.len() * 2);
+
+        for segment in segments {
+            points.push((segment.begin.x, segment.begin.y));
+            points.push((segment.end.x, segment.end.y));
+            depths.push(segment.begin.z);
+            depths.push(segment.end.z);
+        }
+
+        let z_variance = if depths.is_empty() {
+            0.0
+        } else {
+            let count = depths.len() as f32;
+            let mean = depths.iter().sum::<f32>() / count;
+            depths.iter().map(|z| (z - mean) * (z - mean)).sum::<f32>() / count
+        };
+
+        FlattenedPoints { points, z_variance }
+    }
+
+    /// Retrieve the line segments and polygons of the lsystem, as exported to OBJ files.
+    pub fn export_obj_geometry(&self) -> ObjGeometry {
+        let result = &self.lsystem.drawing_result;
+
+        // Line segments with a color index outside of the palette are drawn with the last entry
+        let max_color = (self.lsystem.parameters.color_palette_size as usize).max(1) - 1;
+
+        let lines = result.line_segments.iter()
+            .map(|segment| (segment.begin.clone(), segment.end.clone(), (segment.color as usize).min(max_color)))
+            .collect();
+
+        let polygons = (0..result.polygons.len())
+            .map(|i| (self.polygon_vertices(i), self.polygon_color(i)))
+            .collect();
+
+        ObjGeometry { lines, polygons }
+    }
+
+    /// Ask the user for a file and export the lsystem geometry to it as Wavefront OBJ. If requested
+    /// in the export dialog, one file is written per color index.
+    pub fn export_obj(&mut self) {
+        let result = nfd::open_save_dialog(Some("obj"), None);
+
+        if let Some(path) = self.picked_path(result) {
+            if let Err(e) = write_obj(&path, &self.export_obj_geometry(), self.obj_split_by_color) {
+                self.report_error(&e);
+            }
+        }
+    }
+
+    /// Ask the user for a file and export the line segment end points to it as CSV, flattened
+    /// onto the XY plane. The user is warned if the lsystem was not planar.
+    pub fn export_csv(&mut self) {
+        let result = nfd::open_save_dialog(Some("csv"), None);
+
+        if let Some(path) = self.picked_path(result) {
+            let points = self.export_points_2d();
+
+            if let Err(e) = write_points_csv(&path, &points) {
+                self.report_error(&e);
+            } else if points.is_lossy() {
+                self.report_error(&format!(
+                    "The L-System is not planar (Z variance {:.6}). The exported points were flattened onto the XY plane,\n\
+                     so depth information was lost.",
+                    points.z_variance
+                ));
+            }
+        }
+    }
+
+    /// Ask the user for a file and save all bezier models of the lsystem to it as patch library.
+    pub fn export_patch_library(&mut self) {
+        let result = nfd::open_save_dialog(Some("json"), None);
+
+        if let Some(mut path) = self.picked_path(result) {
+            if !path.to_lowercase().ends_with(".json") {
+                path.push_str(".patches.json");
+            }
+
+            if let Err(e) = save_patch_library(&path, &self.lsystem_params.bezier_models) {
+                self.report_error(&e);
+            }
+        }
+    }
+
+    /// Ask the user for a patch library file and append all bezier models stored in it to the lsystem.
+    /// Imported models whose symbol is already used by an existing model lose their symbol, just like clones.
+    pub fn import_patch_library(&mut self) {
+        let result = nfd::open_file_dialog(Some("json"), None);
+
+        if let Some(path) = self.picked_path(result) {
+            match load_patch_library(&path) {
+                Ok(models) => {
+                    for mut model in models {
+                        let taken = self.lsystem_params.bezier_models.iter()
+                            .any(|other| other.symbol.is_some() && other.symbol == model.symbol);
+
+                        if taken {
+                            model.symbol = None;
+                        }
+
+                        if model.symbol.is_some() {
+                            self.bezier_manager.update_meshes(&model);
+                        }
+
+                        self.lsystem_params.bezier_models.push(model);
+                    }
+
+                    self.mark_dirty();
+                },
+                Err(e) => self.report_error(&e)
+            }
+        }
+    }
+
+    /// Write lsystem parameters to given file.
+    fn write_file(&mut self, path: &str) {
+        let json = self.save();
+
+        match write(path, &json) {
+            Ok(_) => {
+                self.file_path = Some(path.to_string());
+                self.file_modified = Self::modification_time(path);
+                self.dirty = false;
+            },
+            Err(e) => self.report_error(&format!("Could not write file \"{}\": {}", path, e))
+        }
+    }
+
+    /// Save lsystem parameters to JSON string.
+    pub fn save(&mut self) -> String {
+        to_string_pretty(&self.lsystem_params).unwrap()
+    }
+
+    /// Build the mesh for the line segments of given lsystem. Fails if there are more line segments than allowed
+    /// by the application settings, since the vertex data could otherwise exhaust the available memory.
+    fn retrieve_line_mesh(lsystem: &LSystem, params: &LSystemParameters, settings: &ApplicationSettings, generations: Option<&SegmentGenerations>, window: Option<(u32, u32)>, screen_dims: (u32, u32)) -> Result<Mesh, String> {
+        let segment_count = lsystem.drawing_result.line_segments.len();
+
+        if segment_count > settings.max_line_segments as usize {
+            return Err(format!(
+                "The L-System consists of {} line segments, which exceeds the limit of {}. The line mesh was not rebuilt.\n\
+                 Consider lowering the iteration depth, or raise the limit in the application settings.",
+                segment_count, settings.max_line_segments
+            ));
+        }
+
+        let mat: Box<dyn Material> = match params.line_draw_mode {
+            LineDrawMode::Basic => Box::new(SimpleMaterial::new()),
+            LineDrawMode::Advanced2D => Box::new(Line2DMaterial::new(screen_dims, settings.line_join_style)),
+            LineDrawMode::Advanced3D => Box::new(Line3DMaterial::new(settings.tube_radius_scale)),
+            LineDrawMode::Billboard => Box::new(LineBillboardMaterial::new(settings.billboard_width_scale))
+        };
+
+        // Segments outside of the generation window are skipped, if the generations are known
+        let visible = |i: usize| match (generations, window) {
+            (Some(generations), Some(range)) => generations.in_range(i, range),
+            _ => true
+        };
+
+        // Handle legacy lines
+        let mesh: Mesh;
+
+        if let LineDrawMode::Basic = params.line_draw_mode {
+            // Buffer for line vertices
+            let mut vertices = Vec::new();
+
+            for (i, segment) in lsystem.drawing_result.line_segments.iter().enumerate() {
+                if !visible(i) {
+                    continue;
+                }
+
+                let color = Self::segment_color(lsystem, params, settings, generations, i);
+
+                let begin = Vertex::new(segment.begin.clone(), color);
+                let end = Vertex::new(segment.end.clone(), color);
+        
+                vertices.push(begin);
+                vertices.push(end);
+            }
+
+            if settings.indexed_lines {
+                let geometry = BasicIndexedGeometry::from_line_vertices(&vertices);
+
+                mesh = Mesh::new_indexed(PrimitiveType::Lines, mat, &geometry)
+            } else {
+                mesh = Mesh::new(PrimitiveType::Lines, mat, &BasicGeometry::from_vertices(&vertices))
+            }
+        } else {
+            // Line geometry
+            let mut geom = LineGeometry::new();
+
+            for (i, segment) in lsystem.drawing_result.line_segments.iter().enumerate() {
+                if !visible(i) {
+                    continue;
+                }
+
+                let color = Self::segment_color(lsystem, params, settings, generations, i);
+
+                // Each segment keeps the width the turtle had when drawing it. Repeatedly decrementing
+                // the width can make it negative, which would flip the generated geometry.
+                geom.add_segment(
+                    segment.begin.clone(), segment.end.clone(),
+                    color, segment.width.max(0.0)
+                );
+            }
+
+            // 2D lines need adjacency information in order to join consecutive segments
+            if let LineDrawMode::Advanced2D = params.line_draw_mode {
+                geom.build_adjacency();
+                mesh = Mesh::new_indexed(PrimitiveType::LinesAdjacency, mat, &geom)
+            } else {
+                mesh = Mesh::new(PrimitiveType::Lines, mat, &geom)
+            }
+        }
+
+        Ok(mesh)
+    }
+
+    /// Determine the generations of the line segments, which are only needed for the generation gradient and
+    /// the generation window. Replaying the expansion is not free, so nothing is done if both are disabled.
+    fn retrieve_segment_generations(lsystem: &LSystem, params: &LSystemParameters, settings: &ApplicationSettings, window: Option<(u32, u32)>) -> Option<SegmentGenerations> {
+        if settings.generation_gradient || window.is_some() {
+            Some(segment_generations(lsystem, params))
+        } else {
+            None
+        }
+    }
+
+    /// Determine the color of the line segment with given index. This is either its color palette entry, or
+    /// the generation gradient color if the gradient is enabled and the segment generations are given.
+    fn segment_color(lsystem: &LSystem, params: &LSystemParameters, settings: &ApplicationSettings, generations: Option<&SegmentGenerations>, index: usize) -> Vec3 {
+        if settings.generation_gradient {
+            if let Some(generations) = generations {
+                return lerp(&settings.generation_gradient_start, &settings.generation_gradient_end, generations.fractions[index]);
+            }
+        }
+
+        let segment = &lsystem.drawing_result.line_segments[index];
+
+        // Lookup color
+        let color_index = if segment.color >= lsystem.parameters.color_palette_size as _ { 
+            lsystem.parameters.color_palette_size - 1
+        } else {
+            segment.color as _
+        };
+
+        if params.color_palette.len() == 0 {
+            Vec3::repeat(1.0)
+        } else {
+            params.color_palette[color_index as usize]
+        }
+    }
+
+    /// Create a mesh of round points at the begin and end positions of all line segments, which are the
+    /// positions the turtle moved through. Returns None if this debug feature is disabled.
+    fn retrieve_node_points_mesh(lsystem: &LSystem, settings: &ApplicationSettings) -> Option<Mesh> {
+        if !settings.show_node_points || lsystem.drawing_result.line_segments.is_empty() {
+            return None;
+        }
+
+        let color = Vec3::new(1.0, 0.5, 0.0);
+        let mut vertices = Vec::new();
+
+        for segment in &lsystem.drawing_result.line_segments {
+            vertices.push(Vertex::new(segment.begin.clone(), color));
+            vertices.push(Vertex::new(segment.end.clone(), color));
+        }
+
+        let mut mesh = Mesh::new(PrimitiveType::Points, Box::new(PointMaterial::new(6.0)), &BasicGeometry::from_vertices(&vertices));
+        mesh.program_point_size = true;
+
+        Some(mesh)
+    }
+
+    /// Create the mesh showing the turtle frames at all save and restore commands, if enabled.
+    fn retrieve_turtle_frames_mesh(lsystem: &LSystem, params: &LSystemParameters, settings: &ApplicationSettings) -> Option<Mesh> {
+        if !settings.show_turtle_frames {
+            return None;
+        }
+
+        let frames = collect_turtle_frames(
+            lsystem.iteration_result.iter().map(|module| module.identifier),
+            &params.interpretations,
+            &lsystem.parameters
+        );
+
+        if frames.is_empty() {
+            return None;
+        }
+
+        Some(create_turtle_frames_mesh(&frames, lsystem.parameters.step as f32 * 0.5))
+    }
+
+    /// Create the polygon meshes from the interpreted lsystem. Polygons whose color palette entry is not
+    /// fully opaque are grouped by color and returned separately, since they require sorting.
+    fn retrieve_polygon_meshes(lsystem: &LSystem, params: &LSystemParameters, settings: &ApplicationSettings) -> (Vec<Mesh>, Vec<TranslucentMesh>) {
+        let mut meshes = Vec::new();
+
+        let mut combined_geometry = BasicIndexedGeometry::new();
+        let mut translucent_geometries: BTreeMap<usize, BasicIndexedGeometry> = BTreeMap::new();
+
+        for polygon in &lsystem.drawing_result.polygons {
             let color = if params.color_palette.len() > 0 {
                 params.color_palette[polygon.color as usize]
             } else {
@@ -431,40 +1812,104 @@ impl LSystemScene {
             }
 
             
-            let geometry = BasicGeometry::with_auto_normals(PrimitiveType::TriangleFan, &vertices);
+            let geometry = match params.polygon_shading {
+                PolygonShading::Smooth => BasicGeometry::with_auto_normals(PrimitiveType::TriangleFan, &vertices),
+                PolygonShading::Flat => BasicGeometry::with_flat_normals(PrimitiveType::TriangleFan, &vertices)
+            };
             
-            combined_geometry.merge_into(&geometry, 0xFFFFFFFFu32);
+            if params.color_opacity(polygon.color as usize) < 1.0 {
+                translucent_geometries.entry(polygon.color as usize)
+                    .or_insert_with(BasicIndexedGeometry::new)
+                    .merge_into(&geometry, 0xFFFFFFFFu32);
+            } else {
+                combined_geometry.merge_into(&geometry, 0xFFFFFFFFu32);
+            }
 
             if settings.show_normals {
-                let mat = Box::new(NormalTestMaterial::new((params.drawing_parameters.step/2.0) as _, &Vec3::new(1.0, 1.0, 0.0)));
+                let mat = Box::new(NormalTestMaterial::new(settings.normal_debug_length, &Vec3::new(1.0, 1.0, 0.0)));
                 let mut mesh = Mesh::new(PrimitiveType::TriangleStrip, mat, &geometry);
-                mesh.draw_wireframe = settings.draw_wireframe;
+                mesh.wireframe_mode = settings.wireframe_mode;
                 meshes.push(mesh);
             }
         }
 
-        let mat = Box::new(ShadedMaterial::new());
-        let mut mesh = Mesh::new_indexed(PrimitiveType::TriangleFan, mat, &combined_geometry);
+        // Back faces are drawn in magenta, which is unlikely to be part of the color palette
+        let surface_material = |opacity: f32| -> Box<dyn Material> {
+            if settings.two_sided_debug {
+                Box::new(BackfaceTestMaterial::new(&Vec3::new(1.0, 0.0, 1.0)))
+            } else {
+                let mut mat = Box::new(ShadedMaterial::new());
+                mat.opacity = opacity;
+                mat
+            }
+        };
+
+        // Flat shaded polygons are made out of unconnected triangles
+        let primitive_type = match params.polygon_shading {
+            PolygonShading::Smooth => PrimitiveType::TriangleFan,
+            PolygonShading::Flat => PrimitiveType::Triangles
+        };
+
+        let mat = surface_material(1.0);
+        let mut mesh = Mesh::new_indexed(primitive_type, mat, &combined_geometry);
         mesh.primitive_restart_index = Some(0xFFFFFFFFu32);
-        mesh.draw_wireframe = settings.draw_wireframe;
+        mesh.wireframe_mode = settings.wireframe_mode;
+        mesh.polygon_offset = settings.polygon_offset();
         meshes.push(mesh);
 
-        meshes
+        let mut translucent_meshes = Vec::new();
+
+        for (color_index, geometry) in &translucent_geometries {
+            let positions = &geometry.positions.local_buffer;
+            let center = positions.iter().fold(Vec3::zeros(), |acc, p| acc + p) / (positions.len() as f32);
+
+            let mat = surface_material(params.color_opacity(*color_index));
+
+            let mut mesh = Mesh::new_indexed(primitive_type, mat, geometry);
+            mesh.primitive_restart_index = Some(0xFFFFFFFFu32);
+            mesh.wireframe_mode = settings.wireframe_mode;
+            mesh.polygon_offset = settings.polygon_offset();
+
+            translucent_meshes.push(TranslucentMesh{ mesh, center });
+        }
+
+        (meshes, translucent_meshes)
     }
-}
 
+    /// Render the scene as seen by given camera.
+    fn render_with_camera(&self, camera: &Camera) {
+        let mut params = camera.to_render_parameters();
+        params.scale(self.render_scale);
+        params.fog = self.app_settings.fog_settings();
+        params.lighting.normal_darkening = self.app_settings.normal_darkening();
 
-impl Scene for LSystemScene {
-    /// Render scene to screen. This also includes any GUI components.
-    fn render(&self) {
-        let mut params = self.camera.to_render_parameters();
+        // The GUI is not gamma-correct, so conversion to sRGB is only enabled while drawing the lsystem
+        if self.app_settings.srgb {
+            params.color_gamma = 2.2;
+
+            unsafe {
+                gl::Enable(gl::FRAMEBUFFER_SRGB);
+            }
+        }
 
         self.lines_mesh.render(&mut params);
 
+        if let Some(mesh) = &self.node_points_mesh {
+            mesh.render(&mut params);
+        }
+
+        if let Some(mesh) = &self.turtle_frames_mesh {
+            mesh.render(&mut params);
+        }
+
         for mesh in &self.polygon_meshes {
             mesh.render(&mut params);
         }
 
+        if let Some(mesh) = &self.selected_polygon_mesh {
+            mesh.render(&mut params);
+        }
+
         for model in &self.bezier_models {
             model.render(&mut params);
         }
@@ -473,47 +1918,204 @@ impl Scene for LSystemScene {
             if self.app_settings.draw_bounding_box {
                 bb.render(&mut params);
             }
+
+            if self.app_settings.show_bounding_sphere {
+                bb.render_sphere(&mut params);
+            }
+        }
+
+        // Translucent polygons have to be drawn last, from back to front. They do not write
+        // to the depth buffer to avoid hiding other translucent surfaces behind them.
+        if !self.translucent_meshes.is_empty() {
+            let camera_position = params.camera_position;
+            let scale = self.render_scale;
+            let distance = |m: &TranslucentMesh| (m.center * scale - camera_position).norm();
+
+            let mut sorted: Vec<&TranslucentMesh> = self.translucent_meshes.iter().collect();
+            sorted.sort_by(|a, b| distance(b).partial_cmp(&distance(a)).unwrap_or(std::cmp::Ordering::Equal));
+
+            unsafe {
+                gl::DepthMask(gl::FALSE);
+            }
+
+            for translucent in sorted {
+                translucent.mesh.render(&mut params);
+            }
+
+            unsafe {
+                gl::DepthMask(gl::TRUE);
+            }
+        }
+
+        unsafe {
+            gl::Disable(gl::FRAMEBUFFER_SRGB);
         }
     }
+}
 
-    /// Perform logic. Currently, this means checking if a BezierEditorScene just ended, which would mean
-    /// that the modified model has to be applied to the parameters of the current lsystem.
-    fn do_logic(&mut self) {
 
-        let mut should_clear = false;
+impl Scene for LSystemScene {
+    /// Render scene to screen. This also includes any GUI components.
+    fn render(&self) {
+        match &self.render_target {
+            Some(target) => {
+                let viewport = Viewport::current_size();
 
-        if let Some((i, r)) = &self.model_to_refresh {
-            // Retrieve the new model parameters
-            let parameters = r.borrow().clone();
+                target.enable();
 
-            // Recreate mesh
-            self.bezier_manager.update_meshes(&parameters);
+                unsafe {
+                    gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+                }
 
-            // Store it for later
-            self.lsystem_params.bezier_models[*i] = parameters;
+                self.render_with_camera(&self.view_camera());
 
-            // We now need to refresh bezier models.
-            self.refresh_bezier_models();
+                target.disable();
 
-            should_clear = true;
+                // The bars around the letterboxed image are cleared to black, which makes the frame visible
+                unsafe {
+                    let mut background: [f32; 4] = [0.0; 4];
+                    gl::GetFloatv(gl::COLOR_CLEAR_VALUE, background.as_mut_ptr());
+
+                    gl::Viewport(0, 0, viewport.0 as _, viewport.1 as _);
+                    gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+                    gl::Clear(gl::COLOR_BUFFER_BIT);
+                    gl::ClearColor(background[0], background[1], background[2], background[3]);
+                }
+
+                target.blit_to_screen(letterbox(target.size(), viewport));
+            },
+            None => self.render_with_camera(&self.camera)
         }
+    }
 
-        if should_clear {
-            // Clear it, so that we don't to the refreshing again next frame.
-            self.model_to_refresh = None
+    /// Perform logic. Currently, this means advancing the animation clock, picking up the results of finished
+    /// background iterations and bezier mesh generations, checking whether the associated file was modified on
+    /// disk, persisting changed application settings, keeping the fixed render target up to date, and checking
+    /// whether a BezierEditorScene applied or saved changes to the bezier model it is editing.
+    fn do_logic(&mut self) {
+        self.advance_animation_clock();
+        self.poll_iteration();
+        self.poll_file_changes();
+        self.poll_settings_changes();
+        self.refresh_render_target();
+
+        if self.bezier_manager.poll_pending() {
+            self.refresh_bezier_models();
+        }
+
+        self.poll_bezier_edit();
+    }
+
+    /// Keep the bezier models up to date while a bezier editor is shown on top of this scene.
+    fn do_background_logic(&mut self) {
+        self.poll_bezier_edit();
+
+        if self.bezier_manager.poll_pending() {
+            self.refresh_bezier_models();
         }
     }
 
     /// Show imgui GUI if needed.
     fn do_gui(&mut self, ui: &Ui) -> SceneAction {
         ui.show_demo_window(&mut true);
+        self.frame_times.record(ui.io().delta_time);
         gui::do_main_menu_bar(ui, self);
-        gui::do_lsystem_params_gui(ui, self)  
+        gui::do_error_popup(ui, self);
+        gui::do_iteration_progress(ui, self);
+        gui::do_toast(ui, self);
+        gui::do_command_palette(ui, self);
+        gui::do_unsaved_changes_popup(ui, self);
+        gui::do_reload_popup(ui, self);
+        gui::do_turntable_dialog(ui, self);
+        gui::do_obj_dialog(ui, self);
+
+        if self.camera.locked() {
+            camera_lock_indicator(ui, self.width);
+        }
+
+        let mut action = SceneAction::Nothing;
+
+        if self.gui_visible {
+            gui::do_symbol_legend(ui, self);
+            gui::do_bounding_box_dimensions(ui, self);
+            gui::do_polygon_inspector(ui, self);
+
+            if self.app_settings.show_frame_times {
+                gui::do_debug_gui(ui, self);
+            }
+
+            // The depth buffer of the window does not contain the scene when a fixed render target is used
+            if self.app_settings.show_cursor_position && self.render_target.is_none() {
+                cursor_position_tooltip(ui, &self.camera, self.render_scale);
+            }
+            action = gui::do_lsystem_params_gui(ui, self);
+        }
+
+        // The default layout only has to be forced for a single frame
+        self.reset_layout = false;
+
+        if self.quit_requested {
+            self.quit_requested = false;
+            return SceneAction::Quit;
+        }
+
+        if self.close_document_requested {
+            self.close_document_requested = false;
+            return SceneAction::CloseDocument;
+        }
+
+        if let Some(params) = self.new_document.take() {
+            let scene = LSystemScene::new(&params, &self.app_settings, self.width, self.height);
+            return SceneAction::OpenDocument(make_rc_cell(scene));
+        }
+
+        // Only one action can be emitted per frame. If there already is one, the vsync mode is applied next frame.
+        if self.vsync_changed {
+            if let SceneAction::Nothing = action {
+                self.vsync_changed = false;
+                return SceneAction::SetVSyncMode(self.app_settings.vsync_mode);
+            }
+        }
+
+        action
     }
 
     /// Handle input event. This is only called if the UI does not want to grab input.
     fn handle_event(&mut self, window: &Window, event: &WindowEvent) {
-        self.camera.handle_event(window, event);
+        match event {
+            WindowEvent::Key(Key::N, _, Action::Press, modifiers) if modifiers.contains(Modifiers::Control) => {
+                self.perform_discarding_action(DiscardingAction::New);
+            },
+            WindowEvent::Key(Key::O, _, Action::Press, modifiers) if modifiers.contains(Modifiers::Control) => {
+                self.perform_discarding_action(DiscardingAction::Open);
+            },
+            WindowEvent::Key(Key::S, _, Action::Press, modifiers) if modifiers.contains(Modifiers::Control) => {
+                if modifiers.contains(Modifiers::Shift) {
+                    self.save_file_as();
+                } else {
+                    self.save_file();
+                }
+            },
+            WindowEvent::Key(Key::R, _, Action::Press, modifiers) if modifiers.contains(Modifiers::Control | Modifiers::Shift) => {
+                self.toggle_auto_refresh();
+            },
+            WindowEvent::MouseButton(glfw::MouseButton::Button1, Action::Press, modifiers) if modifiers.contains(Modifiers::Control) => {
+                let (x, y) = window.get_cursor_pos();
+                self.pick_polygon(x as _, y as _);
+            },
+            WindowEvent::Key(Key::P, _, Action::Press, modifiers) if modifiers.contains(Modifiers::Control) => {
+                self.open_command_palette = true;
+            },
+            WindowEvent::Key(Key::L, _, Action::Press, _) => {
+                self.camera.set_locked(!self.camera.locked());
+            },
+            WindowEvent::Key(key, _, Action::Press, modifiers) => {
+                if let Some(view) = AxisView::from_key(*key, *modifiers) {
+                    self.camera.snap_to_axis(view);
+                }
+            },
+            _ => self.camera.handle_event(window, event)
+        }
     }
 
     /// Handle window resize event.
@@ -523,10 +2125,47 @@ impl Scene for LSystemScene {
         self.width = w;
         self.height = h;
 
-        // The material in the lines mesh needs accurate viewport dimensions to function correctly
-        if let LineDrawMode::Advanced2D = self.lsystem_params.line_draw_mode {
-            let mut line_mat = self.lines_mesh.retrieve_material_mut_ref::<Line2DMaterial>();
-            line_mat.screen_dimensions = (w, h);
+        self.refresh_line_screen_dimensions();
+    }
+
+    /// Show or hide the lsystem parameters window and overlays. The main menu bar stays visible.
+    fn set_gui_visible(&mut self, visible: bool) {
+        self.gui_visible = visible;
+    }
+
+    fn set_time(&mut self, time: f64) {
+        self.current_time = time;
+    }
+
+    /// Ask the user for confirmation before closing if there are unsaved changes.
+    fn request_close(&mut self) -> bool {
+        if self.dirty {
+            self.pending_discard = Some(DiscardingAction::Quit);
+            false
+        } else {
+            true
+        }
+    }
+
+    /// Ask the user for confirmation before closing the document if there are unsaved changes.
+    fn request_close_document(&mut self) -> bool {
+        if self.dirty {
+            self.pending_discard = Some(DiscardingAction::CloseDocument);
+            false
+        } else {
+            true
         }
     }
-}
\ No newline at end of file
+
+    /// The document is named after the lsystem, with an asterisk marking unsaved changes.
+    fn title(&self) -> Option<String> {
+        Some(format!("{}{}", self.lsystem_params.name, if self.dirty { "*" } else { "" }))
+    }
+}
+
+impl Drop for LSystemScene {
+    /// Make sure settings changes made right before quitting are not lost.
+    fn drop(&mut self) {
+        self.save_settings();
+    }
+}
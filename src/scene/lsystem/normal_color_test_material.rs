@@ -28,12 +28,16 @@ impl NormalColorTestMaterial {
 }
 
 impl Material for NormalColorTestMaterial {
-    fn enable_material(&self, params: &mut RenderParameters) {
+    fn enable_material(&self, params: &mut RenderParameters) -> Result<(), RenderError> {
         self.program.use_program();
 
-        self.program.set_uniform_mat4("projection", &params.projection);
-        self.program.set_uniform_mat4("view", &params.view);
-        self.program.set_uniform_mat4("model", &params.model);
+        self.program.report_uniform_errors(&[
+            self.program.set_uniform_mat4("projection", &params.projection),
+            self.program.set_uniform_mat4("view", &params.view),
+            self.program.set_uniform_mat4("model", &params.model)
+        ]);
+
+        Ok(())
     }
 
     /// Retrieve this instance as a reference to Any. This is used for downcasting.
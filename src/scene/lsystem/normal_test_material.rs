@@ -36,15 +36,19 @@ impl NormalTestMaterial {
 }
 
 impl Material for NormalTestMaterial {
-    fn enable_material(&self, params: &mut RenderParameters) {
+    fn enable_material(&self, params: &mut RenderParameters) -> Result<(), RenderError> {
         self.program.use_program();
 
-        self.program.set_uniform_mat4("projection", &params.projection);
-        self.program.set_uniform_mat4("view", &params.view);
-        self.program.set_uniform_mat4("model", &params.model);
+        self.program.report_uniform_errors(&[
+            self.program.set_uniform_mat4("projection", &params.projection),
+            self.program.set_uniform_mat4("view", &params.view),
+            self.program.set_uniform_mat4("model", &params.model),
 
-        self.program.set_uniform_vec3("normal_color", &self.normal_color);
-        self.program.set_uniform_float("normal_length", self.normal_length);
+            self.program.set_uniform_vec3("normal_color", &self.normal_color),
+            self.program.set_uniform_float("normal_length", self.normal_length)
+        ]);
+
+        Ok(())
     }
 
     /// Retrieve this instance as a reference to Any. This is used for downcasting.
@@ -1,26 +1,56 @@
 use std::rc::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::thread;
 use crate::rendering::bezier::*;
 use crate::rendering::meshes::*;
 use crate::rendering::materials::*;
 use crate::data::bezier::*;
+use crate::scene::lsystem::normal_color_test_material::*;
 
 
+/// A request to generate the patch geometries of a bezier model, tagged with a unique job id.
+type GeometryJob = (u64, BezierModelParameters);
+
+/// The patch geometries generated for the job with given id. The material of the model is sent along
+/// with its geometries, since materials can only be created on the main thread.
+type GeometryResult = (u64, BezierMaterial, Vec<BezierGeometry>);
 
 /// Type that manages the meshes for the various bezier models. This allows
 /// different patch instantiations to share the same mesh instance.
 /// This type maps bezier patch identifiers to the mesh collections and
 /// allows updating of those meshes as reaction to patch updates. 
+/// The patch geometries are generated on a background worker thread, since that can take a while for
+/// complex models. Only the upload to the GPU happens on the main thread, in `poll_pending`.
 pub struct BezierMeshManager {
     /// Mapping between patch model identifier and associated patch meshes
-    mesh_map: HashMap<char, Vec<Rc<Mesh>>>
+    mesh_map: HashMap<char, Vec<Rc<Mesh>>>,
+    /// The id of the most recent geometry job for each model identifier that did not finish yet.
+    /// Results of older jobs are discarded.
+    pending: HashMap<char, u64>,
+    /// The id the next geometry job will receive
+    next_job: u64,
+    /// Sends geometry jobs to the background worker
+    requests: Sender<GeometryJob>,
+    /// Receives the finished geometry jobs from the background worker
+    results: Receiver<GeometryResult>
 }
 
 impl BezierMeshManager {
-    /// Create a new bezier mesh manager instance.
+    /// Create a new bezier mesh manager instance. This starts the background worker, which stops once
+    /// the manager is dropped.
     pub fn new() -> BezierMeshManager {
+        let (request_sender, request_receiver) = channel();
+        let (result_sender, result_receiver) = channel();
+
+        thread::spawn(move || Self::run_worker(request_receiver, result_sender));
+
         BezierMeshManager {
-            mesh_map: HashMap::new()
+            mesh_map: HashMap::new(),
+            pending: HashMap::new(),
+            next_job: 0,
+            requests: request_sender,
+            results: result_receiver
         }
     }
 
@@ -36,21 +66,85 @@ impl BezierMeshManager {
     }
 
     /// Update stored patch meshes for bezier model with given identifier. Will create a new entry
-    /// if it does not already exist.
+    /// if it does not already exist. The meshes are generated in the background, and any existing
+    /// meshes for the identifier are kept until they are ready.
     pub fn update_meshes(&mut self, parameters: &BezierModelParameters) {
         // Ignore models that dont have any parameters set
         if let Some(identifier) = parameters.symbol {
-            // If there is already an entry for this identifier, remove that entry. Its obsolete.
-            if self.has_meshes(identifier) {
-                self.mesh_map.remove(&identifier);     
-            }
+            let job = self.next_job;
+            self.next_job += 1;
 
-            self.mesh_map.insert(identifier, Self::create_meshes(parameters));
+            // This replaces any older request for the same identifier
+            if self.requests.send((job, parameters.clone())).is_ok() {
+                self.pending.insert(identifier, job);
+            }
         }   
     }
 
+    /// The background worker, which generates the patch geometries for all received jobs in order.
+    /// Queued jobs are replaced by newer jobs for the same model, so that superseded geometry is never
+    /// generated.
+    fn run_worker(requests: Receiver<GeometryJob>, results: Sender<GeometryResult>) {
+        let mut queue: VecDeque<GeometryJob> = VecDeque::new();
+
+        loop {
+            // Block until there is work to do. The manager was dropped if the channel is closed.
+            if queue.is_empty() {
+                match requests.recv() {
+                    Ok(job) => queue.push_back(job),
+                    Err(_) => return
+                }
+            }
+
+            while let Ok(job) = requests.try_recv() {
+                queue.retain(|(_, parameters)| parameters.symbol != job.1.symbol);
+                queue.push_back(job);
+            }
+
+            let (job, parameters) = queue.pop_front().unwrap();
+            let geometries = Self::create_geometries(&parameters);
+
+            if results.send((job, parameters.material, geometries)).is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Upload all patch geometries that finished generating in the background. Returns whether
+    /// any meshes changed, in which case the bezier models of the scene have to be refreshed.
+    pub fn poll_pending(&mut self) -> bool {
+        let mut changed = false;
+
+        loop {
+            let (job, material, geometries) = match self.results.try_recv() {
+                Ok(result) => result,
+                // The worker only stops early if it panicked, in which case nothing is pending anymore
+                Err(TryRecvError::Disconnected) => {
+                    self.pending.clear();
+                    break;
+                },
+                Err(TryRecvError::Empty) => break
+            };
+
+            // Results of superseded jobs and removed models are discarded
+            let identifier = self.pending.iter()
+                .find(|(_, pending)| **pending == job)
+                .map(|(identifier, _)| *identifier);
+
+            if let Some(identifier) = identifier {
+                self.pending.remove(&identifier);
+                self.mesh_map.insert(identifier, Self::create_meshes(material, &geometries));
+                changed = true;
+            }
+        }
+
+        changed
+    }
+
     /// Remove meshes for given bezier model
     pub fn remove_meshes(&mut self, identifier: char) {
+        self.pending.remove(&identifier);
+
         if self.has_meshes(identifier) {
             self.mesh_map.remove(&identifier);
         }
@@ -58,6 +152,10 @@ impl BezierMeshManager {
 
     /// Rename bezier model. This avoids regenerating the mesh.
     pub fn rename_meshes(&mut self, old: char, new: char) {
+        if let Some(job) = self.pending.remove(&old) {
+            self.pending.insert(new, job);
+        }
+
         if self.has_meshes(old) {
             let meshes = self.mesh_map.remove(&old).unwrap();
             self.mesh_map.insert(new, meshes);
@@ -69,19 +167,29 @@ impl BezierMeshManager {
         self.mesh_map.contains_key(&identifier)
     }
 
-    /// Create the patch meshes for bezier model described by given parameters.
-    fn create_meshes(parameters: &BezierModelParameters) -> Vec<Rc<Mesh>> {
+    /// Generate the patch geometries for bezier model described by given parameters. This does not
+    /// require an OpenGL context and can thus happen on any thread.
+    fn create_geometries(parameters: &BezierModelParameters) -> Vec<BezierGeometry> {
+        parameters.patches.iter().map(|patch| BezierGeometry::new(patch, parameters.resolution, parameters.resolution, parameters.smoothing_angle)).collect()
+    }
+
+    /// Create the patch meshes from given patch geometries, using given material. This has to happen
+    /// on the main thread.
+    fn create_meshes(material: BezierMaterial, geometries: &[BezierGeometry]) -> Vec<Rc<Mesh>> {
         let mut meshes = Vec::new();
 
         // Create mesh for each patch
-        for patch in &parameters.patches {
-            let geometry = BezierGeometry::new(patch, 30, 30);
-            let material = Box::new(ShadedMaterial::new());
+        for geometry in geometries {
+            let mat: Box<dyn Material> = match material {
+                BezierMaterial::Flat => Box::new(SimpleMaterial::new()),
+                BezierMaterial::Shaded => Box::new(ShadedMaterial::new()),
+                BezierMaterial::NormalColor => Box::new(NormalColorTestMaterial::new())
+            };
 
             meshes.push(Rc::new(Mesh::new_indexed(
-                PrimitiveType::TriangleStrip,
-                material,
-                &geometry
+                geometry.primitive_type(),
+                mat,
+                geometry
             )));
         }
 
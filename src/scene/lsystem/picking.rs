@@ -0,0 +1,54 @@
+use nalgebra_glm::Vec3;
+
+/// Intersect the ray starting at given origin in given direction with the triangle spanned by given
+/// points, using the Möller-Trumbore algorithm. Returns the distance along the ray, in multiples of the
+/// direction vector, if the ray hits the triangle from either side.
+pub fn ray_triangle_intersection(origin: &Vec3, direction: &Vec3, a: &Vec3, b: &Vec3, c: &Vec3) -> Option<f32> {
+    const EPSILON: f32 = 1.0e-7;
+
+    let edge1 = b - a;
+    let edge2 = c - a;
+
+    let p = direction.cross(&edge2);
+    let determinant = edge1.dot(&p);
+
+    // The ray is parallel to the triangle, or the triangle is degenerated
+    if determinant.abs() < EPSILON {
+        return None;
+    }
+
+    let inverse = 1.0 / determinant;
+    let t = origin - a;
+
+    let u = t.dot(&p) * inverse;
+    if u < 0.0 || u > 1.0 {
+        return None;
+    }
+
+    let q = t.cross(&edge1);
+
+    let v = direction.dot(&q) * inverse;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let distance = edge2.dot(&q) * inverse;
+
+    if distance > 0.0 {
+        Some(distance)
+    } else {
+        None
+    }
+}
+
+/// Intersect given ray with a polygon, which is interpreted as triangle fan just like when it is rendered.
+/// Returns the distance to the closest hit, if any.
+pub fn ray_polygon_intersection(origin: &Vec3, direction: &Vec3, vertices: &[Vec3]) -> Option<f32> {
+    if vertices.len() < 3 {
+        return None;
+    }
+
+    (1..vertices.len() - 1)
+        .filter_map(|i| ray_triangle_intersection(origin, direction, &vertices[0], &vertices[i], &vertices[i + 1]))
+        .fold(None, |closest: Option<f32>, distance| Some(closest.map_or(distance, |c| c.min(distance))))
+}
@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use nalgebra_glm::{Vec3, rotate_vec3};
+use lsystems_core::drawing::{DrawingParameters, TurtleCommand};
+use crate::data::Interpretation;
+use crate::rendering::*;
+use crate::rendering::meshes::*;
+use crate::rendering::materials::*;
+
+/// The coordinate frame of the turtle at some point during interpretation.
+#[derive(Clone, Copy)]
+pub struct TurtleFrame {
+    /// Position of the turtle
+    pub position: Vec3,
+    /// The direction the turtle moves in
+    pub heading: Vec3,
+    /// The direction to the left of the turtle
+    pub left: Vec3,
+    /// The direction above the turtle
+    pub up: Vec3
+}
+
+impl TurtleFrame {
+    /// Create the initial turtle frame described by given drawing parameters. The turtle starts out
+    /// in the XY plane, with its heading rotated by the start angle.
+    fn new(params: &DrawingParameters) -> TurtleFrame {
+        let angle = params.start_angle as f32;
+
+        TurtleFrame {
+            position: Vec3::new(params.start_position.x as _, params.start_position.y as _, 0.0),
+            heading: Vec3::new(angle.cos(), angle.sin(), 0.0),
+            left: Vec3::new(-angle.sin(), angle.cos(), 0.0),
+            up: Vec3::z()
+        }
+    }
+
+    /// Rotate the turtle by given angle around given axis. The axis itself is left unchanged.
+    fn rotate(&mut self, angle: f32, axis: &Vec3) {
+        self.heading = rotate_vec3(&self.heading, angle, axis);
+        self.left = rotate_vec3(&self.left, angle, axis);
+        self.up = rotate_vec3(&self.up, angle, axis);
+    }
+}
+
+/// Determine the turtle frames at all save and restore commands by walking the expansion of an lsystem.
+/// lsystems-core does not expose the turtle states it went through, so this replays the turtle commands
+/// the symbols are mapped to. Only commands that move or rotate the turtle are taken into account.
+pub fn collect_turtle_frames<I>(symbols: I, interpretations: &[Interpretation], params: &DrawingParameters) -> Vec<TurtleFrame>
+    where I: Iterator<Item = char> {
+    // Later interpretations for the same symbol replace earlier ones, just like in the interpretation engine
+    let mut commands = HashMap::new();
+    for interpretation in interpretations {
+        if let Some(symbol) = interpretation.symbol {
+            commands.insert(symbol, interpretation.operation);
+        }
+    }
+
+    let delta = params.angle_delta as f32;
+    let step = params.step as f32;
+
+    let mut frames = Vec::new();
+    let mut stack = Vec::new();
+    let mut turtle = TurtleFrame::new(params);
+
+    for symbol in symbols {
+        let command = match commands.get(&symbol) {
+            Some(command) => command,
+            None => continue
+        };
+
+        match command {
+            TurtleCommand::Forward | TurtleCommand::ForwardNoDraw | TurtleCommand::ForwardContracting => {
+                turtle.position += turtle.heading * step;
+            },
+            TurtleCommand::TurnLeft => { let axis = turtle.up; turtle.rotate(delta, &axis) },
+            TurtleCommand::TurnRight => { let axis = turtle.up; turtle.rotate(-delta, &axis) },
+            TurtleCommand::TurnAround => { let axis = turtle.up; turtle.rotate(PI, &axis) },
+            TurtleCommand::PitchDown => { let axis = turtle.left; turtle.rotate(delta, &axis) },
+            TurtleCommand::PitchUp => { let axis = turtle.left; turtle.rotate(-delta, &axis) },
+            TurtleCommand::RollLeft => { let axis = turtle.heading; turtle.rotate(delta, &axis) },
+            TurtleCommand::RollRight => { let axis = turtle.heading; turtle.rotate(-delta, &axis) },
+            TurtleCommand::SaveState => {
+                stack.push(turtle);
+                frames.push(turtle);
+            },
+            TurtleCommand::LoadState => {
+                // Unbalanced restore commands are ignored, like unbalanced brackets in the rules
+                if let Some(saved) = stack.pop() {
+                    turtle = saved;
+                    frames.push(turtle);
+                }
+            },
+            _ => {}
+        }
+    }
+
+    frames
+}
+
+/// Create a mesh showing an axis triad for each of given turtle frames. The heading is drawn in red,
+/// the left direction in green and the up direction in blue.
+pub fn create_turtle_frames_mesh(frames: &[TurtleFrame], axis_length: f32) -> Mesh {
+    let mut vertices = Vec::new();
+
+    for frame in frames {
+        let axes = [
+            (frame.heading, Vec3::new(1.0, 0.0, 0.0)),
+            (frame.left, Vec3::new(0.0, 1.0, 0.0)),
+            (frame.up, Vec3::new(0.0, 0.0, 1.0))
+        ];
+
+        for (direction, color) in &axes {
+            vertices.push(Vertex::new(frame.position, *color));
+            vertices.push(Vertex::new(frame.position + direction * axis_length, *color));
+        }
+    }
+
+    let mut mesh = Mesh::new(PrimitiveType::Lines, Box::new(SimpleMaterial::new()), &BasicGeometry::from_vertices(&vertices));
+    mesh.line_width = 2.0;
+
+    mesh
+}
@@ -1,8 +1,9 @@
 
 use std::rc::Rc;
 use std::cell::RefCell;
-use imgui::Ui;
-use glfw::{Window, WindowEvent};
+use imgui::{Ui, ImString, Window, Condition, im_str};
+use glfw::WindowEvent;
+use crate::data::VSyncMode;
 
 /// Module containg scene that allows rendering and display of a L-System
 pub mod lsystem;
@@ -29,7 +30,7 @@ pub trait Scene {
     fn do_gui(&mut self, ui: &Ui) -> SceneAction;
 
     /// Handle input event. This is only called if the UI does not want to grab input.
-    fn handle_event(&mut self, window: &Window, event: &WindowEvent);
+    fn handle_event(&mut self, window: &glfw::Window, event: &WindowEvent);
 
     /// Handle window resize event.
     fn handle_resize(&mut self, w: u32, h: u32);
@@ -38,36 +39,243 @@ pub trait Scene {
     /// Its purpose is to sneak in some operations that detect things like "just switched back to this scene"
     /// etc.
     fn do_logic(&mut self);
+
+    /// Do logic while another scene is shown on top of this one in the same document. This is called every
+    /// frame before the current scene is borrowed, and allows scenes to react to changes made by the scenes they
+    /// pushed, for example to keep a preview up to date.
+    fn do_background_logic(&mut self) {
+    }
+
+    /// Called when the user tries to close the application window. Returning false cancels the close request,
+    /// in which case the scene is expected to ask the user for confirmation and emit `SceneAction::Quit` once
+    /// the application may actually quit.
+    fn request_close(&mut self) -> bool {
+        true
+    }
+
+    /// Called when the user tries to close the document this scene belongs to. Returning false cancels the
+    /// request, in which case the scene is expected to ask the user for confirmation and emit
+    /// `SceneAction::CloseDocument` once the document may actually be closed.
+    fn request_close_document(&mut self) -> bool {
+        true
+    }
+
+    /// Retrieve the title of the document this scene belongs to, as shown in the document tab bar. Scenes
+    /// returning None keep the title set by the scenes below them.
+    fn title(&self) -> Option<String> {
+        None
+    }
+
+    /// Show or hide the GUI windows of this scene. This is called every frame, before `do_gui`. Scenes
+    /// should keep the main menu bar and any popups asking for user input visible.
+    fn set_gui_visible(&mut self, _visible: bool) {
+    }
+
+    /// Provide the current time in seconds, as reported by glfw. This is called every frame, before `do_logic`.
+    /// Animations should be advanced based on the elapsed time instead of the number of frames, so they play
+    /// at the same speed regardless of framerate.
+    fn set_time(&mut self, _time: f64) {
+    }
 }
 
-/// A struct that manages a stack of scenes.
-pub struct SceneManager {
+/// A single open document. Each document has its own scene stack, with the scene that opened
+/// the document at the bottom.
+struct Document {
     /// Scene stack
-    scenes: Vec<RcCell<dyn Scene>>
+    scenes: Vec<RcCell<dyn Scene>>,
+    /// The title shown in the document tab bar
+    title: String,
+    /// Unique identifier, used to keep the imgui tab state stable when documents are closed
+    id: u32
+}
+
+/// Events caused by user interaction with the document tab bar.
+pub enum DocumentTabEvent {
+    /// The user selected the document with given index
+    Select(usize),
+    /// The user clicked the close button of the document with given index
+    Close(usize)
+}
+
+/// A struct that manages a set of open documents, each consisting of a stack of scenes.
+/// Only the current scene of the active document is shown.
+pub struct SceneManager {
+    /// All open documents
+    documents: Vec<Document>,
+    /// Index of the active document
+    active: usize,
+    /// The identifier given to the next opened document
+    next_id: u32,
+    /// Whether the tab of the active document has to be selected in the tab bar. This is required
+    /// when the active document was changed programmatically.
+    select_active_tab: bool
 }
 
 impl SceneManager {
     /// Create new scene manager instance
     pub fn new() -> SceneManager {
         SceneManager {
-            scenes: Vec::new()
+            documents: Vec::new(),
+            active: 0,
+            next_id: 0,
+            select_active_tab: false
         }
     }
 
-    /// Push given scene on top of the scene stack. It will be the scene rendered from this point on,
-    /// until it either gets popped of the stack, or some other scene gets pushed.
+    /// Push given scene on top of the scene stack of the active document. It will be the scene rendered
+    /// from this point on, until it either gets popped of the stack, or some other scene gets pushed.
+    /// If there is no document yet, a new one is opened.
     pub fn push_scene(&mut self, scene: RcCell<dyn Scene>) {
-        self.scenes.push(scene);
+        if self.documents.is_empty() {
+            self.open_document(scene);
+        } else {
+            self.documents[self.active].scenes.push(scene);
+        }
     }
 
-    /// Remove the topmost scene from the scene stack.
+    /// Remove the topmost scene from the scene stack of the active document.
     pub fn pop_scene(&mut self) {
-        self.scenes.pop().expect("pop called on empty scene stack");
+        self.documents[self.active].scenes.pop().expect("pop called on empty scene stack");
     }
 
-    /// Retrieve the current scene
+    /// Retrieve the current scene of the active document
     pub fn current_scene(&self) -> &RcCell<dyn Scene> {
-        self.scenes.last().expect("current_scene called on empty scene stack")
+        self.documents.get(self.active)
+            .and_then(|document| document.scenes.last())
+            .expect("current_scene called on empty scene stack")
+    }
+
+    /// Perform the background logic of all scenes of the active document that are covered by another scene.
+    pub fn do_background_logic(&self) {
+        if let Some(document) = self.documents.get(self.active) {
+            if let Some((_, covered)) = document.scenes.split_last() {
+                for scene in covered {
+                    scene.borrow_mut().do_background_logic();
+                }
+            }
+        }
+    }
+
+    /// Open a new document with given scene at the bottom of its scene stack, and make it the active document.
+    pub fn open_document(&mut self, scene: RcCell<dyn Scene>) {
+        self.documents.push(Document {
+            scenes: vec![scene],
+            title: String::new(),
+            id: self.next_id
+        });
+
+        self.next_id += 1;
+        self.set_active_document(self.documents.len() - 1);
+    }
+
+    /// Close the active document, including all scenes on its stack. The document before it becomes
+    /// the active one. The last document can't be closed.
+    pub fn close_document(&mut self) {
+        if self.documents.len() > 1 {
+            self.documents.remove(self.active);
+            self.set_active_document(self.active.saturating_sub(1));
+        }
+    }
+
+    /// Make the document with given index the active one.
+    pub fn set_active_document(&mut self, index: usize) {
+        if index < self.documents.len() {
+            self.active = index;
+            self.select_active_tab = true;
+        }
+    }
+
+    /// Retrieve the number of open documents.
+    pub fn document_count(&self) -> usize {
+        self.documents.len()
+    }
+
+    /// Check whether the document with given index has scenes pushed on top of the one that opened it.
+    pub fn has_pushed_scenes(&self, index: usize) -> bool {
+        self.documents.get(index).map_or(false, |document| document.scenes.len() > 1)
+    }
+
+    /// Set the title of the active document, as shown in the document tab bar.
+    pub fn set_document_title(&mut self, title: String) {
+        self.documents[self.active].title = title;
+    }
+
+    /// Ask the current scene of every document whether the application may be closed. The first document
+    /// refusing to do so becomes the active document, so that it can ask the user for confirmation.
+    /// The current scene of the active document must not be borrowed when calling this.
+    pub fn request_close_all(&mut self) -> bool {
+        for i in 0..self.documents.len() {
+            let scene = self.documents[i].scenes.last().expect("document with empty scene stack").clone();
+
+            if !scene.borrow_mut().request_close() {
+                self.set_active_document(i);
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Show a tab bar below the main menu bar that allows switching between the open documents.
+    /// The tab bar is only shown if there is more than one document.
+    pub fn do_document_tabs(&mut self, ui: &Ui) -> Option<DocumentTabEvent> {
+        if self.documents.len() < 2 {
+            return None;
+        }
+
+        let mut event = None;
+        let select_active_tab = self.select_active_tab;
+        self.select_active_tab = false;
+
+        let documents = &self.documents;
+        let active = self.active;
+        let width = ui.io().display_size[0];
+
+        Window::new(im_str!("Documents"))
+            .title_bar(false)
+            .resizable(false)
+            .movable(false)
+            .scroll_bar(false)
+            .position([0.0, 20.0], Condition::Always)
+            .size([width, 36.0], Condition::Always)
+            .build(ui, || {
+                // imgui-rs does not wrap tab bars yet, so the raw bindings are used here
+                unsafe {
+                    if !imgui::sys::igBeginTabBar(im_str!("##document_tabs").as_ptr(), 0) {
+                        return;
+                    }
+
+                    for (i, document) in documents.iter().enumerate() {
+                        // The part after "###" is the tab ID, which keeps the tab stable when its title changes
+                        let label = ImString::new(format!("{}###document{}", document.title, document.id));
+                        let mut open = true;
+
+                        let flags = if select_active_tab && i == active {
+                            imgui::sys::ImGuiTabItemFlags_SetSelected as _
+                        } else {
+                            0
+                        };
+
+                        if imgui::sys::igBeginTabItem(label.as_ptr(), &mut open, flags) {
+                            // Selection changes made by the user are ignored while the active tab is being selected,
+                            // since the tab bar still reports the old selection during that frame.
+                            if i != active && !select_active_tab {
+                                event = Some(DocumentTabEvent::Select(i));
+                            }
+
+                            imgui::sys::igEndTabItem();
+                        }
+
+                        if !open {
+                            event = Some(DocumentTabEvent::Close(i));
+                        }
+                    }
+
+                    imgui::sys::igEndTabBar();
+                }
+            });
+
+        event
     }
 
     /// Process given scene action command object.
@@ -75,6 +283,8 @@ impl SceneManager {
         match action {
             SceneAction::PushScene(scene) => self.push_scene(scene),
             SceneAction::PopScene => self.pop_scene(),
+            SceneAction::OpenDocument(scene) => self.open_document(scene),
+            SceneAction::CloseDocument => self.close_document(),
             _ => {}
         }
     }
@@ -90,5 +300,13 @@ pub enum SceneAction {
     PopScene,
     /// Push given new scene to the scene stack. It will become the new
     /// current scene.
-    PushScene(RcCell<dyn Scene>)
+    PushScene(RcCell<dyn Scene>),
+    /// Close the application window and quit
+    Quit,
+    /// Open a new document with given scene, and make it the active document
+    OpenDocument(RcCell<dyn Scene>),
+    /// Close the active document, including all scenes on its stack
+    CloseDocument,
+    /// Change how buffer swaps are synchronized with the display refresh
+    SetVSyncMode(VSyncMode)
 }
\ No newline at end of file